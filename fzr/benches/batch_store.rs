@@ -0,0 +1,186 @@
+//! Heap allocations made by `store_files`, which reads every file into a pooled buffer, against
+//! storing the same batch with independent `store_file` calls. Measured in allocations per batch
+//! by a counting allocator rather than in time.
+//!
+//! Run with `cargo bench --bench batch_store`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::stream::{self, StreamExt};
+
+use fzr::data::ipfs_client::{IpfsClient, IpfsClientConfig, IpfsClientRef};
+use fzr::data::ipfs_ops::{store_file, store_files};
+use fzr::data::runtime::{self, Arc, RwLock};
+
+const BATCH_SIZES: &[usize] = &[100, 1000];
+
+const FILE_SIZE_BYTES: usize = 4 * 1024;
+
+const CONCURRENCY: usize = 4;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Passes everything through to the system allocator, counting each allocation.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Counts allocations, including reallocations, made between the start and end of a sample.
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> Self::Intermediate {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        ALLOCATIONS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        match *throughput {
+            Throughput::Elements(elements) => {
+                for value in values {
+                    *value /= elements as f64;
+                }
+                "allocs/file"
+            }
+            Throughput::Bytes(bytes) => {
+                for value in values {
+                    *value /= bytes as f64;
+                }
+                "allocs/byte"
+            }
+        }
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+fn in_memory_client() -> IpfsClientRef {
+    let config = IpfsClientConfig {
+        in_memory: true,
+        ..IpfsClientConfig::default()
+    };
+    let client = runtime::block_on(IpfsClient::with_config(config)).unwrap();
+
+    Arc::new(RwLock::new(client))
+}
+
+/// Distinct text files of `FILE_SIZE_BYTES` each.
+fn write_files(dir: &tempfile::TempDir, count: usize) -> Vec<PathBuf> {
+    (0..count)
+        .map(|i| {
+            let path = dir.path().join(format!("{}.txt", i));
+            let line = format!("file {}\n", i);
+            let content: String = line.chars().cycle().take(FILE_SIZE_BYTES).collect();
+            fs::write(&path, content).unwrap();
+            path
+        })
+        .collect()
+}
+
+/// Stores `paths` the way `store_files` did before it pooled buffers.
+async fn store_files_unpooled(paths: Vec<PathBuf>, ipfs_client: IpfsClientRef) {
+    stream::iter(paths)
+        .map(|path| {
+            let ipfs_client = Arc::clone(&ipfs_client);
+            async move {
+                // Sized for the batch report, as `store_files` needed.
+                runtime::fs::metadata(&path).await.unwrap();
+                store_file(path, ipfs_client).await
+            }
+        })
+        .buffer_unordered(CONCURRENCY)
+        .for_each(|result| async move {
+            result.unwrap().unwrap();
+        })
+        .await;
+}
+
+fn bench_batch_store(c: &mut Criterion<Allocations>) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut group = c.benchmark_group("store_files");
+    group.sample_size(10);
+
+    for &batch_size in BATCH_SIZES {
+        let paths = write_files(&dir, batch_size);
+        let client_ref = in_memory_client();
+        group.throughput(Throughput::Elements(batch_size as u64));
+
+        group.bench_function(BenchmarkId::new("pooled", batch_size), |b| {
+            b.iter(|| {
+                runtime::block_on(store_files(paths.clone(), client_ref.clone(), CONCURRENCY))
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("unpooled", batch_size), |b| {
+            b.iter(|| runtime::block_on(store_files_unpooled(paths.clone(), client_ref.clone())))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(Allocations);
+    targets = bench_batch_store
+}
+criterion_main!(benches);
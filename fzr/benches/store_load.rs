@@ -0,0 +1,173 @@
+//! Throughput of `store_file` and `load_file` across content types and sizes, against an
+//! in-memory client so serialization and hashing dominate rather than disk I/O.
+//!
+//! Run with `cargo bench`, or `cargo bench -- store/image` to pick out one group.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use tempfile::TempDir;
+
+use fzr::data::ipfs_client::{IpfsClient, IpfsClientConfig, IpfsClientRef};
+use fzr::data::ipfs_ops::{load_file, store_file};
+use fzr::data::runtime::{self, Arc, RwLock};
+
+const SIZES: &[(&str, usize)] = &[
+    ("1KiB", 1024),
+    ("1MiB", 1024 * 1024),
+    ("16MiB", 16 * 1024 * 1024),
+];
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog.\n";
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Image,
+    Binary,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Text => "text",
+            Kind::Image => "image",
+            Kind::Binary => "binary",
+        }
+    }
+
+    /// Roughly `size` bytes of content of this kind.
+    fn content(self, size: usize) -> Vec<u8> {
+        match self {
+            Kind::Text => TEXT.bytes().cycle().take(size).collect(),
+            // Noise doesn't compress, so the encoded PNG comes out close to its pixel data size.
+            Kind::Image => {
+                let side = ((size / 3) as f64).sqrt().max(1.0) as u32;
+                let pixels = noise(side as usize * side as usize * 3);
+                let image = RgbImage::from_raw(side, side, pixels).unwrap();
+
+                let mut png = Cursor::new(Vec::new());
+                DynamicImage::ImageRgb8(image)
+                    .write_to(&mut png, ImageFormat::Png)
+                    .unwrap();
+                png.into_inner()
+            }
+            // A leading NUL and 0xff keep it from being read as UTF-8 or sniffed as a known type.
+            Kind::Binary => {
+                let mut bytes = vec![0x00, 0xff];
+                bytes.extend(noise(size.saturating_sub(2)));
+                bytes
+            }
+        }
+    }
+}
+
+/// Deterministic pseudo-random bytes, so every run benchmarks the same content.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn in_memory_client() -> IpfsClientRef {
+    let config = IpfsClientConfig {
+        in_memory: true,
+        ..IpfsClientConfig::default()
+    };
+    let client = runtime::block_on(IpfsClient::with_config(config)).unwrap();
+
+    Arc::new(RwLock::new(client))
+}
+
+fn write_content(dir: &TempDir, kind: Kind, size_name: &str, size: usize) -> (PathBuf, u64) {
+    let path = dir.path().join(format!("{}-{}", kind.name(), size_name));
+    let content = kind.content(size);
+    fs::write(&path, &content).unwrap();
+
+    (path, content.len() as u64)
+}
+
+/// Times `iters` calls of `f`, all inside one runtime so starting it isn't measured.
+fn time_iters<F, Fut>(iters: u64, f: F) -> Duration
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    runtime::block_on(async {
+        let start = Instant::now();
+        for _ in 0..iters {
+            f().await;
+        }
+        start.elapsed()
+    })
+}
+
+fn bench_store(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+
+    for kind in [Kind::Text, Kind::Image, Kind::Binary] {
+        let mut group = c.benchmark_group(format!("store/{}", kind.name()));
+        group.sample_size(10);
+
+        for &(size_name, size) in SIZES {
+            let (path, len) = write_content(&dir, kind, size_name, size);
+            let client_ref = in_memory_client();
+            group.throughput(Throughput::Bytes(len));
+
+            group.bench_function(BenchmarkId::from_parameter(size_name), |b| {
+                b.iter_custom(|iters| {
+                    time_iters(iters, || async {
+                        store_file(path.clone(), client_ref.clone())
+                            .await
+                            .unwrap()
+                            .unwrap();
+                    })
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn bench_load(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+
+    for kind in [Kind::Text, Kind::Image, Kind::Binary] {
+        let mut group = c.benchmark_group(format!("load/{}", kind.name()));
+        group.sample_size(10);
+
+        for &(size_name, size) in SIZES {
+            let (path, len) = write_content(&dir, kind, size_name, size);
+            let client_ref = in_memory_client();
+            let cid = runtime::block_on(store_file(path, client_ref.clone()))
+                .unwrap()
+                .unwrap();
+            group.throughput(Throughput::Bytes(len));
+
+            group.bench_function(BenchmarkId::from_parameter(size_name), |b| {
+                b.iter_custom(|iters| {
+                    time_iters(iters, || async {
+                        load_file(cid, client_ref.clone()).await.unwrap();
+                    })
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_store, bench_load);
+criterion_main!(benches);
@@ -12,6 +12,7 @@ use log::{error, info};
 use std::path::PathBuf;
 
 use Fuser::{
+    data::content_cid::ContentCid,
     data::fs_ops::walk_dir,
     data::ipfs_client::{IpfsClient, IpfsClientRef},
     data::ipfs_ops::load_file,
@@ -164,12 +165,16 @@ impl Application for Fuser {
                 }
                 Message::ViewPageLoadContent => {
                     let cid_string = self.pages.view.input_value.clone();
-                    match self.ipfs_client.clone() {
-                        Some(ipfs_client) => Command::perform(
-                            load_file(cid_string, ipfs_client),
+                    match (self.ipfs_client.clone(), cid_string.parse::<ContentCid>()) {
+                        (Some(ipfs_client), Ok(cid)) => Command::perform(
+                            load_file(cid, ipfs_client),
                             Message::ViewPageContentLoaded,
                         ),
-                        None => Command::none(),
+                        (_, Err(err)) => {
+                            error!("Invalid CID {:?}: {}", cid_string, err);
+                            Command::none()
+                        }
+                        (None, _) => Command::none(),
                     }
                 }
                 Message::ThemeChanged(theme) => {
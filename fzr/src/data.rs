@@ -1,5 +1,28 @@
+pub mod animation;
+pub mod audio;
+pub mod block_db;
+pub mod cached_client;
+pub mod car;
+pub mod classify;
 pub mod content;
+pub mod content_cid;
+pub mod crypto;
+pub mod csv;
+pub mod error;
+pub mod exif_ops;
+pub mod font;
 pub mod fs_ops;
+pub mod gateway;
+pub mod heic;
+pub mod http_api;
+pub mod images;
 pub mod ipfs_client;
 pub mod ipfs_ops;
-pub mod thumbnails;
\ No newline at end of file
+pub mod markdown;
+pub mod meta;
+pub mod pdf;
+pub mod retry;
+pub mod runtime;
+pub mod svg;
+pub mod text_ops;
+pub mod video;
\ No newline at end of file
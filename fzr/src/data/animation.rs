@@ -0,0 +1,147 @@
+use std::convert::TryInto;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Reads `(frame_count, loop_count)` from an animated GIF, APNG or animated WebP, or returns
+/// `None` for still images. The loop count is as declared by the file, where 0 means forever, and
+/// is `None` for GIFs that don't declare one and so play once.
+pub fn animation_info(buffer: &[u8]) -> Option<(u32, Option<u32>)> {
+    let (frame_count, loop_count) = if buffer.starts_with(b"GIF8") {
+        gif_animation(buffer)
+    } else if buffer.starts_with(PNG_SIGNATURE) {
+        apng_animation(buffer)?
+    } else if buffer.len() >= 12 && &buffer[0..4] == b"RIFF" && &buffer[8..12] == b"WEBP" {
+        webp_animation(buffer)
+    } else {
+        return None;
+    };
+
+    if frame_count > 1 {
+        Some((frame_count, loop_count))
+    } else {
+        None
+    }
+}
+
+/// Counts image descriptors and reads the NETSCAPE2.0 loop extension, stopping early at the end
+/// of a truncated file.
+fn gif_animation(buffer: &[u8]) -> (u32, Option<u32>) {
+    let mut frame_count = 0;
+    let mut loop_count = None;
+
+    // Header and logical screen descriptor, then the global color table if there is one.
+    let mut pos = 13;
+    if let Some(&packed) = buffer.get(10) {
+        pos += color_table_len(packed);
+    }
+
+    while let Some(&separator) = buffer.get(pos) {
+        match separator {
+            // Extension: a label, then data sub-blocks.
+            0x21 => {
+                let label = buffer.get(pos + 1).copied();
+                pos += 2;
+
+                if label == Some(0xff) && buffer.get(pos..pos + 12) == Some(b"\x0bNETSCAPE2.0") {
+                    if let Some([3, 1, low, high]) = buffer.get(pos + 12..pos + 16) {
+                        loop_count = Some(u16::from_le_bytes([*low, *high]) as u32);
+                    }
+                }
+
+                pos = match skip_sub_blocks(buffer, pos) {
+                    Some(pos) => pos,
+                    None => break,
+                };
+            }
+            // Image descriptor, then an optional local color table, the LZW code size and the
+            // image data sub-blocks.
+            0x2c => {
+                frame_count += 1;
+
+                let packed = match buffer.get(pos + 9) {
+                    Some(&packed) => packed,
+                    None => break,
+                };
+                pos += 10 + color_table_len(packed) + 1;
+
+                pos = match skip_sub_blocks(buffer, pos) {
+                    Some(pos) => pos,
+                    None => break,
+                };
+            }
+            _ => break,
+        }
+    }
+
+    (frame_count, loop_count)
+}
+
+/// Length of the color table flagged in a GIF screen or image descriptor's packed byte.
+fn color_table_len(packed: u8) -> usize {
+    if packed & 0x80 == 0 {
+        0
+    } else {
+        3 << ((packed & 0x07) + 1)
+    }
+}
+
+/// Returns the position just past a run of GIF data sub-blocks and their terminator.
+fn skip_sub_blocks(buffer: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *buffer.get(pos)? as usize;
+        pos += 1 + len;
+
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Reads the `acTL` chunk, which an APNG must have before its image data.
+fn apng_animation(buffer: &[u8]) -> Option<(u32, Option<u32>)> {
+    let mut pos = PNG_SIGNATURE.len();
+
+    while let Some(header) = buffer.get(pos..pos + 8) {
+        let len = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let data = buffer.get(pos + 8..pos + 8 + len)?;
+
+        match &header[4..8] {
+            b"acTL" if len >= 8 => {
+                let frame_count = u32::from_be_bytes(data[0..4].try_into().unwrap());
+                let loop_count = u32::from_be_bytes(data[4..8].try_into().unwrap());
+
+                return Some((frame_count, Some(loop_count)));
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => pos += 8 + len + 4, // Length and type, data, then the CRC
+        }
+    }
+
+    None
+}
+
+/// Counts `ANMF` frame chunks and reads the loop count from the `ANIM` chunk.
+fn webp_animation(buffer: &[u8]) -> (u32, Option<u32>) {
+    let mut frame_count = 0;
+    let mut loop_count = None;
+    let mut pos = 12;
+
+    while let Some(header) = buffer.get(pos..pos + 8) {
+        let len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+
+        match &header[0..4] {
+            b"ANIM" => {
+                if let Some([low, high]) = buffer.get(pos + 12..pos + 14) {
+                    loop_count = Some(u16::from_le_bytes([*low, *high]) as u32);
+                }
+            }
+            b"ANMF" => frame_count += 1,
+            _ => {}
+        }
+
+        // Chunks are padded to an even length.
+        pos += 8 + len + (len & 1);
+    }
+
+    (frame_count, loop_count)
+}
\ No newline at end of file
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use std::io::Cursor;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Audio MIME types (as reported by `infer`) that can be probed.
+const AUDIO_MIME_TYPES: [&str; 4] = ["audio/mpeg", "audio/x-flac", "audio/ogg", "audio/x-wav"];
+
+pub fn is_audio(mime_type: &str) -> bool {
+    AUDIO_MIME_TYPES.contains(&mime_type)
+}
+
+/// Reads `(duration_ms, sample_rate_hz, channels)` from the header of an in-memory audio file.
+pub fn audio_properties(buffer: &[u8]) -> Result<(u64, u32, u16)> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(buffer.to_vec())), Default::default());
+    let probed = symphonia::default::get_probe().format(
+        &Hint::new(),
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let track = probed
+        .format
+        .default_track()
+        .ok_or_else(|| anyhow!("No audio track found"))?;
+    let params = &track.codec_params;
+
+    let sample_rate_hz = params
+        .sample_rate
+        .ok_or_else(|| anyhow!("Audio track has no sample rate"))?;
+    let channels = params
+        .channels
+        .map_or(0, |channels| channels.count() as u16);
+    let duration_ms = params
+        .n_frames
+        .map_or(0, |frames| frames * 1000 / sample_rate_hz as u64);
+
+    Ok((duration_ms, sample_rate_hz, channels))
+}
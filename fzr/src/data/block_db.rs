@@ -0,0 +1,91 @@
+//! Direct access to the embedded store's SQLite database, for deleting single blocks. ipfs-embed
+//! only deletes blocks through its collector, which keeps every unpinned block until the store
+//! outgrows its cache limits, so `IpfsClient::remove` and `IpfsClient::gc` delete rows
+//! themselves. The schema is ipfs-sqlite-block-store's, which ipfs-embed is pinned to a version
+//! of.
+
+use anyhow::{Context, Result};
+use libipld::Cid;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::data::runtime;
+
+/// The database file of an embedded store. For an in-memory client the file lives in a temporary
+/// directory, removed once the last clone of the client is dropped.
+pub(crate) struct BlockDatabase {
+    path: PathBuf,
+    _temp_dir: Option<TempDir>,
+}
+
+impl BlockDatabase {
+    pub(crate) fn new(path: PathBuf) -> BlockDatabase {
+        BlockDatabase {
+            path,
+            _temp_dir: None,
+        }
+    }
+
+    /// A database in a fresh temporary directory.
+    pub(crate) fn temporary() -> Result<BlockDatabase> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("fzr-store")
+            .tempdir()
+            .context("Failed to create a temporary store")?;
+
+        Ok(BlockDatabase {
+            path: temp_dir.path().join("sqlite"),
+            _temp_dir: Some(temp_dir),
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Deletes the given blocks' data, returning the size of each one that was stored. Their
+    /// links go too, as if they'd never been received, so the blocks they linked to are only
+    /// kept by whatever else links to them. The store's block count and size are updated to
+    /// match, since its collector works from them.
+    pub(crate) async fn delete_blocks(&self, cids: &[Cid]) -> Result<Vec<(Cid, u64)>> {
+        let path = self.path.clone();
+        let cids = cids.to_vec();
+
+        runtime::spawn_blocking(move || delete_blocks(&path, &cids)).await
+    }
+}
+
+fn delete_blocks(path: &Path, cids: &[Cid]) -> Result<Vec<(Cid, u64)>> {
+    let mut connection = Connection::open(path)
+        .with_context(|| format!("Failed to open block store: {}", path.display()))?;
+    // Take the write lock up front, so the collector can't change the rows read below.
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let mut deleted = Vec::new();
+    {
+        let mut find = transaction.prepare(
+            "SELECT id, LENGTH(block) FROM cids INNER JOIN blocks ON id = block_id WHERE cid = ?",
+        )?;
+        let mut delete_block = transaction.prepare("DELETE FROM blocks WHERE block_id = ?")?;
+        let mut delete_links = transaction.prepare("DELETE FROM refs WHERE parent_id = ?")?;
+        let mut update_stats =
+            transaction.prepare("UPDATE stats SET count = count - 1, size = size - ?")?;
+
+        for cid in cids {
+            let row: Option<(i64, i64)> = find
+                .query_row([cid.to_bytes()], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+
+            if let Some((id, size_bytes)) = row {
+                delete_block.execute([id])?;
+                delete_links.execute([id])?;
+                update_stats.execute([size_bytes])?;
+                deleted.push((*cid, size_bytes as u64));
+            }
+        }
+    }
+    transaction.commit()?;
+
+    Ok(deleted)
+}
\ No newline at end of file
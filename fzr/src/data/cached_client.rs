@@ -0,0 +1,173 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Error;
+use libipld::{Cid, Result};
+use lru::LruCache;
+
+use crate::data::content::ContentItemBlock;
+use crate::data::ipfs_client::IpfsClient;
+use crate::data::runtime::Arc;
+
+/// Wraps an [`IpfsClient`] with an in-memory LRU cache of decoded blocks, bounded by the total
+/// content size of the cached blocks.
+pub struct CachedIpfsClient {
+    client: IpfsClient,
+    cache: Mutex<BlockCache>,
+    misses: AtomicUsize,
+}
+
+struct BlockCache {
+    blocks: LruCache<Cid, ContentItemBlock>,
+    capacity_bytes: u64,
+    size_bytes: u64,
+}
+
+impl CachedIpfsClient {
+    pub fn new(client: IpfsClient, capacity_bytes: u64) -> Self {
+        CachedIpfsClient {
+            client,
+            cache: Mutex::new(BlockCache {
+                blocks: LruCache::unbounded(),
+                capacity_bytes,
+                size_bytes: 0,
+            }),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The wrapped client, for operations that don't go through the cache.
+    pub fn client(&self) -> &IpfsClient {
+        &self.client
+    }
+
+    /// Number of `get` calls that weren't served from the cache.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub async fn get(&self, cid: &Cid) -> Result<ContentItemBlock, Arc<Error>> {
+        if let Some(block) = self.cache.lock().unwrap().blocks.get(cid) {
+            return Ok(block.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let block = self.client.get(cid).await?;
+        self.cache.lock().unwrap().insert(*cid, block.clone());
+
+        Ok(block)
+    }
+
+    pub async fn remove(&self, cid: &Cid) -> Result<bool, Arc<Error>> {
+        self.cache.lock().unwrap().remove(cid);
+
+        self.client.remove(cid).await
+    }
+}
+
+impl BlockCache {
+    fn insert(&mut self, cid: Cid, block: ContentItemBlock) {
+        // Blocks larger than the whole cache would only evict everything else.
+        if block.size_bytes > self.capacity_bytes {
+            return;
+        }
+
+        self.size_bytes += block.size_bytes;
+        if let Some(replaced) = self.blocks.put(cid, block) {
+            self.size_bytes -= replaced.size_bytes;
+        }
+
+        while self.size_bytes > self.capacity_bytes {
+            match self.blocks.pop_lru() {
+                Some((_, evicted)) => self.size_bytes -= evicted.size_bytes,
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, cid: &Cid) {
+        if let Some(removed) = self.blocks.pop(cid) {
+            self.size_bytes -= removed.size_bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::content::{
+        Compression, ContentItem, Fingerprint, LineEndings, TextContent, TextMetadata,
+    };
+    use crate::data::ipfs_client::IpfsClientConfig;
+    use crate::data::runtime;
+
+    use std::error::Error;
+
+    fn text_block(string: &str) -> ContentItemBlock {
+        let size_bytes = string.len() as u64;
+
+        ContentItemBlock {
+            content: ContentItem::Text(
+                TextContent {
+                    string: string.into(),
+                },
+                TextMetadata {
+                    size_bytes,
+                    fingerprint: Fingerprint::of(string.as_bytes()),
+                    detected_encoding: None,
+                    char_count: string.chars().count() as u64,
+                    word_count: string.split_whitespace().count() as u64,
+                    line_endings: LineEndings::None,
+                    line_endings_normalized: false,
+                    compression: Compression::None,
+                    compressed_size_bytes: None,
+                },
+            ),
+            size_bytes,
+            metadata: Vec::new(),
+            metadata_cid: None,
+            previous_version: None,
+            transcoded_cid: None,
+            thumbnail_cid: None,
+            created_at: None,
+        }
+    }
+
+    #[runtime::test]
+    async fn test_cached_get() -> Result<(), Box<dyn Error>> {
+        let config = IpfsClientConfig {
+            in_memory: true,
+            ..IpfsClientConfig::default()
+        };
+        let client = IpfsClient::with_config(config).await.unwrap();
+        let block = text_block("cached content");
+        let cid = client.add(&block).await.unwrap();
+
+        let cached = CachedIpfsClient::new(client, 1024);
+        assert_eq!(block, cached.get(&cid).await.unwrap());
+        assert_eq!(block, cached.get(&cid).await.unwrap());
+        assert_eq!(1, cached.misses());
+
+        assert!(cached.remove(&cid).await.unwrap());
+        assert!(cached.get(&cid).await.is_err());
+        assert_eq!(2, cached.misses());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_cache_evicts_least_recently_used() -> Result<(), Box<dyn Error>> {
+        let client = IpfsClient::new().await.unwrap();
+        let first = client.add(&text_block("first block")).await.unwrap();
+        let second = client.add(&text_block("second block")).await.unwrap();
+
+        // Room for only one of the two blocks.
+        let cached = CachedIpfsClient::new(client, 12);
+        cached.get(&first).await.unwrap();
+        cached.get(&second).await.unwrap();
+        cached.get(&first).await.unwrap();
+        assert_eq!(3, cached.misses());
+
+        Ok(())
+    }
+}
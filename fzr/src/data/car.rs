@@ -0,0 +1,118 @@
+//! Minimal CARv1 (Content Addressable aRchive) framing: a DagCbor header listing the root CIDs,
+//! followed by varint length-prefixed `cid || data` sections, one per block.
+
+use anyhow::{anyhow, Context, Result};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::{Cid, DagCbor};
+use std::io::Cursor;
+
+pub const CAR_VERSION: u64 = 1;
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct CarHeader {
+    pub roots: Vec<Cid>,
+    pub version: u64,
+}
+
+pub async fn write_header<W: AsyncWrite + Unpin>(writer: &mut W, roots: &[Cid]) -> Result<()> {
+    let header = CarHeader {
+        roots: roots.to_vec(),
+        version: CAR_VERSION,
+    };
+    let header_bytes = DagCborCodec.encode(&header)?;
+
+    write_section(writer, &[&header_bytes]).await
+}
+
+pub async fn write_block<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    cid: &Cid,
+    data: &[u8],
+) -> Result<()> {
+    let cid_bytes = cid.to_bytes();
+
+    write_section(writer, &[&cid_bytes, data]).await
+}
+
+async fn write_section<W: AsyncWrite + Unpin>(writer: &mut W, parts: &[&[u8]]) -> Result<()> {
+    let len = parts.iter().map(|part| part.len()).sum();
+    let mut len_buffer = unsigned_varint::encode::usize_buffer();
+
+    writer
+        .write_all(unsigned_varint::encode::usize(len, &mut len_buffer))
+        .await?;
+
+    for part in parts {
+        writer.write_all(part).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> Result<CarHeader> {
+    let header_bytes = read_section(reader)
+        .await?
+        .ok_or_else(|| anyhow!("CAR stream is empty"))?;
+    let header: CarHeader = DagCborCodec
+        .decode(&header_bytes)
+        .context("Failed to decode CAR header")?;
+
+    if header.version != CAR_VERSION {
+        return Err(anyhow!("Unsupported CAR version: {}", header.version));
+    }
+
+    Ok(header)
+}
+
+/// Reads the next `(cid, data)` block, returning `None` once the stream is exhausted.
+pub async fn read_block<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<(Cid, Vec<u8>)>> {
+    match read_section(reader).await? {
+        Some(section) => {
+            let mut cursor = Cursor::new(&section);
+            let cid = Cid::read_bytes(&mut cursor).context("Failed to read block CID")?;
+            let data = section[cursor.position() as usize..].to_vec();
+
+            Ok(Some((cid, data)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reads a varint length-prefixed section, returning `None` at a clean end of stream.
+async fn read_section<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len_buffer = unsigned_varint::encode::usize_buffer();
+    let mut len_bytes = 0;
+
+    loop {
+        let mut byte = [0; 1];
+
+        if reader.read(&mut byte).await? == 0 {
+            if len_bytes == 0 {
+                return Ok(None);
+            }
+            return Err(anyhow!("CAR stream ended inside a section length"));
+        }
+
+        if len_bytes == len_buffer.len() {
+            return Err(anyhow!("CAR section length is too long"));
+        }
+
+        len_buffer[len_bytes] = byte[0];
+        len_bytes += 1;
+
+        if unsigned_varint::decode::is_last(byte[0]) {
+            break;
+        }
+    }
+
+    let (len, _) = unsigned_varint::decode::usize(&len_buffer[..len_bytes])?;
+    let mut section = vec![0; len];
+    reader
+        .read_exact(&mut section)
+        .await
+        .context("CAR stream ended inside a section")?;
+
+    Ok(Some(section))
+}
@@ -0,0 +1,558 @@
+use anyhow::Context;
+use image::io::Reader as ImageReader;
+use std::convert::TryFrom;
+use std::io::Cursor;
+
+use crate::data::animation;
+use crate::data::audio;
+use crate::data::content::{
+    AudioContent, AudioMetadata, BinaryContent, BinaryMetadata, Compression, ContentItem,
+    CsvContent, CsvMetadata, DocumentContent, DocumentMetadata, Fingerprint, FontContent,
+    FontMetadata, ImageContent, ImageMetadata, JsonContent, JsonKind, JsonMetadata,
+    MarkdownContent, MarkdownMetadata, TextContent, TextMetadata, VectorContent, VectorMetadata,
+    VideoContent, VideoMetadata,
+};
+use crate::data::csv;
+use crate::data::error::FzrError;
+use crate::data::exif_ops;
+use crate::data::font;
+use crate::data::heic;
+use crate::data::markdown;
+use crate::data::pdf;
+use crate::data::svg;
+use crate::data::text_ops;
+use crate::data::video;
+
+/// Number of leading bytes inspected for magic number detection.
+const MAGIC_NUMBER_LEN: usize = 4;
+
+/// MIME type used for binary content that `infer` doesn't recognize.
+const DEFAULT_BINARY_MIME_TYPE: &str = "application/octet-stream";
+
+/// How an in-memory buffer is described in error messages.
+const BUFFER_SOURCE: &str = "buffer";
+
+/// Classifies an in-memory buffer the same way `store_file` classifies a file's contents,
+/// without storing it.
+pub fn classify_bytes(buffer: &[u8]) -> Result<ContentItem, FzrError> {
+    classify(buffer, BUFFER_SOURCE, None)
+}
+
+impl TryFrom<&[u8]> for ContentItem {
+    type Error = FzrError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        classify_bytes(buffer)
+    }
+}
+
+/// Classifies content as the type a caller supplies, or else detects images, video, audio, PDFs and
+/// fonts by their magic numbers, falling back to text, then binary. `source` describes where the
+/// content came from in error messages.
+pub(crate) fn classify(
+    buffer: &[u8],
+    source: &str,
+    content_type: Option<&str>,
+) -> Result<ContentItem, FzrError> {
+    let size_bytes = buffer.len() as u64;
+    let fingerprint = Fingerprint::of(buffer);
+
+    match content_type {
+        Some(content_type) => typed_item(buffer, size_bytes, fingerprint, content_type, source),
+        None => sniffed_item(buffer, size_bytes, fingerprint, source),
+    }
+}
+
+fn sniffed_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let mime_type = infer::get(buffer).map(|kind| kind.mime_type());
+
+    if buffer.len() >= MAGIC_NUMBER_LEN && infer::is_image(&buffer[0..MAGIC_NUMBER_LEN]) {
+        let mime_type = infer::get(&buffer[0..MAGIC_NUMBER_LEN])
+            .unwrap()
+            .mime_type()
+            .to_string();
+
+        image_item(buffer, size_bytes, fingerprint, mime_type, source)
+    } else if let Some(mime_type) =
+        mime_type.filter(|mime_type| heic::HEIC_SUPPORTED && heic::is_heic(mime_type))
+    {
+        heic_item(buffer, size_bytes, fingerprint, mime_type, source)
+    } else if let Some(mime_type) = mime_type.filter(|mime_type| video::is_video(mime_type)) {
+        video_item(buffer, size_bytes, fingerprint, mime_type, source)
+    } else if let Some(mime_type) = mime_type.filter(|mime_type| audio::is_audio(mime_type)) {
+        audio_item(buffer, size_bytes, fingerprint, mime_type, source)
+    } else if mime_type == Some(pdf::PDF_MIME_TYPE) {
+        Ok(document_item(buffer, size_bytes, fingerprint))
+    } else if matches!(mime_type, Some(mime_type) if font::is_font(mime_type)) {
+        font_item(buffer, size_bytes, fingerprint, source)
+    } else {
+        let item = match std::str::from_utf8(buffer).map(str::to_owned) {
+            Ok(markup) if svg::is_svg(&markup) => vector_item(markup, size_bytes, fingerprint),
+            Ok(string) => match text_ops::json_shape(&string) {
+                Some((kind, top_level_count)) => {
+                    json_item(string, size_bytes, fingerprint, kind, top_level_count)
+                }
+                None => match csv::sniff(&string) {
+                    Some(table) => csv_item(string, size_bytes, fingerprint, table),
+                    None => text_item(string, size_bytes, fingerprint, None),
+                },
+            },
+            Err(_) => match text_ops::decode_non_utf8(buffer) {
+                // Text in a legacy encoding is transcoded so it's always stored as UTF-8.
+                Some((string, encoding)) => {
+                    text_item(string, size_bytes, fingerprint, Some(encoding))
+                }
+                // Not text, so store the raw bytes as-is.
+                None => binary_item(
+                    buffer,
+                    size_bytes,
+                    fingerprint,
+                    mime_type.unwrap_or(DEFAULT_BINARY_MIME_TYPE).to_string(),
+                ),
+            },
+        };
+
+        Ok(item)
+    }
+}
+
+/// Builds the content item a caller-supplied MIME type calls for, without sniffing. Types with
+/// no variant of their own are stored as binary under that type.
+fn typed_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    content_type: &str,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+    if essence == svg::SVG_MIME_TYPE {
+        let markup = std::str::from_utf8(buffer)
+            .map(str::to_owned)
+            .with_context(|| format!("Expected SVG markup at {}", source))
+            .map_err(FzrError::decode)?;
+
+        Ok(vector_item(markup, size_bytes, fingerprint))
+    } else if heic::is_heic(essence) {
+        if heic::HEIC_SUPPORTED {
+            heic_item(buffer, size_bytes, fingerprint, essence, source)
+        } else {
+            Ok(binary_item(
+                buffer,
+                size_bytes,
+                fingerprint,
+                essence.to_string(),
+            ))
+        }
+    } else if essence.starts_with("image/") {
+        image_item(buffer, size_bytes, fingerprint, essence.to_string(), source)
+    } else if video::is_video(essence) {
+        video_item(buffer, size_bytes, fingerprint, essence, source)
+    } else if audio::is_audio(essence) {
+        audio_item(buffer, size_bytes, fingerprint, essence, source)
+    } else if essence == pdf::PDF_MIME_TYPE {
+        Ok(document_item(buffer, size_bytes, fingerprint))
+    } else if font::is_font(essence) {
+        font_item(buffer, size_bytes, fingerprint, source)
+    } else if essence == text_ops::JSON_MIME_TYPE || essence.starts_with("text/") {
+        let string = match std::str::from_utf8(buffer) {
+            Ok(string) => string.to_owned(),
+            Err(_) => {
+                return match text_ops::decode_non_utf8(buffer) {
+                    Some((string, encoding)) => {
+                        Ok(text_item(string, size_bytes, fingerprint, Some(encoding)))
+                    }
+                    None => Err(FzrError::UnsupportedFormat(format!(
+                        "Expected {} text but found binary content at {}",
+                        essence, source
+                    ))),
+                };
+            }
+        };
+
+        if markdown::is_markdown(essence) {
+            return Ok(markdown_item(string, size_bytes, fingerprint));
+        }
+
+        if csv::is_csv(essence) {
+            let table = csv::read_table(&string);
+            return Ok(csv_item(string, size_bytes, fingerprint, table));
+        }
+
+        match text_ops::json_shape(&string).filter(|_| essence == text_ops::JSON_MIME_TYPE) {
+            Some((kind, top_level_count)) => Ok(json_item(
+                string,
+                size_bytes,
+                fingerprint,
+                kind,
+                top_level_count,
+            )),
+            None => Ok(text_item(string, size_bytes, fingerprint, None)),
+        }
+    } else {
+        Ok(binary_item(
+            buffer,
+            size_bytes,
+            fingerprint,
+            content_type.to_string(),
+        ))
+    }
+}
+
+fn image_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    mime_type: String,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let (width_px, height_px) = ImageReader::new(Cursor::new(buffer))
+        .with_guessed_format()
+        .with_context(|| format!("Failed to guess image format at {}", source))
+        .map_err(FzrError::decode)?
+        .into_dimensions()
+        .with_context(|| format!("Failed to read image dimensions at {}", source))
+        .map_err(FzrError::decode)?;
+
+    let exif = if exif_ops::has_exif_support(&mime_type) {
+        exif_ops::read_exif(buffer)
+    } else {
+        None
+    };
+
+    let (frame_count, loop_count) = match animation::animation_info(buffer) {
+        Some((frame_count, loop_count)) => (Some(frame_count), loop_count),
+        None => (None, None),
+    };
+
+    let buffer = Box::from(buffer);
+
+    Ok(ContentItem::Image(
+        ImageContent { buffer },
+        ImageMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type,
+            width_px,
+            height_px,
+            exif,
+            phash: None,
+            dominant_colors: Vec::new(),
+            blurhash: None,
+            frame_count,
+            loop_count,
+            original_format: None,
+        },
+    ))
+}
+
+/// Like `image_item`, but reads the dimensions with libheif, since `image` can't decode HEIC.
+fn heic_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    mime_type: &str,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let (width_px, height_px) = heic::heic_dimensions(buffer)
+        .with_context(|| format!("Failed to read HEIC dimensions at {}", source))
+        .map_err(FzrError::decode)?;
+    let mime_type = mime_type.to_string();
+
+    let buffer = Box::from(buffer);
+
+    Ok(ContentItem::Image(
+        ImageContent { buffer },
+        ImageMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type,
+            width_px,
+            height_px,
+            exif: None,
+            phash: None,
+            dominant_colors: Vec::new(),
+            blurhash: None,
+            frame_count: None,
+            loop_count: None,
+            original_format: None,
+        },
+    ))
+}
+
+fn video_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    mime_type: &str,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let (width_px, height_px, duration_ms) = video::video_dimensions(buffer, mime_type)
+        .with_context(|| format!("Failed to probe video at {}", source))
+        .map_err(FzrError::decode)?;
+    let mime_type = mime_type.to_string();
+
+    let buffer = Box::from(buffer);
+
+    Ok(ContentItem::Video(
+        VideoContent { buffer },
+        VideoMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type,
+            width_px,
+            height_px,
+            duration_ms,
+        },
+    ))
+}
+
+fn audio_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    mime_type: &str,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let (duration_ms, sample_rate_hz, channels) = audio::audio_properties(buffer)
+        .with_context(|| format!("Failed to probe audio at {}", source))
+        .map_err(FzrError::decode)?;
+    let mime_type = mime_type.to_string();
+
+    let buffer = Box::from(buffer);
+
+    Ok(ContentItem::Audio(
+        AudioContent { buffer },
+        AudioMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type,
+            duration_ms,
+            sample_rate_hz,
+            channels,
+        },
+    ))
+}
+
+fn binary_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    mime_type: String,
+) -> ContentItem {
+    let buffer = Box::from(buffer);
+
+    ContentItem::Binary(
+        BinaryContent { buffer },
+        BinaryMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type,
+        },
+    )
+}
+
+fn document_item(buffer: &[u8], size_bytes: u64, fingerprint: Fingerprint) -> ContentItem {
+    let (page_count, title, author) = pdf::pdf_info(buffer);
+
+    let buffer = Box::from(buffer);
+
+    ContentItem::Document(
+        DocumentContent { buffer },
+        DocumentMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type: pdf::PDF_MIME_TYPE.to_string(),
+            page_count,
+            title,
+            author,
+        },
+    )
+}
+
+fn font_item(
+    buffer: &[u8],
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    source: &str,
+) -> Result<ContentItem, FzrError> {
+    let (format, family_name, style) = font::font_info(buffer)
+        .with_context(|| format!("Failed to read font at {}", source))
+        .map_err(FzrError::decode)?;
+
+    let buffer = Box::from(buffer);
+
+    Ok(ContentItem::Font(
+        FontContent { buffer },
+        FontMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type: format.mime_type().to_string(),
+            format,
+            family_name,
+            style,
+        },
+    ))
+}
+
+fn json_item(
+    string: String,
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    kind: JsonKind,
+    top_level_count: u64,
+) -> ContentItem {
+    let line_endings = text_ops::line_endings(&string);
+
+    ContentItem::Json(
+        JsonContent { string },
+        JsonMetadata {
+            size_bytes,
+            fingerprint,
+            kind,
+            top_level_count,
+            line_endings,
+            line_endings_normalized: false,
+            compression: Compression::None,
+            compressed_size_bytes: None,
+        },
+    )
+}
+
+fn markdown_item(string: String, size_bytes: u64, fingerprint: Fingerprint) -> ContentItem {
+    let word_count = string.split_whitespace().count() as u64;
+    let outline = markdown::outline(&string);
+    let line_endings = text_ops::line_endings(&string);
+
+    ContentItem::Markdown(
+        MarkdownContent { string },
+        MarkdownMetadata {
+            size_bytes,
+            fingerprint,
+            word_count,
+            outline,
+            line_endings,
+            line_endings_normalized: false,
+            compression: Compression::None,
+            compressed_size_bytes: None,
+        },
+    )
+}
+
+fn csv_item(
+    string: String,
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    table: csv::Table,
+) -> ContentItem {
+    let line_endings = text_ops::line_endings(&string);
+
+    ContentItem::Csv(
+        CsvContent { string },
+        CsvMetadata {
+            size_bytes,
+            fingerprint,
+            delimiter: table.delimiter.to_string(),
+            row_count: table.row_count,
+            column_count: table.column_count,
+            has_header: table.has_header,
+            line_endings,
+            line_endings_normalized: false,
+            compression: Compression::None,
+            compressed_size_bytes: None,
+        },
+    )
+}
+
+fn vector_item(markup: String, size_bytes: u64, fingerprint: Fingerprint) -> ContentItem {
+    let (width_px, height_px, view_box) = svg::svg_dimensions(&markup);
+
+    ContentItem::Vector(
+        VectorContent { markup },
+        VectorMetadata {
+            size_bytes,
+            fingerprint,
+            mime_type: svg::SVG_MIME_TYPE.to_string(),
+            width_px,
+            height_px,
+            view_box,
+        },
+    )
+}
+
+fn text_item(
+    string: String,
+    size_bytes: u64,
+    fingerprint: Fingerprint,
+    detected_encoding: Option<String>,
+) -> ContentItem {
+    let char_count = string.chars().count() as u64;
+    let word_count = string.split_whitespace().count() as u64;
+    let line_endings = text_ops::line_endings(&string);
+
+    ContentItem::Text(
+        TextContent { string },
+        TextMetadata {
+            size_bytes,
+            fingerprint,
+            detected_encoding,
+            char_count,
+            word_count,
+            line_endings,
+            line_endings_normalized: false,
+            compression: Compression::None,
+            compressed_size_bytes: None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat};
+
+    #[test]
+    fn test_classify_bytes() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 3)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+
+        match ContentItem::try_from(&png[..]).unwrap() {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!(png, content.buffer.into_vec());
+                assert_eq!("image/png", metadata.mime_type);
+                assert_eq!((4, 3), (metadata.width_px, metadata.height_px));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        // Parameters on a given content type are dropped, like a sniffed type has none.
+        match classify(&png, "test", Some("image/png; name=dots.png")).unwrap() {
+            ContentItem::Image(_, metadata) => assert_eq!("image/png", metadata.mime_type),
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        match classify_bytes("howdy there".as_bytes()).unwrap() {
+            ContentItem::Text(content, metadata) => {
+                assert_eq!("howdy there", content.string);
+                assert_eq!(2, metadata.word_count);
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        match classify_bytes(b"\x00\x9f\x92\x96\xc3\x28\xa0\xa1\xfe\xff").unwrap() {
+            ContentItem::Binary(content, metadata) => {
+                assert_eq!(10, content.buffer.len());
+                assert_eq!(DEFAULT_BINARY_MIME_TYPE, metadata.mime_type);
+            }
+            other => panic!("Expected binary content, got {:?}", other),
+        }
+    }
+}
\ No newline at end of file
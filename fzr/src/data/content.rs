@@ -1,44 +1,813 @@
+use libipld::cbor::DagCborCodec;
+use libipld::codec::{Decode, Encode};
+use libipld::multihash::{Code, MultihashDigest};
 use libipld::Cid;
 use libipld::DagCbor;
-use std::hash::Hash;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, Write};
 use std::path::PathBuf;
 
+use crate::data::csv;
+use crate::data::error::FzrError;
+use crate::data::markdown;
+use crate::data::meta::MetadataItem;
+use crate::data::text_ops;
+
 type Bytes = u64;
 type Px = u32;
+type Millis = u64;
+type Hz = u32;
+
+/// Serializes byte buffers as base64 strings so they stay JSON-safe.
+mod base64_buffer {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(buffer: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(buffer))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<[u8]>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let buffer = base64::decode(encoded).map_err(D::Error::custom)?;
+
+        Ok(buffer.into_boxed_slice())
+    }
+}
+
+/// Serializes CIDs as their usual multibase strings rather than raw bytes.
+mod cid_string {
+    use libipld::Cid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(cid: &Cid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&cid.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+
+        Cid::from_str(&encoded).map_err(D::Error::custom)
+    }
+}
+
+/// Like `cid_string`, for optional CIDs.
+mod optional_cid_string {
+    use libipld::Cid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(cid: &Option<Cid>, serializer: S) -> Result<S::Ok, S::Error> {
+        match cid {
+            Some(cid) => serializer.serialize_some(&cid.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Cid>, D::Error> {
+        let encoded = Option::<String>::deserialize(deserializer)?;
+
+        encoded
+            .map(|encoded| Cid::from_str(&encoded).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// SHA-256 of the bytes content was stored from, before transformations such as compression or
+/// EXIF stripping. Text whose line endings were normalized is fingerprinted as normalized, so
+/// copies from different platforms still match. Unlike a CID it doesn't depend on how the block
+/// is encoded, so it can be matched against hashes from systems that know nothing about IPFS.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    pub fn of(buffer: &[u8]) -> Self {
+        let digest = Code::Sha2_256.digest(buffer);
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(digest.digest());
+
+        Fingerprint(bytes)
+    }
+
+    /// Lowercase hex, as printed by `sha256sum`.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash)]
+// DAG-CBOR has no fixed-size arrays, so fingerprints are encoded as byte strings.
+impl Encode<DagCborCodec> for Fingerprint {
+    fn encode<W: Write>(&self, c: DagCborCodec, w: &mut W) -> libipld::Result<()> {
+        self.0[..].encode(c, w)
+    }
+}
+
+impl Decode<DagCborCodec> for Fingerprint {
+    fn decode<R: Read + Seek>(c: DagCborCodec, r: &mut R) -> libipld::Result<Self> {
+        let bytes = Box::<[u8]>::decode(c, r)?;
+        let bytes = <[u8; 32]>::try_from(&bytes[..]).map_err(|_| {
+            anyhow::anyhow!(
+                "Expected a 32-byte fingerprint, found {} bytes",
+                bytes.len()
+            )
+        })?;
+
+        Ok(Fingerprint(bytes))
+    }
+}
+
+impl Serialize for Fingerprint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let mut bytes = [0; 32];
+
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(de::Error::custom("Expected a fingerprint of 64 hex digits"));
+        }
+
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(de::Error::custom)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(de::Error::custom)?;
+        }
+
+        Ok(Fingerprint(bytes))
+    }
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ImageContent {
+    #[serde(with = "base64_buffer")]
     pub buffer: Box<[u8]>,
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct TextContent {
     pub string: String,
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash)]
+/// Text compressed as described by its metadata. Only found in stored blocks: `load_file`
+/// returns it decompressed, as [`ContentItem::Text`] or whichever kind of text it was stored as.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CompressedTextContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+/// A JSON document, kept exactly as written.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct JsonContent {
+    pub string: String,
+}
+
+/// A Markdown document, kept exactly as written.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct MarkdownContent {
+    pub string: String,
+}
+
+/// A delimited table such as a CSV file, kept exactly as written.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CsvContent {
+    pub string: String,
+}
+
+/// SVG markup, which is text rather than pixels and so isn't stored as [`ImageContent`].
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VectorContent {
+    pub markup: String,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VideoContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AudioContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BinaryContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DocumentContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct FontContent {
+    #[serde(with = "base64_buffer")]
+    pub buffer: Box<[u8]>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ImageMetadata {
     pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
     pub mime_type: String,
     pub width_px: Px,
     pub height_px: Px,
+    pub exif: Option<ExifData>,
+    pub phash: Option<u64>, // Perceptual (difference) hash, compare with `phash_distance`
+    pub dominant_colors: Vec<(u8, u8, u8)>, // RGB, most common first
+    pub blurhash: Option<String>, // Blurred placeholder, see https://blurha.sh
+    pub frame_count: Option<u32>, // Only set for animated GIF, APNG and WebP images
+    pub loop_count: Option<u32>, // As declared by the image, 0 meaning forever
+    /// MIME type of the image this one was transcoded from, such as `image/heif`.
+    pub original_format: Option<String>,
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExifData {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub datetime: Option<String>,
+    pub gps_latitude: Option<f64>, // Signed decimal degrees, negative south of the equator
+    pub gps_longitude: Option<f64>, // Signed decimal degrees, negative west of Greenwich
+    pub orientation: Option<u16>,
+    pub exposure_time: Option<String>,
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash)]
+// `read_exif` never yields NaN coordinates, so they equal themselves. Zero is hashed as positive
+// zero since `-0.0 == 0.0`, keeping the hash consistent with `PartialEq`.
+impl Eq for ExifData {}
+
+fn coordinate_bits(coordinate: Option<f64>) -> Option<u64> {
+    coordinate.map(|degrees| if degrees == 0.0 { 0 } else { degrees.to_bits() })
+}
+
+impl Hash for ExifData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.camera_make.hash(state);
+        self.camera_model.hash(state);
+        self.datetime.hash(state);
+        coordinate_bits(self.gps_latitude).hash(state);
+        coordinate_bits(self.gps_longitude).hash(state);
+        self.orientation.hash(state);
+        self.exposure_time.hash(state);
+    }
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct TextMetadata {
     pub size_bytes: Bytes,
+    /// Taken before transcoding, so it matches the file as it was on disk.
+    pub fingerprint: Fingerprint,
+    /// Encoding the text was transcoded from, or `None` if it was already UTF-8.
+    pub detected_encoding: Option<String>,
+    pub char_count: u64,
+    pub word_count: u64,
+    /// Line endings the text was read with, before any were normalized.
+    pub line_endings: LineEndings,
+    /// Whether any CRLF or CR line endings were converted to LF before storing.
+    pub line_endings_normalized: bool,
+    pub compression: Compression,
+    /// Size of the text as stored, if it was compressed.
+    pub compressed_size_bytes: Option<Bytes>,
+}
+
+/// Which line endings text uses.
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum LineEndings {
+    Lf,
+    Crlf,
+    Cr,
+    /// More than one kind, such as a file edited on both Windows and Unix.
+    Mixed,
+    /// No line breaks at all.
+    None,
+}
+
+/// How text is compressed in its stored block.
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct JsonMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub kind: JsonKind,
+    pub top_level_count: u64, // Keys of an object, or elements of an array
+    /// Line endings the document was read with, before any were normalized.
+    pub line_endings: LineEndings,
+    /// Whether any CRLF or CR line endings were converted to LF before storing.
+    pub line_endings_normalized: bool,
+    pub compression: Compression,
+    /// Size of the document as stored, if it was compressed.
+    pub compressed_size_bytes: Option<Bytes>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct MarkdownMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub word_count: u64,
+    pub outline: Vec<Heading>, // In document order, for a table of contents
+    /// Line endings the document was read with, before any were normalized.
+    pub line_endings: LineEndings,
+    /// Whether any CRLF or CR line endings were converted to LF before storing.
+    pub line_endings_normalized: bool,
+    pub compression: Compression,
+    /// Size of the document as stored, if it was compressed.
+    pub compressed_size_bytes: Option<Bytes>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CsvMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub delimiter: String, // A single character, such as `,` or a tab
+    pub row_count: u64,    // Including the header, if there is one
+    pub column_count: u64, // Of the widest row
+    pub has_header: bool,  // A guess, from whether the first row looks like column names
+    /// Line endings the table was read with, before any were normalized.
+    pub line_endings: LineEndings,
+    /// Whether any CRLF or CR line endings were converted to LF before storing.
+    pub line_endings_normalized: bool,
+    pub compression: Compression,
+    /// Size of the table as stored, if it was compressed.
+    pub compressed_size_bytes: Option<Bytes>,
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Heading {
+    pub level: u8, // 1 for `#`, through 6 for `######`
+    pub text: String,
+}
+
+/// What a JSON document's top-level value is. Documents holding a bare string, number or literal
+/// are stored as text instead.
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum JsonKind {
+    Object,
+    Array,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VectorMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+    pub width_px: Option<Px>, // Falls back to the viewBox when the root element has no width
+    pub height_px: Option<Px>,
+    pub view_box: Option<String>, // As written, e.g. "0 0 24 24"
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VideoMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+    pub width_px: Px,
+    pub height_px: Px,
+    pub duration_ms: Millis,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AudioMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+    pub duration_ms: Millis,
+    pub sample_rate_hz: Hz,
+    pub channels: u16,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BinaryMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+    pub page_count: Option<u32>,
+    pub title: Option<String>, // From the document information dictionary, when it has one
+    pub author: Option<String>,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct FontMetadata {
+    pub size_bytes: Bytes,
+    pub fingerprint: Fingerprint,
+    pub mime_type: String,
+    pub format: FontFormat,
+    pub family_name: Option<String>, // From the name table, preferring the typographic family
+    pub style: Option<String>,       // Such as "Bold Italic"
+}
+
+/// The container a font is stored in. WOFF and WOFF2 wrap a TrueType or OpenType font.
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum FontFormat {
+    TrueType,
+    OpenType,
+    Woff,
+    Woff2,
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum ContentItem {
     Image(ImageContent, ImageMetadata),
     Text(TextContent, TextMetadata),
+    CompressedText(CompressedTextContent, TextMetadata),
+    Video(VideoContent, VideoMetadata),
+    Audio(AudioContent, AudioMetadata),
+    Binary(BinaryContent, BinaryMetadata),
+    Vector(VectorContent, VectorMetadata),
+    Json(JsonContent, JsonMetadata),
+    CompressedJson(CompressedTextContent, JsonMetadata),
+    Document(DocumentContent, DocumentMetadata),
+    Markdown(MarkdownContent, MarkdownMetadata),
+    CompressedMarkdown(CompressedTextContent, MarkdownMetadata),
+    Csv(CsvContent, CsvMetadata),
+    CompressedCsv(CompressedTextContent, CsvMetadata),
+    Font(FontContent, FontMetadata),
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+impl ContentItem {
+    /// The fingerprint of the bytes this item was stored from.
+    pub fn fingerprint(&self) -> &Fingerprint {
+        match self {
+            ContentItem::Image(_, metadata) => &metadata.fingerprint,
+            ContentItem::Text(_, metadata) => &metadata.fingerprint,
+            ContentItem::CompressedText(_, metadata) => &metadata.fingerprint,
+            ContentItem::Video(_, metadata) => &metadata.fingerprint,
+            ContentItem::Audio(_, metadata) => &metadata.fingerprint,
+            ContentItem::Binary(_, metadata) => &metadata.fingerprint,
+            ContentItem::Vector(_, metadata) => &metadata.fingerprint,
+            ContentItem::Json(_, metadata) => &metadata.fingerprint,
+            ContentItem::CompressedJson(_, metadata) => &metadata.fingerprint,
+            ContentItem::Document(_, metadata) => &metadata.fingerprint,
+            ContentItem::Markdown(_, metadata) => &metadata.fingerprint,
+            ContentItem::CompressedMarkdown(_, metadata) => &metadata.fingerprint,
+            ContentItem::Csv(_, metadata) => &metadata.fingerprint,
+            ContentItem::CompressedCsv(_, metadata) => &metadata.fingerprint,
+            ContentItem::Font(_, metadata) => &metadata.fingerprint,
+        }
+    }
+
+    /// The fingerprint as lowercase hex, comparable with the output of `sha256sum`.
+    pub fn fingerprint_hex(&self) -> String {
+        self.fingerprint().to_hex()
+    }
+
+    /// The MIME type the content was stored as, without parameters such as a charset.
+    pub fn mime_type(&self) -> &str {
+        match self {
+            ContentItem::Image(_, metadata) => &metadata.mime_type,
+            ContentItem::Text(..) | ContentItem::CompressedText(..) => text_ops::TEXT_MIME_TYPE,
+            ContentItem::Video(_, metadata) => &metadata.mime_type,
+            ContentItem::Audio(_, metadata) => &metadata.mime_type,
+            ContentItem::Binary(_, metadata) => &metadata.mime_type,
+            ContentItem::Vector(_, metadata) => &metadata.mime_type,
+            ContentItem::Json(..) | ContentItem::CompressedJson(..) => text_ops::JSON_MIME_TYPE,
+            ContentItem::Document(_, metadata) => &metadata.mime_type,
+            ContentItem::Markdown(..) | ContentItem::CompressedMarkdown(..) => {
+                markdown::MARKDOWN_MIME_TYPE
+            }
+            ContentItem::Csv(..) | ContentItem::CompressedCsv(..) => csv::CSV_MIME_TYPE,
+            ContentItem::Font(_, metadata) => &metadata.mime_type,
+        }
+    }
+}
+
+/// The metadata half of a [`ContentItem`], as returned by `load_metadata`. Compressed text has
+/// the same metadata as it would uncompressed.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ContentMetadata {
+    Image(ImageMetadata),
+    Text(TextMetadata),
+    Video(VideoMetadata),
+    Audio(AudioMetadata),
+    Binary(BinaryMetadata),
+    Vector(VectorMetadata),
+    Json(JsonMetadata),
+    Document(DocumentMetadata),
+    Markdown(MarkdownMetadata),
+    Csv(CsvMetadata),
+    Font(FontMetadata),
+}
+
+impl From<ContentItem> for ContentMetadata {
+    fn from(content: ContentItem) -> Self {
+        match content {
+            ContentItem::Image(_, metadata) => ContentMetadata::Image(metadata),
+            ContentItem::Text(_, metadata) => ContentMetadata::Text(metadata),
+            ContentItem::CompressedText(_, metadata) => ContentMetadata::Text(metadata),
+            ContentItem::Video(_, metadata) => ContentMetadata::Video(metadata),
+            ContentItem::Audio(_, metadata) => ContentMetadata::Audio(metadata),
+            ContentItem::Binary(_, metadata) => ContentMetadata::Binary(metadata),
+            ContentItem::Vector(_, metadata) => ContentMetadata::Vector(metadata),
+            ContentItem::Json(_, metadata) => ContentMetadata::Json(metadata),
+            ContentItem::CompressedJson(_, metadata) => ContentMetadata::Json(metadata),
+            ContentItem::Document(_, metadata) => ContentMetadata::Document(metadata),
+            ContentItem::Markdown(_, metadata) => ContentMetadata::Markdown(metadata),
+            ContentItem::CompressedMarkdown(_, metadata) => ContentMetadata::Markdown(metadata),
+            ContentItem::Csv(_, metadata) => ContentMetadata::Csv(metadata),
+            ContentItem::CompressedCsv(_, metadata) => ContentMetadata::Csv(metadata),
+            ContentItem::Font(_, metadata) => ContentMetadata::Font(metadata),
+        }
+    }
+}
+
+/// The bytes content was stored from, to write back to disk or forward elsewhere. Text comes back
+/// as UTF-8 whatever encoding it was read in, and compressed text decompressed, failing only if
+/// the compressed buffer is corrupt.
+impl TryFrom<&ContentItem> for Vec<u8> {
+    type Error = FzrError;
+
+    fn try_from(content: &ContentItem) -> Result<Self, Self::Error> {
+        let bytes = match content {
+            ContentItem::Text(content, _) => content.string.as_bytes().to_vec(),
+            ContentItem::CompressedText(content, _)
+            | ContentItem::CompressedJson(content, _)
+            | ContentItem::CompressedMarkdown(content, _)
+            | ContentItem::CompressedCsv(content, _) => text_ops::decompress(&content.buffer)
+                .map_err(FzrError::decode)?
+                .into_bytes(),
+            ContentItem::Image(content, _) => content.buffer.to_vec(),
+            ContentItem::Video(content, _) => content.buffer.to_vec(),
+            ContentItem::Audio(content, _) => content.buffer.to_vec(),
+            ContentItem::Binary(content, _) => content.buffer.to_vec(),
+            ContentItem::Vector(content, _) => content.markup.as_bytes().to_vec(),
+            ContentItem::Json(content, _) => content.string.as_bytes().to_vec(),
+            ContentItem::Document(content, _) => content.buffer.to_vec(),
+            ContentItem::Markdown(content, _) => content.string.as_bytes().to_vec(),
+            ContentItem::Csv(content, _) => content.string.as_bytes().to_vec(),
+            ContentItem::Font(content, _) => content.buffer.to_vec(),
+        };
+
+        Ok(bytes)
+    }
+}
+
+/// A one-line summary for logs and CLI output, like `Image(image/png, 640x480, 120.5 KiB)`,
+/// leaving out the content itself.
+impl fmt::Display for ContentItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContentItem::Image(_, metadata) => write!(
+                f,
+                "Image({}, {}x{}, {})",
+                metadata.mime_type,
+                metadata.width_px,
+                metadata.height_px,
+                DisplaySize(metadata.size_bytes)
+            ),
+            ContentItem::Text(_, metadata) => write!(
+                f,
+                "Text({}, {})",
+                DisplaySize(metadata.size_bytes),
+                plural(metadata.word_count, "word")
+            ),
+            ContentItem::CompressedText(content, metadata) => write!(
+                f,
+                "CompressedText({}, {}, {} compressed)",
+                DisplaySize(metadata.size_bytes),
+                plural(metadata.word_count, "word"),
+                DisplaySize(content.buffer.len() as u64)
+            ),
+            ContentItem::Video(_, metadata) => write!(
+                f,
+                "Video({}, {}x{}, {:.1} s, {})",
+                metadata.mime_type,
+                metadata.width_px,
+                metadata.height_px,
+                metadata.duration_ms as f64 / 1000.0,
+                DisplaySize(metadata.size_bytes)
+            ),
+            ContentItem::Audio(_, metadata) => write!(
+                f,
+                "Audio({}, {:.1} s, {} Hz, {})",
+                metadata.mime_type,
+                metadata.duration_ms as f64 / 1000.0,
+                metadata.sample_rate_hz,
+                DisplaySize(metadata.size_bytes)
+            ),
+            ContentItem::Binary(_, metadata) => write!(
+                f,
+                "Binary({}, {})",
+                metadata.mime_type,
+                DisplaySize(metadata.size_bytes)
+            ),
+            ContentItem::Vector(_, metadata) => {
+                let dimension = |px: Option<Px>| px.map_or("?".to_string(), |px| px.to_string());
+
+                write!(
+                    f,
+                    "Vector({}, {}x{}, {})",
+                    metadata.mime_type,
+                    dimension(metadata.width_px),
+                    dimension(metadata.height_px),
+                    DisplaySize(metadata.size_bytes)
+                )
+            }
+            ContentItem::Json(_, metadata) => {
+                let (kind, unit) = json_kind_names(metadata.kind);
+
+                write!(
+                    f,
+                    "Json({}, {}, {})",
+                    kind,
+                    plural(metadata.top_level_count, unit),
+                    DisplaySize(metadata.size_bytes)
+                )
+            }
+            ContentItem::CompressedJson(content, metadata) => {
+                let (kind, unit) = json_kind_names(metadata.kind);
+
+                write!(
+                    f,
+                    "CompressedJson({}, {}, {}, {} compressed)",
+                    kind,
+                    plural(metadata.top_level_count, unit),
+                    DisplaySize(metadata.size_bytes),
+                    DisplaySize(content.buffer.len() as u64)
+                )
+            }
+            ContentItem::Document(_, metadata) => {
+                let pages = metadata
+                    .page_count
+                    .map_or("? pages".to_string(), |count| plural(count as u64, "page"));
+
+                write!(
+                    f,
+                    "Document({}, {}, {})",
+                    metadata.mime_type,
+                    pages,
+                    DisplaySize(metadata.size_bytes)
+                )
+            }
+            ContentItem::Markdown(_, metadata) => write!(
+                f,
+                "Markdown({}, {})",
+                DisplaySize(metadata.size_bytes),
+                plural(metadata.outline.len() as u64, "heading")
+            ),
+            ContentItem::CompressedMarkdown(content, metadata) => write!(
+                f,
+                "CompressedMarkdown({}, {}, {} compressed)",
+                DisplaySize(metadata.size_bytes),
+                plural(metadata.outline.len() as u64, "heading"),
+                DisplaySize(content.buffer.len() as u64)
+            ),
+            ContentItem::Csv(_, metadata) => write!(
+                f,
+                "Csv({}, {}, {})",
+                plural(metadata.row_count, "row"),
+                plural(metadata.column_count, "column"),
+                DisplaySize(metadata.size_bytes)
+            ),
+            ContentItem::CompressedCsv(content, metadata) => write!(
+                f,
+                "CompressedCsv({}, {}, {}, {} compressed)",
+                plural(metadata.row_count, "row"),
+                plural(metadata.column_count, "column"),
+                DisplaySize(metadata.size_bytes),
+                DisplaySize(content.buffer.len() as u64)
+            ),
+            ContentItem::Font(_, metadata) => {
+                let name = |name: &Option<String>| name.clone().unwrap_or_else(|| "?".to_string());
+
+                write!(
+                    f,
+                    "Font({}, {} {}, {})",
+                    metadata.mime_type,
+                    name(&metadata.family_name),
+                    name(&metadata.style),
+                    DisplaySize(metadata.size_bytes)
+                )
+            }
+        }
+    }
+}
+
+/// Formats a byte count in bytes below 1 KiB, or else binary units with one decimal place.
+struct DisplaySize(Bytes);
+
+impl fmt::Display for DisplaySize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{}", plural(self.0, "byte"));
+        }
+
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        write!(f, "{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn plural(count: u64, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// What to call a JSON document's top-level value and the things it counts.
+fn json_kind_names(kind: JsonKind) -> (&'static str, &'static str) {
+    match kind {
+        JsonKind::Object => ("object", "key"),
+        JsonKind::Array => ("array", "element"),
+    }
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ContentItemBlock {
     pub content: ContentItem,
     pub size_bytes: Bytes, // Content size, not total block size
+    pub metadata: Vec<MetadataItem>,
+    /// Metadata shared with other content, stored once as a [`MetadataBlock`].
+    #[serde(default, with = "optional_cid_string")]
+    pub metadata_cid: Option<Cid>,
+    /// The version this content replaces, when stored with `store_version`.
+    #[serde(default, with = "optional_cid_string")]
+    pub previous_version: Option<Cid>,
+    /// A copy of the content transcoded to a format browsers can display, when stored with
+    /// `transcode_heic`.
+    #[serde(default, with = "optional_cid_string")]
+    pub transcoded_cid: Option<Cid>,
+    /// A downscaled copy of an image, when stored with `store_image_with_thumbnail`.
+    #[serde(default, with = "optional_cid_string")]
+    pub thumbnail_cid: Option<Cid>,
+    /// When the content was created, in Unix milliseconds, if stored with `created_at`. Left
+    /// unset otherwise, since it changes the CID of content that's otherwise identical.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+}
+
+/// Metadata stored as a block of its own, so many content blocks can link to it by CID.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct MetadataBlock {
+    pub items: Vec<MetadataItem>,
+}
+
+/// Root block of a file stored in fixed-size raw chunks, listing the chunk CIDs in order.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct ChunkedFileBlock {
+    pub chunks: Vec<Cid>,
+    pub chunk_size_bytes: Bytes, // Every chunk is this size except the last
+    pub size_bytes: Bytes,
+}
+
+/// A `ContentItemBlock` encoded as DAG-CBOR and sealed with XChaCha20-Poly1305.
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct EncryptedBlock {
+    pub nonce: Box<[u8]>,
+    pub ciphertext: Box<[u8]>, // Includes the authentication tag
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ManifestEntry {
+    Stored(#[serde(with = "cid_string")] Cid),
+    Failed(String),
+    Skipped(String), // Why the walk passed over it, such as a symlink that wasn't followed
+}
+
+/// Outcome of storing a directory tree, keyed by each file's path relative to the root.
+#[derive(Clone, DagCbor, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryManifest {
+    pub entries: BTreeMap<String, ManifestEntry>,
 }
 
 #[derive(Clone, Debug, Hash)]
@@ -52,4 +821,181 @@ pub struct PathThumb {
     pub path: PathBuf,
     pub image: Box<[u8]>,
     pub metadata: ImageMetadata,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_block_json_round_trip() {
+        let block = ContentItemBlock {
+            content: ContentItem::Text(
+                TextContent {
+                    string: "howdy".into(),
+                },
+                TextMetadata {
+                    size_bytes: 5,
+                    fingerprint: Fingerprint::of(b"howdy"),
+                    detected_encoding: None,
+                    char_count: 5,
+                    word_count: 1,
+                    line_endings: LineEndings::None,
+                    line_endings_normalized: false,
+                    compression: Compression::None,
+                    compressed_size_bytes: None,
+                },
+            ),
+            size_bytes: 5,
+            metadata: Vec::new(),
+            metadata_cid: Some(
+                "bafyreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+                    .parse()
+                    .unwrap(),
+            ),
+            previous_version: None,
+            transcoded_cid: None,
+            thumbnail_cid: None,
+            created_at: None,
+        };
+
+        let json = serde_json::to_string(&block).unwrap();
+        let decoded: ContentItemBlock = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(block, decoded);
+    }
+
+    #[test]
+    fn test_manifest_json_round_trip() {
+        let cid: Cid = "bafyreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+            .parse()
+            .unwrap();
+        let mut manifest = DirectoryManifest::default();
+        manifest
+            .entries
+            .insert("howdy.txt".into(), ManifestEntry::Stored(cid));
+        manifest
+            .entries
+            .insert("broken.txt".into(), ManifestEntry::Failed("oops".into()));
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert_eq!(
+            r#"{"entries":{"broken.txt":{"Failed":"oops"},"howdy.txt":{"Stored":"bafyreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"}}}"#,
+            json
+        );
+
+        let decoded: DirectoryManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(manifest, decoded);
+    }
+
+    #[test]
+    fn test_image_buffer_base64_round_trip() {
+        let image = ImageContent {
+            buffer: Box::new(*b"GIF89a\x01\0\x01\0\0\0\0;"),
+        };
+
+        let json = serde_json::to_string(&image).unwrap();
+        assert_eq!(r#"{"buffer":"R0lGODlhAQABAAAAADs="}"#, json);
+
+        let decoded: ImageContent = serde_json::from_str(&json).unwrap();
+        assert_eq!(image, decoded);
+    }
+
+    #[test]
+    fn test_exif_hash_matches_eq() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |exif: &ExifData| {
+            let mut hasher = DefaultHasher::new();
+            exif.hash(&mut hasher);
+            hasher.finish()
+        };
+        let exif = ExifData {
+            camera_make: None,
+            camera_model: None,
+            datetime: None,
+            gps_latitude: Some(0.0),
+            gps_longitude: Some(-122.42),
+            orientation: None,
+            exposure_time: None,
+        };
+        let negative_zero = ExifData {
+            gps_latitude: Some(-0.0),
+            ..exif.clone()
+        };
+
+        assert_eq!(exif, negative_zero);
+        assert_eq!(hash(&exif), hash(&negative_zero));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = Fingerprint::of(b"abc");
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(hex, fingerprint.to_hex());
+
+        let json = serde_json::to_string(&fingerprint).unwrap();
+        assert_eq!(format!("\"{}\"", hex), json);
+        assert_eq!(fingerprint, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<Fingerprint>("\"ba78\"").is_err());
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let image = ContentItem::Image(
+            ImageContent {
+                buffer: vec![0; 123_392].into_boxed_slice(),
+            },
+            ImageMetadata {
+                size_bytes: 123_392,
+                fingerprint: Fingerprint([0; 32]),
+                mime_type: "image/png".into(),
+                width_px: 640,
+                height_px: 480,
+                exif: None,
+                phash: None,
+                dominant_colors: Vec::new(),
+                blurhash: None,
+                frame_count: None,
+                loop_count: None,
+                original_format: None,
+            },
+        );
+        assert_eq!("Image(image/png, 640x480, 120.5 KiB)", image.to_string());
+
+        let text = ContentItem::Text(
+            TextContent {
+                string: "howdy there".into(),
+            },
+            TextMetadata {
+                size_bytes: 11,
+                fingerprint: Fingerprint([0; 32]),
+                detected_encoding: None,
+                char_count: 11,
+                word_count: 2,
+                line_endings: LineEndings::None,
+                line_endings_normalized: false,
+                compression: Compression::None,
+                compressed_size_bytes: None,
+            },
+        );
+        assert_eq!("Text(11 bytes, 2 words)", text.to_string());
+
+        let json = ContentItem::Json(
+            JsonContent {
+                string: "[1]".into(),
+            },
+            JsonMetadata {
+                size_bytes: 3,
+                fingerprint: Fingerprint([0; 32]),
+                kind: JsonKind::Array,
+                top_level_count: 1,
+                line_endings: LineEndings::None,
+                line_endings_normalized: false,
+                compression: Compression::None,
+                compressed_size_bytes: None,
+            },
+        );
+        assert_eq!("Json(array, 1 element, 3 bytes)", json.to_string());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+use libipld::cbor::DagCborCodec;
+use libipld::cid::Cid;
+use libipld::multihash::Code;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use crate::data::error::FzrError;
+
+/// CID of a stored `ContentItemBlock`: DAG-CBOR encoded and hashed with BLAKE3 or SHA-256, as
+/// produced by `store_file`. Constructing one from any other CID fails, so raw chunks or foreign
+/// blocks can't be passed where content is expected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ContentCid(Cid);
+
+impl ContentCid {
+    pub fn new(cid: Cid) -> Result<ContentCid, FzrError> {
+        let hash = cid.hash().code();
+
+        if cid.codec() != u64::from(DagCborCodec)
+            || (hash != u64::from(Code::Blake3_256) && hash != u64::from(Code::Sha2_256))
+        {
+            return Err(FzrError::NotContentCid(cid));
+        }
+
+        Ok(ContentCid(cid))
+    }
+
+    pub fn cid(&self) -> &Cid {
+        &self.0
+    }
+}
+
+impl Deref for ContentCid {
+    type Target = Cid;
+
+    fn deref(&self) -> &Cid {
+        &self.0
+    }
+}
+
+impl TryFrom<Cid> for ContentCid {
+    type Error = FzrError;
+
+    fn try_from(cid: Cid) -> Result<Self, Self::Error> {
+        ContentCid::new(cid)
+    }
+}
+
+impl From<ContentCid> for Cid {
+    fn from(content_cid: ContentCid) -> Self {
+        content_cid.0
+    }
+}
+
+impl PartialEq<Cid> for ContentCid {
+    fn eq(&self, other: &Cid) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<ContentCid> for Cid {
+    fn eq(&self, other: &ContentCid) -> bool {
+        *self == other.0
+    }
+}
+
+impl FromStr for ContentCid {
+    type Err = FzrError;
+
+    fn from_str(cid_string: &str) -> Result<Self, Self::Err> {
+        ContentCid::new(Cid::from_str(cid_string)?)
+    }
+}
+
+impl fmt::Display for ContentCid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::raw::RawCodec;
+    use libipld::store::DefaultParams;
+    use libipld::Block;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_content_cid_validation() -> Result<(), Box<dyn Error>> {
+        let content = Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &1)?;
+        let content_cid = ContentCid::new(*content.cid())?;
+        assert_eq!(content.cid(), content_cid.cid());
+        assert_eq!(
+            content_cid,
+            content.cid().to_string().parse::<ContentCid>()?
+        );
+
+        let raw = Block::<DefaultParams>::encode(RawCodec, Code::Blake3_256, &b"chunk"[..])?;
+        assert!(matches!(
+            ContentCid::new(*raw.cid()),
+            Err(FzrError::NotContentCid(cid)) if cid == *raw.cid()
+        ));
+
+        let sha256 = Block::<DefaultParams>::encode(DagCborCodec, Code::Sha2_256, &1)?;
+        assert!(ContentCid::new(*sha256.cid()).is_ok());
+
+        let sha512 = Block::<DefaultParams>::encode(DagCborCodec, Code::Sha2_512, &1)?;
+        assert!(ContentCid::new(*sha512.cid()).is_err());
+
+        assert!(matches!(
+            "not-a-cid".parse::<ContentCid>(),
+            Err(FzrError::CidParse(_))
+        ));
+
+        Ok(())
+    }
+}
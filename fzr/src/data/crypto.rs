@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::convert::TryInto;
+
+use crate::data::content::EncryptedBlock;
+
+pub const KEY_LEN: usize = 32;
+
+/// XChaCha20's extended nonce is long enough to pick at random without risking reuse.
+const NONCE_LEN: usize = 24;
+
+/// Encrypts `plaintext` under `key` with a fresh random nonce, which is kept in the returned block.
+pub fn encrypt(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<EncryptedBlock> {
+    let cipher = XChaCha20Poly1305::new(&Key::from(*key));
+
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(&XNonce::from(nonce), plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt {} bytes", plaintext.len()))?;
+
+    Ok(EncryptedBlock {
+        nonce: Box::new(nonce),
+        ciphertext: ciphertext.into_boxed_slice(),
+    })
+}
+
+/// Decrypts a block sealed by [`encrypt`], or returns `None` if it wasn't encrypted under `key`
+/// or has been tampered with.
+pub fn decrypt(key: &[u8; KEY_LEN], block: &EncryptedBlock) -> Option<Vec<u8>> {
+    let nonce: [u8; NONCE_LEN] = (*block.nonce).try_into().ok()?;
+    let cipher = XChaCha20Poly1305::new(&Key::from(*key));
+
+    cipher
+        .decrypt(&XNonce::from(nonce), &*block.ciphertext)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = [7; KEY_LEN];
+        let block = encrypt(&key, b"howdy").unwrap();
+
+        assert_ne!(b"howdy", &*block.ciphertext);
+        assert_eq!(Some(b"howdy".to_vec()), decrypt(&key, &block));
+        assert_eq!(None, decrypt(&[8; KEY_LEN], &block));
+
+        let mut tampered = block.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(None, decrypt(&key, &tampered));
+
+        // Nonces are never reused, so the same plaintext encrypts differently each time.
+        assert_ne!(block, encrypt(&key, b"howdy").unwrap());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,198 @@
+//! Comma-, tab- and semicolon-separated tables. Fields may be quoted with `"`, doubling any quotes
+//! inside them, and quoted fields may span lines.
+
+use std::path::Path;
+
+pub const CSV_MIME_TYPE: &str = "text/csv";
+
+const CSV_EXTENSIONS: [&str; 1] = ["csv"];
+
+/// Delimiters sniffing tries, most likely first.
+const DELIMITERS: [char; 3] = [',', '\t', ';'];
+
+/// The shape of a delimited table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Table {
+    pub delimiter: char,
+    /// Records in the table, including any header.
+    pub row_count: u64,
+    /// Fields in the widest record.
+    pub column_count: u64,
+    pub has_header: bool,
+}
+
+pub fn is_csv(mime_type: &str) -> bool {
+    mime_type == CSV_MIME_TYPE
+}
+
+pub fn has_csv_extension(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => CSV_EXTENSIONS
+            .iter()
+            .any(|csv| csv.eq_ignore_ascii_case(extension)),
+        None => false,
+    }
+}
+
+/// Recognizes text with nothing else to say it's a table as one, when a delimiter splits it
+/// evenly and there's more to go on than prose with a comma in each line would give: at least
+/// three columns, or a header over at least three rows.
+pub fn sniff(text: &str) -> Option<Table> {
+    DELIMITERS.iter().find_map(|&delimiter| {
+        split_evenly(text, delimiter)
+            .filter(|table| table.column_count >= 3 || (table.has_header && table.row_count > 3))
+    })
+}
+
+/// Reads text already known to be a table, such as a `.csv` file. Text that no delimiter splits
+/// evenly is split on whichever appears most in its first line.
+pub fn read_table(text: &str) -> Table {
+    let even = DELIMITERS
+        .iter()
+        .find_map(|&delimiter| split_evenly(text, delimiter));
+
+    even.unwrap_or_else(|| {
+        let first_line = text.lines().next().unwrap_or_default();
+        let delimiter = DELIMITERS
+            .iter()
+            .copied()
+            // Ties go to the likelier delimiter, which `max_by_key` only does when it comes last.
+            .rev()
+            .max_by_key(|&delimiter| first_line.matches(delimiter).count())
+            .unwrap_or(DELIMITERS[0]);
+
+        read_table_with(text, delimiter)
+    })
+}
+
+/// Reads a table already known to be split by `delimiter`.
+pub fn read_table_with(text: &str, delimiter: char) -> Table {
+    table(&records(text, delimiter).0, delimiter)
+}
+
+/// The table `delimiter` makes of text when it splits every line into the same number of fields,
+/// at least two, over at least two lines. Text with an unclosed quote isn't one.
+fn split_evenly(text: &str, delimiter: char) -> Option<Table> {
+    let (records, closed) = records(text, delimiter);
+    let column_count = records.first()?.len();
+
+    let consistent = closed
+        && records.len() >= 2
+        && column_count >= 2
+        && records.iter().all(|record| record.len() == column_count);
+
+    Some(table(&records, delimiter)).filter(|_| consistent)
+}
+
+fn table(records: &[Vec<String>], delimiter: char) -> Table {
+    Table {
+        delimiter,
+        row_count: records.len() as u64,
+        column_count: records.iter().map(Vec::len).max().unwrap_or(0) as u64,
+        has_header: has_header(records),
+    }
+}
+
+/// Splits text into records of fields, skipping blank lines, and reports whether every quote was
+/// closed. An unclosed quote runs to the end of the text.
+fn records(text: &str, delimiter: char) -> (Vec<Vec<String>>, bool) {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            end_record(&mut records, &mut record, &mut field);
+        } else {
+            field.push(c);
+        }
+    }
+
+    end_record(&mut records, &mut record, &mut field);
+
+    (records, !quoted)
+}
+
+fn end_record(records: &mut Vec<Vec<String>>, record: &mut Vec<String>, field: &mut String) {
+    if record.is_empty() && field.is_empty() {
+        return;
+    }
+
+    record.push(std::mem::take(field));
+    records.push(std::mem::take(record));
+}
+
+/// Guesses whether the first record names the columns, as Python's `csv.Sniffer` does: each
+/// column whose values are all numbers, or all the same length, votes for a header if the first
+/// record's field doesn't fit that pattern and against one if it does.
+fn has_header(records: &[Vec<String>]) -> bool {
+    let (header, rows) = match records.split_first() {
+        Some((header, rows)) if !rows.is_empty() => (header, rows),
+        _ => return false,
+    };
+
+    if header
+        .iter()
+        .any(|name| name.trim().is_empty() || is_number(name))
+    {
+        return false;
+    }
+
+    let votes: i64 = header
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            let values: Vec<&str> = rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .map(|value| value.trim())
+                .collect();
+
+            let length = values.first().map(|value| value.chars().count());
+
+            if values.is_empty() {
+                0
+            } else if values.iter().all(|value| is_number(value)) {
+                // The first record was already checked for numbers.
+                1
+            } else if values
+                .iter()
+                .all(|value| Some(value.chars().count()) == length)
+            {
+                if length == Some(name.trim().chars().count()) {
+                    -1
+                } else {
+                    1
+                }
+            } else {
+                0
+            }
+        })
+        .sum();
+
+    votes > 0
+}
+
+fn is_number(value: &str) -> bool {
+    let value = value.trim();
+
+    value.parse::<f64>().is_ok() && value.bytes().any(|byte| byte.is_ascii_digit())
+}
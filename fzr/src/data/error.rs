@@ -0,0 +1,77 @@
+use libipld::cid::{self, Cid};
+use std::io;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::data::content_cid::ContentCid;
+
+/// Errors returned by the storage and loading APIs. Sources are shared behind `Arc`s so results
+/// can be cloned into UI messages.
+#[derive(Clone, Debug, Error)]
+pub enum FzrError {
+    #[error("I/O error: {0}")]
+    Io(Arc<io::Error>),
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Failed to parse CID: {0}")]
+    CidParse(Arc<cid::Error>),
+    #[error("CID {0} doesn't refer to a DAG-CBOR content block")]
+    NotContentCid(Cid),
+    #[error("No block found for CID: {0}")]
+    NotFound(Cid),
+    #[error("Block {0} didn't match its source when read back")]
+    VerificationFailed(Cid),
+    #[error("Block {0} couldn't be decrypted with the given key")]
+    DecryptionFailed(Cid),
+    #[error("Input is larger than the limit of {0} bytes")]
+    TooLarge(u64),
+    #[error("Invalid glob pattern: {0}")]
+    InvalidGlob(Arc<globset::Error>),
+    #[error("Failed to decode content: {0:#}")]
+    Decode(Arc<anyhow::Error>),
+    #[error("IPFS error: {0:#}")]
+    Ipfs(Arc<anyhow::Error>),
+    #[error("{0}, and rolling it back left {} blocks stored", .1.len())]
+    RollbackIncomplete(Arc<FzrError>, Vec<ContentCid>),
+}
+
+/// Errors from building and validating metadata.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum MetadataError {
+    #[error("Metadata chain revisits {0:?} among its own ancestors")]
+    Cycle(String),
+    #[error("Metadata value must not be empty")]
+    EmptyValue,
+    #[error("Metadata category must be set")]
+    MissingCategory,
+}
+
+impl FzrError {
+    pub(crate) fn decode(err: impl Into<anyhow::Error>) -> Self {
+        FzrError::Decode(Arc::new(err.into()))
+    }
+}
+
+impl From<io::Error> for FzrError {
+    fn from(err: io::Error) -> Self {
+        FzrError::Io(Arc::new(err))
+    }
+}
+
+impl From<cid::Error> for FzrError {
+    fn from(err: cid::Error) -> Self {
+        FzrError::CidParse(Arc::new(err))
+    }
+}
+
+impl From<globset::Error> for FzrError {
+    fn from(err: globset::Error) -> Self {
+        FzrError::InvalidGlob(Arc::new(err))
+    }
+}
+
+impl From<Arc<anyhow::Error>> for FzrError {
+    fn from(err: Arc<anyhow::Error>) -> Self {
+        FzrError::Ipfs(err)
+    }
+}
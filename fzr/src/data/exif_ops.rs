@@ -0,0 +1,121 @@
+use exif::{Exif, In, Reader, Tag, Value};
+use std::io::Cursor;
+
+use crate::data::content::ExifData;
+
+/// Image MIME types whose EXIF segments are parsed when stored.
+const EXIF_MIME_TYPES: [&str; 2] = ["image/jpeg", "image/tiff"];
+
+pub fn has_exif_support(mime_type: &str) -> bool {
+    EXIF_MIME_TYPES.contains(&mime_type)
+}
+
+/// Extracts common capture fields from an image's EXIF data, or `None` if it has none.
+pub fn read_exif(buffer: &[u8]) -> Option<ExifData> {
+    let exif = Reader::new()
+        .read_from_container(&mut Cursor::new(buffer))
+        .ok()?;
+
+    Some(ExifData {
+        camera_make: ascii(&exif, Tag::Make),
+        camera_model: ascii(&exif, Tag::Model),
+        datetime: ascii(&exif, Tag::DateTimeOriginal).or_else(|| ascii(&exif, Tag::DateTime)),
+        gps_latitude: coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        gps_longitude: coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+        orientation: exif
+            .get_field(Tag::Orientation, In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .map(|orientation| orientation as u16),
+        exposure_time: exif
+            .get_field(Tag::ExposureTime, In::PRIMARY)
+            .map(|field| field.display_value().to_string()),
+    })
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(|value| {
+            String::from_utf8_lossy(value)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string()
+        }),
+        _ => None,
+    }
+}
+
+/// Converts a degrees/minutes/seconds coordinate to signed decimal degrees, negative when the
+/// reference tag is `negative_ref` (south or west). A part with a zero denominator makes the
+/// coordinate `None` rather than infinite or NaN, and zero is always positive zero.
+fn coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: &str) -> Option<f64> {
+    let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(parts) if parts.len() == 3 && parts.iter().all(|part| part.denom != 0) => {
+            parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+
+    if !degrees.is_finite() {
+        return None;
+    }
+
+    match ascii(exif, ref_tag) {
+        Some(reference) if reference == negative_ref && degrees != 0.0 => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::experimental::Writer;
+    use exif::{Field, Rational};
+
+    fn gps_field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn tiff_with_gps(latitude: [Rational; 3], latitude_ref: &[u8]) -> Vec<u8> {
+        let fields = [
+            gps_field(Tag::GPSLatitude, Value::Rational(latitude.to_vec())),
+            gps_field(
+                Tag::GPSLatitudeRef,
+                Value::Ascii(vec![latitude_ref.to_vec()]),
+            ),
+            gps_field(
+                Tag::GPSLongitude,
+                Value::Rational(vec![(0, 1).into(), (0, 1).into(), (0, 1).into()]),
+            ),
+            gps_field(Tag::GPSLongitudeRef, Value::Ascii(vec![b"W".to_vec()])),
+        ];
+
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+
+        let mut buffer = Cursor::new(Vec::new());
+        writer.write(&mut buffer, false).unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_read_gps_coordinates() {
+        let tiff = tiff_with_gps([(37, 1).into(), (46, 1).into(), (30, 1).into()], b"N");
+        let exif = read_exif(&tiff).unwrap();
+
+        assert!((exif.gps_latitude.unwrap() - 37.775).abs() < 1e-6);
+        // Zero west of Greenwich is positive zero, so it hashes like any other zero.
+        assert_eq!(Some(0.0f64.to_bits()), exif.gps_longitude.map(f64::to_bits));
+
+        let tiff = tiff_with_gps([(37, 0).into(), (46, 1).into(), (30, 1).into()], b"N");
+        assert_eq!(None, read_exif(&tiff).unwrap().gps_latitude);
+
+        let tiff = tiff_with_gps([(0, 0).into(), (0, 1).into(), (0, 1).into()], b"S");
+        assert_eq!(None, read_exif(&tiff).unwrap().gps_latitude);
+    }
+}
\ No newline at end of file
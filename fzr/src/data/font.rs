@@ -0,0 +1,283 @@
+//! TrueType, OpenType, WOFF and WOFF2 fonts. Names are read from the font's `name` table, which
+//! WOFF compresses with zlib and WOFF2 with Brotli.
+
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::{MACINTOSH, UTF_16BE};
+use std::convert::TryInto;
+use std::io::Read;
+
+use crate::data::content::FontFormat;
+
+/// Font MIME types: the registered `font/*` ones, and the older ones `infer` reports.
+const FONT_MIME_TYPES: [&str; 6] = [
+    "font/ttf",
+    "font/otf",
+    "font/woff",
+    "font/woff2",
+    "application/font-sfnt",
+    "application/font-woff",
+];
+
+const NAME_TAG: [u8; 4] = *b"name";
+
+/// Tags WOFF2 refers to by index, in its order. Only those up to `loca` matter here.
+const WOFF2_KNOWN_TAGS: [[u8; 4]; 12] = [
+    *b"cmap", *b"head", *b"hhea", *b"hmtx", *b"maxp", *b"name", *b"OS/2", *b"post", *b"cvt ",
+    *b"fpgm", *b"glyf", *b"loca",
+];
+
+/// WOFF2 flag index saying the table's tag follows, rather than being one of the known ones.
+const WOFF2_ARBITRARY_TAG: u8 = 63;
+
+// Name IDs, with the typographic ones preferred since they group a family's weights together.
+const FAMILY_NAME_IDS: [u16; 2] = [16, 1];
+const STYLE_NAME_IDS: [u16; 2] = [17, 2];
+
+const ENGLISH_US: u16 = 0x409;
+
+pub fn is_font(mime_type: &str) -> bool {
+    FONT_MIME_TYPES.contains(&mime_type)
+}
+
+impl FontFormat {
+    /// The registered MIME type for fonts in this format.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            FontFormat::TrueType => "font/ttf",
+            FontFormat::OpenType => "font/otf",
+            FontFormat::Woff => "font/woff",
+            FontFormat::Woff2 => "font/woff2",
+        }
+    }
+}
+
+/// Reads `(format, family_name, style)` from a font. The format comes from the font's signature,
+/// since `infer` doesn't tell TrueType from OpenType or WOFF from WOFF2. Names are `None` when
+/// the font has no `name` table or no English or Unicode entry for them.
+pub fn font_info(buffer: &[u8]) -> Result<(FontFormat, Option<String>, Option<String>)> {
+    let format = match buffer.get(0..4) {
+        Some(b"\0\x01\0\0") | Some(b"true") => FontFormat::TrueType,
+        Some(b"OTTO") => FontFormat::OpenType,
+        Some(b"wOFF") => FontFormat::Woff,
+        Some(b"wOF2") => FontFormat::Woff2,
+        _ => return Err(anyhow!("Not a TrueType, OpenType, WOFF or WOFF2 font")),
+    };
+
+    let name_table = match format {
+        FontFormat::TrueType | FontFormat::OpenType => sfnt_table(buffer, NAME_TAG)?,
+        FontFormat::Woff => woff_table(buffer, NAME_TAG)?,
+        FontFormat::Woff2 => woff2_table(buffer, NAME_TAG)?,
+    };
+
+    let (family_name, style) = match name_table {
+        Some(table) => (
+            find_name(&table, &FAMILY_NAME_IDS)?,
+            find_name(&table, &STYLE_NAME_IDS)?,
+        ),
+        None => (None, None),
+    };
+
+    Ok((format, family_name, style))
+}
+
+/// A table from an uncompressed TrueType or OpenType font's table directory.
+fn sfnt_table(buffer: &[u8], tag: [u8; 4]) -> Result<Option<Vec<u8>>> {
+    let num_tables = u16_at(buffer, 4)?;
+
+    for index in 0..num_tables as usize {
+        let record = 12 + index * 16;
+
+        if bytes_at(buffer, record, 4)? == tag {
+            let offset = u32_at(buffer, record + 8)? as usize;
+            let length = u32_at(buffer, record + 12)? as usize;
+
+            return Ok(Some(bytes_at(buffer, offset, length)?.to_vec()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A table from a WOFF font, where each table is zlib-compressed unless that didn't make it
+/// smaller.
+fn woff_table(buffer: &[u8], tag: [u8; 4]) -> Result<Option<Vec<u8>>> {
+    let num_tables = u16_at(buffer, 12)?;
+
+    for index in 0..num_tables as usize {
+        let entry = 44 + index * 20;
+
+        if bytes_at(buffer, entry, 4)? == tag {
+            let offset = u32_at(buffer, entry + 4)? as usize;
+            let compressed_length = u32_at(buffer, entry + 8)? as usize;
+            let length = u32_at(buffer, entry + 12)? as usize;
+            let data = bytes_at(buffer, offset, compressed_length)?;
+
+            if compressed_length == length {
+                return Ok(Some(data.to_vec()));
+            }
+
+            let table = miniz_oxide::inflate::decompress_to_vec_zlib(data)
+                .map_err(|err| anyhow!("Failed to decompress WOFF table: {:?}", err))?;
+
+            return Ok(Some(table));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A table from a WOFF2 font, whose tables are Brotli-compressed together in one stream after
+/// the table directory. Font collections aren't read.
+fn woff2_table(buffer: &[u8], tag: [u8; 4]) -> Result<Option<Vec<u8>>> {
+    if bytes_at(buffer, 4, 4)? == b"ttcf" {
+        return Ok(None);
+    }
+
+    let num_tables = u16_at(buffer, 12)?;
+    let compressed_length = u32_at(buffer, 20)? as usize;
+
+    let mut position = 48;
+    let mut stream_offset = 0;
+    let mut found = None;
+
+    for _ in 0..num_tables {
+        let flags = *buffer
+            .get(position)
+            .ok_or_else(|| anyhow!("WOFF2 table directory is truncated"))?;
+        position += 1;
+
+        let entry_tag = match flags & 0x3f {
+            WOFF2_ARBITRARY_TAG => {
+                let entry_tag = bytes_at(buffer, position, 4)?.try_into()?;
+                position += 4;
+                entry_tag
+            }
+            index => WOFF2_KNOWN_TAGS
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default(),
+        };
+
+        let length = base128_at(buffer, &mut position)?;
+
+        // For `glyf` and `loca`, transform 3 is the null one. For every other table it's 0.
+        let transform = flags >> 6;
+        let transformed = match &entry_tag {
+            b"glyf" | b"loca" => transform != 3,
+            _ => transform != 0,
+        };
+        let length = if transformed {
+            base128_at(buffer, &mut position)?
+        } else {
+            length
+        };
+
+        if entry_tag == tag && found.is_none() {
+            found = Some((stream_offset, length as usize));
+        }
+
+        stream_offset += length as usize;
+    }
+
+    let (offset, length) = match found {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let mut stream = Vec::new();
+    brotli_decompressor::Decompressor::new(bytes_at(buffer, position, compressed_length)?, 4096)
+        .take((offset + length) as u64)
+        .read_to_end(&mut stream)
+        .context("Failed to decompress WOFF2 tables")?;
+
+    Ok(Some(bytes_at(&stream, offset, length)?.to_vec()))
+}
+
+/// The best record for the first of `name_ids` the table has: Windows US English, then any other
+/// Windows or Unicode record, then Mac Roman.
+fn find_name(table: &[u8], name_ids: &[u16]) -> Result<Option<String>> {
+    let count = u16_at(table, 2)?;
+    let storage = u16_at(table, 4)? as usize;
+
+    for &name_id in name_ids {
+        let mut best: Option<(u8, &[u8], bool)> = None;
+
+        for index in 0..count as usize {
+            let record = 6 + index * 12;
+
+            if u16_at(table, record + 6)? != name_id {
+                continue;
+            }
+
+            let platform = u16_at(table, record)?;
+            let encoding = u16_at(table, record + 2)?;
+            let language = u16_at(table, record + 4)?;
+
+            let (rank, utf16) = match (platform, encoding) {
+                (3, 1) | (3, 10) if language == ENGLISH_US => (0, true),
+                (3, 1) | (3, 10) | (0, _) => (1, true),
+                (1, 0) => (2, false),
+                _ => continue,
+            };
+
+            if !matches!(best, Some((best_rank, ..)) if best_rank <= rank) {
+                let length = u16_at(table, record + 8)? as usize;
+                let offset = u16_at(table, record + 10)? as usize;
+                best = Some((rank, bytes_at(table, storage + offset, length)?, utf16));
+            }
+        }
+
+        if let Some((_, raw, utf16)) = best {
+            let encoding = if utf16 { UTF_16BE } else { MACINTOSH };
+            let name = encoding
+                .decode_without_bom_handling(raw)
+                .0
+                .trim()
+                .to_string();
+
+            if !name.is_empty() {
+                return Ok(Some(name));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// WOFF2's variable-length integers, seven bits to a byte, most significant first.
+fn base128_at(buffer: &[u8], position: &mut usize) -> Result<u32> {
+    let mut value: u32 = 0;
+
+    for _ in 0..5 {
+        let byte = *buffer
+            .get(*position)
+            .ok_or_else(|| anyhow!("WOFF2 table directory is truncated"))?;
+        *position += 1;
+
+        value = value
+            .checked_mul(128)
+            .ok_or_else(|| anyhow!("WOFF2 length overflows"))?
+            | (byte & 0x7f) as u32;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(anyhow!("WOFF2 length is longer than five bytes"))
+}
+
+fn bytes_at(buffer: &[u8], offset: usize, length: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(length)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or_else(|| anyhow!("Font is truncated at offset {}", offset))
+}
+
+fn u16_at(buffer: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_be_bytes(bytes_at(buffer, offset, 2)?.try_into()?))
+}
+
+fn u32_at(buffer: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_be_bytes(bytes_at(buffer, offset, 4)?.try_into()?))
+}
@@ -0,0 +1,120 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::error;
+use walkdir::{DirEntry, WalkDir};
+
+/// Files found by [`walk_dir_with_options`], and the paths it passed over.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DirWalk {
+    pub files: Vec<PathBuf>,
+    /// Each with the reason: a symlink that wasn't followed, or one leading back into a directory
+    /// that was already walked.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Recursively collects every file under `path`. Symlinks aren't followed, to avoid cycles.
+pub fn walk_dir(path: &Path) -> Vec<PathBuf> {
+    walk_dir_filtered(path, false)
+}
+
+/// Like [`walk_dir`], optionally skipping dot-prefixed files and directories.
+pub fn walk_dir_filtered(path: &Path, skip_hidden: bool) -> Vec<PathBuf> {
+    walk_dir_with_options(path, skip_hidden, false).files
+}
+
+/// Like [`walk_dir_filtered`], also following symlinks if `follow_symlinks` is set. Each directory
+/// is only walked once, by its canonical path, so symlink cycles end rather than loop forever.
+pub fn walk_dir_with_options(path: &Path, skip_hidden: bool, follow_symlinks: bool) -> DirWalk {
+    let mut walk = DirWalk::default();
+    let mut visited = HashSet::new();
+    // Sorted, so which path to a directory reached more than once gets walked is predictable.
+    let mut entries = WalkDir::new(path)
+        .follow_links(follow_symlinks)
+        .sort_by_file_name()
+        .into_iter();
+
+    while let Some(entry) = entries.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Cycles through a directory's own ancestors are caught by `walkdir` itself.
+            Err(err) => match (err.path(), err.loop_ancestor()) {
+                (Some(link), Some(ancestor)) => {
+                    let reason = format!("Symlink loops back to {:?}", ancestor);
+                    walk.skipped.push((link.to_path_buf(), reason));
+                    continue;
+                }
+                _ => {
+                    error!(path = ?path, error = %err, "Error walking directory");
+                    continue;
+                }
+            },
+        };
+
+        if skip_hidden && is_hidden(&entry) {
+            if entry.file_type().is_dir() {
+                entries.skip_current_dir();
+            }
+        } else if entry.path_is_symlink() && !follow_symlinks {
+            let reason = "Symlink not followed".to_string();
+            walk.skipped.push((entry.into_path(), reason));
+        } else if entry.file_type().is_dir() {
+            let first_visit =
+                fs::canonicalize(entry.path()).map_or(true, |canonical| visited.insert(canonical));
+
+            if !first_visit {
+                entries.skip_current_dir();
+                let reason = "Directory already walked through another path".to_string();
+                walk.skipped.push((entry.into_path(), reason));
+            }
+        } else if entry.file_type().is_file() {
+            walk.files.push(entry.into_path());
+        }
+    }
+
+    walk
+}
+
+/// Include and exclude globs, such as `**/*.jpg`, matched against paths relative to a walk's root.
+/// `*` matches across directories, so `*.jpg` also matches `photos/cat.jpg`.
+#[derive(Clone, Debug)]
+pub struct PathFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(PathFilter {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    /// Whether `relative_path` matches an include glob, or there are none, and no exclude glob.
+    /// Excludes win when both match.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        (self.include.is_empty() || self.include.is_match(relative_path))
+            && !self.exclude.is_match(relative_path)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+
+    builder.build()
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    // The root itself is never considered hidden, even if it's something like `.config`.
+    entry.depth() > 0
+        && entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with('.'))
+}
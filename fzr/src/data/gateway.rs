@@ -0,0 +1,214 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use tide::http::Mime;
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::data::content::ContentItem;
+use crate::data::content_cid::ContentCid;
+use crate::data::error::FzrError;
+use crate::data::ipfs_client::IpfsClientRef;
+use crate::data::ipfs_ops::load_file;
+use crate::data::runtime;
+
+const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+const MARKDOWN_CONTENT_TYPE: &str = "text/markdown; charset=utf-8";
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// An HTTP gateway serving stored content at `GET /ipfs/<cid>`, with the `Content-Type` taken from
+/// its metadata so browsers render images and text inline. Single byte ranges are supported.
+/// `GET /health` answers 200 while the store is healthy and 503 otherwise, for liveness probes.
+pub fn app(ipfs_client: IpfsClientRef) -> tide::Server<IpfsClientRef> {
+    let mut app = tide::with_state(ipfs_client);
+    app.at("/ipfs/:cid").get(get_content);
+    app.at("/health").get(get_health);
+    app
+}
+
+async fn get_health(req: Request<IpfsClientRef>) -> tide::Result {
+    let ipfs_client = req.state().clone();
+    let health = runtime::compat(async move { ipfs_client.read().await.health().await }).await;
+
+    let status = if health.is_healthy() {
+        StatusCode::Ok
+    } else {
+        StatusCode::ServiceUnavailable
+    };
+    let block_count = health
+        .block_count
+        .map_or("unknown".to_string(), |count| count.to_string());
+
+    let mut response = Response::new(status);
+    response.set_body(format!(
+        "writable: {}\nblocks: {}\npeers: {}\n",
+        health.writable, block_count, health.peer_count
+    ));
+
+    Ok(response)
+}
+
+async fn get_content(req: Request<IpfsClientRef>) -> tide::Result {
+    let cid = match ContentCid::from_str(req.param("cid")?) {
+        Ok(cid) => cid,
+        Err(err) => return Ok(error_response(StatusCode::BadRequest, &err)),
+    };
+
+    let (bytes, content_type) = match runtime::compat(load_file(cid, req.state().clone())).await {
+        Ok(content) => response_body(content)?,
+        Err(err @ FzrError::NotFound(_)) => return Ok(error_response(StatusCode::NotFound, &err)),
+        Err(err) => return Ok(error_response(StatusCode::InternalServerError, &err)),
+    };
+
+    let range = req
+        .header("Range")
+        .map(|values| parse_range(values.last().as_str(), bytes.len()));
+
+    let len = bytes.len();
+    let mut response = match range {
+        None => {
+            let mut response = Response::new(StatusCode::Ok);
+            response.set_body(Body::from_bytes(bytes));
+            response
+        }
+        Some(Some((start, end))) => {
+            let mut response = Response::new(StatusCode::PartialContent);
+            response.insert_header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+            response.set_body(Body::from_bytes(bytes[start..=end].to_vec()));
+            response
+        }
+        Some(None) => {
+            let mut response = Response::new(StatusCode::RequestedRangeNotSatisfiable);
+            response.insert_header("Content-Range", format!("bytes */{}", len));
+            return Ok(response);
+        }
+    };
+
+    response.set_content_type(content_type);
+    response.insert_header("Accept-Ranges", "bytes");
+
+    Ok(response)
+}
+
+/// The bytes a content item was stored from, with the MIME type to serve them as.
+fn response_body(content: ContentItem) -> tide::Result<(Vec<u8>, Mime)> {
+    let bytes = Vec::try_from(&content)?;
+    let content_type = match &content {
+        ContentItem::Text(..) | ContentItem::CompressedText(..) => TEXT_CONTENT_TYPE,
+        ContentItem::Markdown(..) | ContentItem::CompressedMarkdown(..) => MARKDOWN_CONTENT_TYPE,
+        ContentItem::Csv(..) | ContentItem::CompressedCsv(..) => CSV_CONTENT_TYPE,
+        other => other.mime_type(),
+    };
+
+    Ok((bytes, Mime::from_str(content_type)?))
+}
+
+/// Parses a single-range `Range` header into inclusive `(start, end)` offsets within `len` bytes,
+/// or `None` if it can't be satisfied.
+fn parse_range(header: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = header.strip_prefix("bytes=")?.split_once('-')?;
+    let last = len.checked_sub(1)?;
+
+    match (start.trim(), end.trim()) {
+        ("", suffix_len) => {
+            let suffix_len: usize = suffix_len.parse().ok().filter(|&len| len > 0)?;
+            Some((len.saturating_sub(suffix_len), last))
+        }
+        (start, "") => {
+            let start: usize = start.parse().ok().filter(|&start| start <= last)?;
+            Some((start, last))
+        }
+        (start, end) => {
+            let start: usize = start.parse().ok().filter(|&start| start <= last)?;
+            let end: usize = end.parse().ok().filter(|&end| end >= start)?;
+            Some((start, end.min(last)))
+        }
+    }
+}
+
+fn error_response(status: StatusCode, err: &FzrError) -> Response {
+    let mut response = Response::new(status);
+    response.set_body(err.to_string());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ipfs_client::IpfsClient;
+    use crate::data::ipfs_ops::store_file;
+
+    use crate::data::runtime::{Arc, RwLock};
+
+    use async_std::task;
+    use image::{DynamicImage, ImageFormat};
+    use tempfile::tempdir;
+    use tide::listener::Listener;
+
+    use std::error::Error;
+    use std::fs;
+
+    /// Serves the gateway on an ephemeral local port, returning its base URL.
+    async fn serve(ipfs_client: IpfsClientRef) -> Result<String, Box<dyn Error>> {
+        let mut listener = app(ipfs_client).bind("127.0.0.1:0").await?;
+        let base_url = listener.info()[0].connection().to_string();
+        task::spawn(async move { listener.accept().await });
+
+        Ok(base_url)
+    }
+
+    #[runtime::test]
+    async fn test_gateway() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 3).write_to(&mut png, ImageFormat::Png)?;
+        let image_path = dir.path().join("small.png");
+        fs::write(&image_path, &png)?;
+        let image_cid = store_file(image_path, client_ref.clone()).await?.unwrap();
+
+        let text_path = dir.path().join("howdy.txt");
+        fs::write(&text_path, "howdy")?;
+        let text_cid = store_file(text_path, client_ref.clone()).await?.unwrap();
+
+        let base_url = serve(client_ref).await?;
+
+        let mut response = surf::get(format!("{}/ipfs/{}", base_url, image_cid)).await?;
+        assert_eq!(StatusCode::Ok, response.status());
+        assert_eq!("image/png", response.content_type().unwrap().essence());
+        assert_eq!(png, response.body_bytes().await?);
+
+        let mut response = surf::get(format!("{}/ipfs/{}", base_url, text_cid)).await?;
+        assert_eq!(
+            Some(Mime::from_str(TEXT_CONTENT_TYPE).unwrap()),
+            response.content_type()
+        );
+        assert_eq!("howdy", response.body_string().await?);
+
+        let mut response = surf::get(format!("{}/ipfs/{}", base_url, text_cid))
+            .header("Range", "bytes=1-3")
+            .await?;
+        assert_eq!(StatusCode::PartialContent, response.status());
+        assert_eq!("owd", response.body_string().await?);
+
+        let response = surf::get(format!("{}/ipfs/not-a-cid", base_url)).await?;
+        assert_eq!(StatusCode::BadRequest, response.status());
+
+        let response = surf::get(format!("{}/health", base_url)).await?;
+        assert_eq!(StatusCode::Ok, response.status());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(Some((0, 4)), parse_range("bytes=0-4", 10));
+        assert_eq!(Some((5, 9)), parse_range("bytes=5-", 10));
+        assert_eq!(Some((7, 9)), parse_range("bytes=-3", 10));
+        assert_eq!(Some((8, 9)), parse_range("bytes=8-100", 10));
+        assert_eq!(None, parse_range("bytes=10-", 10));
+        assert_eq!(None, parse_range("bytes=4-2", 10));
+        assert_eq!(None, parse_range("items=0-4", 10));
+        assert_eq!(None, parse_range("bytes=0-0", 0));
+    }
+}
\ No newline at end of file
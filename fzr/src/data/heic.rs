@@ -0,0 +1,77 @@
+//! HEIC and HEIF images, as taken by iPhones. `image` can't decode them, so reading them goes
+//! through libheif, which is only linked with the `heic` feature. Without it these functions fail
+//! and HEIC files are stored as binary.
+
+use anyhow::Result;
+
+#[cfg(not(feature = "heic"))]
+use anyhow::anyhow;
+#[cfg(feature = "heic")]
+use anyhow::Context;
+#[cfg(feature = "heic")]
+use image::codecs::jpeg::JpegEncoder;
+#[cfg(feature = "heic")]
+use image::{DynamicImage, RgbImage};
+#[cfg(feature = "heic")]
+use libheif_rs::{ColorSpace, HeifContext, RgbChroma};
+
+/// HEIC and HEIF MIME types, including the one `infer` reports for both.
+const HEIC_MIME_TYPES: [&str; 2] = ["image/heif", "image/heic"];
+
+/// Whether this build can read HEIC images.
+pub const HEIC_SUPPORTED: bool = cfg!(feature = "heic");
+
+pub fn is_heic(mime_type: &str) -> bool {
+    HEIC_MIME_TYPES.contains(&mime_type)
+}
+
+/// Reads `(width_px, height_px)` of the primary image from the container, without decoding it.
+#[cfg(feature = "heic")]
+pub fn heic_dimensions(buffer: &[u8]) -> Result<(u32, u32)> {
+    let context = HeifContext::read_from_bytes(buffer)?;
+    let handle = context.primary_image_handle()?;
+
+    Ok((handle.width(), handle.height()))
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn heic_dimensions(_buffer: &[u8]) -> Result<(u32, u32)> {
+    Err(anyhow!("Reading HEIC images needs the heic feature"))
+}
+
+/// Decodes the primary image and re-encodes it as a JPEG at `quality` (1-100).
+#[cfg(feature = "heic")]
+pub fn to_jpeg(buffer: &[u8], quality: u8) -> Result<Vec<u8>> {
+    let context = HeifContext::read_from_bytes(buffer)?;
+    let image = context
+        .primary_image_handle()?
+        .decode(ColorSpace::Rgb(RgbChroma::Rgb), false)?;
+
+    let planes = image.planes();
+    let plane = planes
+        .interleaved
+        .context("Decoded HEIC image has no interleaved RGB plane")?;
+
+    // Rows may be padded out to the stride.
+    let row_len = plane.width as usize * 3;
+    let pixels = plane
+        .data
+        .chunks(plane.stride)
+        .take(plane.height as usize)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect();
+    let rgb = RgbImage::from_raw(plane.width, plane.height, pixels)
+        .context("Decoded HEIC image is smaller than its dimensions")?;
+
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, quality)
+        .encode_image(&DynamicImage::ImageRgb8(rgb))?;
+
+    Ok(encoded)
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn to_jpeg(_buffer: &[u8], _quality: u8) -> Result<Vec<u8>> {
+    Err(anyhow!("Transcoding HEIC images needs the heic feature"))
+}
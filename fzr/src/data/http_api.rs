@@ -0,0 +1,665 @@
+use anyhow::{anyhow, Context, Error};
+use libipld::{Cid, Result};
+use serde::Deserialize;
+use std::str::FromStr;
+use surf::{Body, Client, Response, Url};
+
+/// Multicodec codes for the block encodings this crate stores.
+const DAG_CBOR_CODEC: u64 = 0x71;
+const RAW_CODEC: u64 = 0x55;
+
+/// Multihash codes for the hash functions blocks can be stored under.
+const BLAKE3_256_HASH: u64 = 0x1e;
+const SHA2_256_HASH: u64 = 0x12;
+
+/// `Type` of a `routing/findprovs` event that reports providers.
+const PROVIDER_EVENT: u32 = 4;
+
+/// Separates the block data from the surrounding form fields in a `block/put` upload.
+const MULTIPART_BOUNDARY: &str = "fzr-block-upload-boundary-7d1c6a0f";
+
+/// Minimal client for the `/api/v0` HTTP API of an external IPFS daemon such as kubo, covering
+/// just the block and pin operations `IpfsClient` needs.
+#[derive(Clone)]
+pub struct HttpApi {
+    client: Client,
+    base_url: Url,
+}
+
+#[derive(Deserialize)]
+struct BlockKey {
+    #[serde(rename = "Key")]
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct PublishedName {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ResolvedPath {
+    #[serde(rename = "Path")]
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct KeyList {
+    #[serde(rename = "Keys")]
+    keys: Vec<KeyName>,
+}
+
+#[derive(Deserialize)]
+struct KeyName {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SwarmPeers {
+    /// Null rather than empty when the daemon has no connections.
+    #[serde(rename = "Peers", default)]
+    peers: Option<Vec<SwarmPeer>>,
+}
+
+#[derive(Deserialize)]
+struct SwarmPeer {
+    #[serde(rename = "Peer")]
+    peer: String,
+}
+
+/// One line of `routing/findprovs` output. Only lines of type [`PROVIDER_EVENT`] list providers.
+#[derive(Deserialize)]
+struct RoutingEvent {
+    #[serde(rename = "Type")]
+    event_type: u32,
+    #[serde(rename = "Responses", default)]
+    responses: Option<Vec<RoutingPeer>>,
+}
+
+#[derive(Deserialize)]
+struct RoutingPeer {
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct LocalRef {
+    #[serde(rename = "Ref")]
+    cid: String,
+    #[serde(rename = "Err", default)]
+    err: String,
+}
+
+/// One line of `repo/gc` output, naming either a removed block or a failure.
+#[derive(Deserialize)]
+struct GcResult {
+    #[serde(rename = "Key", default)]
+    key: Option<CidLink>,
+    #[serde(rename = "Error", default)]
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct CidLink {
+    #[serde(rename = "/")]
+    cid: String,
+}
+
+#[derive(Deserialize)]
+struct RepoStat {
+    #[serde(rename = "RepoSize")]
+    repo_size: u64,
+    // Left out of `size-only` stats.
+    #[serde(rename = "NumObjects", default)]
+    num_objects: u64,
+}
+
+impl HttpApi {
+    /// Creates a client for the daemon whose API listens at `api_url`, such as
+    /// `http://127.0.0.1:5001`.
+    pub fn new(api_url: &str) -> Result<HttpApi> {
+        let base_url = Url::parse(api_url)
+            .and_then(|url| url.join("api/v0/"))
+            .with_context(|| format!("Invalid IPFS API URL: {}", api_url))?;
+
+        Ok(HttpApi {
+            client: Client::new(),
+            base_url,
+        })
+    }
+
+    /// Uploads a block, checking that the daemon stored it under the CID it was encoded with.
+    pub async fn put_block(&self, cid: &Cid, data: &[u8]) -> Result<()> {
+        let format = match cid.codec() {
+            DAG_CBOR_CODEC => "cbor",
+            RAW_CODEC => "raw",
+            codec => return Err(anyhow!("Unsupported block codec: {:#x}", codec)),
+        };
+        let mhtype = match cid.hash().code() {
+            BLAKE3_256_HASH => "blake3",
+            SHA2_256_HASH => "sha2-256",
+            code => return Err(anyhow!("Unsupported multihash: {:#x}", code)),
+        };
+
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"block\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            MULTIPART_BOUNDARY
+        )
+        .into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+
+        let content_type = format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY);
+        let mut body = Body::from_bytes(body);
+        body.set_mime(content_type.as_str());
+
+        let response = self
+            .post(
+                "block/put",
+                &[("format", format), ("mhtype", mhtype), ("mhlen", "32")],
+                Some(body),
+            )
+            .await?;
+        let stored: BlockKey = serde_json::from_slice(&response)?;
+        let stored_cid = Cid::from_str(&stored.key)?;
+
+        if stored_cid.hash() != cid.hash() {
+            return Err(anyhow!(
+                "IPFS daemon stored block as {} instead of {}",
+                stored_cid,
+                cid
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>> {
+        self.post("block/get", &[("arg", &cid.to_string())], None)
+            .await
+    }
+
+    /// Checks the daemon's local store only, without searching the network for the block.
+    pub async fn has_block(&self, cid: &Cid) -> Result<bool> {
+        let response = self
+            .call(
+                "block/stat",
+                &[("arg", &cid.to_string()), ("offline", "true")],
+                None,
+            )
+            .await?;
+
+        Ok(response.status().is_success())
+    }
+
+    pub async fn remove_block(&self, cid: &Cid) -> Result<()> {
+        self.post("block/rm", &[("arg", &cid.to_string())], None)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_blocks(&self) -> Result<Vec<Cid>> {
+        let response = self.post("refs/local", &[], None).await?;
+
+        serde_json::Deserializer::from_slice(&response)
+            .into_iter::<LocalRef>()
+            .map(|local_ref| {
+                let local_ref = local_ref?;
+
+                if !local_ref.err.is_empty() {
+                    return Err(anyhow!("Failed to list local blocks: {}", local_ref.err));
+                }
+
+                Ok(Cid::from_str(&local_ref.cid)?)
+            })
+            .collect()
+    }
+
+    /// Runs the daemon's garbage collector, returning the CIDs of the blocks it removed.
+    pub async fn repo_gc(&self) -> Result<Vec<Cid>> {
+        let response = self.post("repo/gc", &[], None).await?;
+
+        serde_json::Deserializer::from_slice(&response)
+            .into_iter::<GcResult>()
+            .filter_map(|result| match result {
+                Ok(result) if !result.error.is_empty() => {
+                    Some(Err(anyhow!("Failed to collect garbage: {}", result.error)))
+                }
+                Ok(result) => result.key.map(|key| Ok(Cid::from_str(&key.cid)?)),
+                Err(err) => Some(Err(err.into())),
+            })
+            .collect()
+    }
+
+    /// Total bytes the daemon's repo takes up on disk.
+    pub async fn repo_size(&self) -> Result<u64> {
+        let response = self
+            .post("repo/stat", &[("size-only", "true")], None)
+            .await?;
+        let stat: RepoStat = serde_json::from_slice(&response)?;
+
+        Ok(stat.repo_size)
+    }
+
+    /// Number of blocks in the daemon's repo, which it has to count, so this is slower than
+    /// `repo_size`.
+    pub async fn repo_num_objects(&self) -> Result<u64> {
+        let response = self.post("repo/stat", &[], None).await?;
+        let stat: RepoStat = serde_json::from_slice(&response)?;
+
+        Ok(stat.num_objects)
+    }
+
+    pub async fn pin(&self, cid: &Cid) -> Result<()> {
+        self.post("pin/add", &[("arg", &cid.to_string())], None)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn unpin(&self, cid: &Cid) -> Result<()> {
+        self.post("pin/rm", &[("arg", &cid.to_string())], None)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_pinned(&self, cid: &Cid) -> Result<bool> {
+        let response = self
+            .call(
+                "pin/ls",
+                &[("arg", &cid.to_string()), ("type", "recursive")],
+                None,
+            )
+            .await?;
+
+        Ok(response.status().is_success())
+    }
+
+    /// Publishes an IPNS record pointing at `cid`, signed with the daemon's key named `key_name`,
+    /// which is generated first if the daemon doesn't have it yet. Returns the IPNS name.
+    pub async fn publish_name(&self, cid: &Cid, key_name: &str) -> Result<String> {
+        let response = self.post("key/list", &[], None).await?;
+        let key_list: KeyList = serde_json::from_slice(&response)?;
+
+        if !key_list.keys.iter().any(|key| key.name == key_name) {
+            self.post("key/gen", &[("arg", key_name), ("type", "ed25519")], None)
+                .await?;
+        }
+
+        let path = format!("/ipfs/{}", cid);
+        let response = self
+            .post("name/publish", &[("arg", &path), ("key", key_name)], None)
+            .await?;
+        let published: PublishedName = serde_json::from_slice(&response)?;
+
+        Ok(published.name)
+    }
+
+    pub async fn resolve_name(&self, name: &str) -> Result<Cid> {
+        let response = self.post("name/resolve", &[("arg", name)], None).await?;
+        let resolved: ResolvedPath = serde_json::from_slice(&response)?;
+
+        let cid = resolved
+            .path
+            .strip_prefix("/ipfs/")
+            .ok_or_else(|| anyhow!("IPNS name resolved to a non-IPFS path: {}", resolved.path))?;
+
+        Ok(Cid::from_str(cid)?)
+    }
+
+    /// Has the daemon dial `addr`, which must end in the peer's `/p2p/` ID.
+    pub async fn swarm_connect(&self, addr: &str) -> Result<()> {
+        self.post("swarm/connect", &[("arg", addr)], None).await?;
+
+        Ok(())
+    }
+
+    /// Lists the IDs of the peers the daemon is connected to.
+    pub async fn swarm_peers(&self) -> Result<Vec<String>> {
+        let response = self.post("swarm/peers", &[], None).await?;
+        let swarm_peers: SwarmPeers = serde_json::from_slice(&response)?;
+
+        Ok(swarm_peers
+            .peers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|peer| peer.peer)
+            .collect())
+    }
+
+    /// Announces to the DHT that the daemon can provide `cid`.
+    pub async fn routing_provide(&self, cid: &Cid) -> Result<()> {
+        self.post("routing/provide", &[("arg", &cid.to_string())], None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Looks `cid` up in the DHT, returning the IDs of the peers providing it.
+    pub async fn routing_find_providers(&self, cid: &Cid) -> Result<Vec<String>> {
+        let response = self
+            .post("routing/findprovs", &[("arg", &cid.to_string())], None)
+            .await?;
+
+        let mut providers = Vec::new();
+
+        for line in response.split(|&byte| byte == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+
+            let event: RoutingEvent = serde_json::from_slice(line)?;
+
+            if event.event_type == PROVIDER_EVENT {
+                providers.extend(event.responses.into_iter().flatten().map(|peer| peer.id));
+            }
+        }
+
+        Ok(providers)
+    }
+
+    /// Calls an API endpoint and returns the response body, failing on an error status.
+    async fn post(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: Option<Body>,
+    ) -> Result<Vec<u8>> {
+        let mut response = self.call(endpoint, query, body).await?;
+        let body = response.body_bytes().await.map_err(Error::msg)?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "IPFS API call to {} failed with status {}: {}",
+                endpoint,
+                response.status(),
+                String::from_utf8_lossy(&body)
+            ));
+        }
+
+        Ok(body)
+    }
+
+    /// Calls an API endpoint, failing only if the daemon couldn't be reached.
+    async fn call(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: Option<Body>,
+    ) -> Result<Response> {
+        let mut url = self.base_url.join(endpoint)?;
+        url.query_pairs_mut().extend_pairs(query);
+
+        let mut request = self.client.post(url);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+
+        request
+            .await
+            .map_err(Error::msg)
+            .with_context(|| format!("Failed to reach IPFS API endpoint: {}", endpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::cbor::DagCborCodec;
+    use libipld::multihash::Code;
+    use libipld::store::DefaultParams;
+
+    use async_std::io::{ReadExt, WriteExt};
+    use async_std::net::TcpListener;
+    use async_std::task::{self, JoinHandle};
+    use std::error::Error;
+
+    /// Serves one canned `(status, body)` response per connection, returning the request line of
+    /// each request it received.
+    async fn mock_server(responses: Vec<(u16, Vec<u8>)>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = task::spawn(async move {
+            let mut requests = Vec::new();
+
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let request = String::from_utf8_lossy(&request).into_owned();
+                requests.push(request.lines().next().unwrap().to_string());
+
+                let head = format!(
+                    "HTTP/1.1 {} OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(&body).await.unwrap();
+            }
+
+            requests
+        });
+
+        (url, handle)
+    }
+
+    #[async_std::test]
+    async fn test_put_and_get_block() -> Result<(), Box<dyn Error>> {
+        let block = libipld::Block::<DefaultParams>::encode(
+            DagCborCodec,
+            Code::Blake3_256,
+            &"howdy".to_string(),
+        )?;
+        let cid = *block.cid();
+
+        let (url, server) = mock_server(vec![
+            (
+                200,
+                format!("{{\"Key\":\"{}\",\"Size\":6}}", cid).into_bytes(),
+            ),
+            (200, block.data().to_vec()),
+            (
+                500,
+                b"{\"Message\":\"blockservice: key not found\"}".to_vec(),
+            ),
+        ])
+        .await;
+
+        let api = HttpApi::new(&url)?;
+        api.put_block(&cid, block.data()).await?;
+        assert_eq!(block.data(), api.get_block(&cid).await?.as_slice());
+        assert!(!api.has_block(&cid).await?);
+
+        let requests = server.await;
+        assert!(requests[0]
+            .starts_with("POST /api/v0/block/put?format=cbor&mhtype=blake3&mhlen=32 HTTP/1.1"));
+        assert!(requests[1].starts_with(&format!("POST /api/v0/block/get?arg={} ", cid)));
+        assert!(requests[2].starts_with(&format!(
+            "POST /api/v0/block/stat?arg={}&offline=true ",
+            cid
+        )));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_put_block_rejects_mismatched_cid() -> Result<(), Box<dyn Error>> {
+        let block = libipld::Block::<DefaultParams>::encode(
+            DagCborCodec,
+            Code::Blake3_256,
+            &"howdy".to_string(),
+        )?;
+        let other = libipld::Block::<DefaultParams>::encode(
+            DagCborCodec,
+            Code::Blake3_256,
+            &"other".to_string(),
+        )?;
+
+        let (url, _server) = mock_server(vec![(
+            200,
+            format!("{{\"Key\":\"{}\",\"Size\":6}}", other.cid()).into_bytes(),
+        )])
+        .await;
+
+        let api = HttpApi::new(&url)?;
+        assert!(api.put_block(block.cid(), block.data()).await.is_err());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_publish_and_resolve_name() -> Result<(), Box<dyn Error>> {
+        let block = libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &1)?;
+        let cid = block.cid();
+        let name = "k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8";
+
+        let (url, server) = mock_server(vec![
+            (
+                200,
+                b"{\"Keys\":[{\"Name\":\"self\",\"Id\":\"k51\"}]}".to_vec(),
+            ),
+            (200, b"{\"Name\":\"profile\",\"Id\":\"k51\"}".to_vec()),
+            (
+                200,
+                format!("{{\"Name\":\"{}\",\"Value\":\"/ipfs/{}\"}}", name, cid).into_bytes(),
+            ),
+            (200, format!("{{\"Path\":\"/ipfs/{}\"}}", cid).into_bytes()),
+        ])
+        .await;
+
+        let api = HttpApi::new(&url)?;
+        assert_eq!(name, api.publish_name(cid, "profile").await?);
+        assert_eq!(*cid, api.resolve_name(name).await?);
+
+        let requests = server.await;
+        assert!(requests[1].starts_with("POST /api/v0/key/gen?arg=profile&type=ed25519 "));
+        assert!(requests[2].starts_with(&format!(
+            "POST /api/v0/name/publish?arg=%2Fipfs%2F{}&key=profile ",
+            cid
+        )));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_list_blocks() -> Result<(), Box<dyn Error>> {
+        let first = libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &1)?;
+        let second = libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &2)?;
+
+        let listing = format!(
+            "{{\"Ref\":\"{}\",\"Err\":\"\"}}\n{{\"Ref\":\"{}\",\"Err\":\"\"}}\n",
+            first.cid(),
+            second.cid()
+        );
+        let (url, _server) = mock_server(vec![(200, listing.into_bytes())]).await;
+
+        let api = HttpApi::new(&url)?;
+        assert_eq!(vec![*first.cid(), *second.cid()], api.list_blocks().await?);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_repo_gc() -> Result<(), Box<dyn Error>> {
+        let block = libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &1)?;
+
+        let (url, server) = mock_server(vec![
+            (
+                200,
+                format!("{{\"Key\":{{\"/\":\"{}\"}}}}\n", block.cid()).into_bytes(),
+            ),
+            (200, b"{\"RepoSize\":4096,\"StorageMax\":0}".to_vec()),
+            (200, b"{\"Error\":\"could not remove block\"}\n".to_vec()),
+            (
+                200,
+                b"{\"NumObjects\":12,\"RepoSize\":4096,\"StorageMax\":0}".to_vec(),
+            ),
+        ])
+        .await;
+
+        let api = HttpApi::new(&url)?;
+        assert_eq!(vec![*block.cid()], api.repo_gc().await?);
+        assert_eq!(4096, api.repo_size().await?);
+        assert!(api.repo_gc().await.is_err());
+        assert_eq!(12, api.repo_num_objects().await?);
+
+        let requests = server.await;
+        assert!(requests[1].starts_with("POST /api/v0/repo/stat?size-only=true "));
+        assert!(
+            requests[3].starts_with("POST /api/v0/repo/stat") && !requests[3].contains("size-only")
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_swarm_peers() -> Result<(), Box<dyn Error>> {
+        let peer = "12D3KooWLPxG2WbFHApBsGV4WDrLnKJn2FY4ovnTbe4FwhiQBCs4";
+        let (url, server) = mock_server(vec![
+            (200, b"{\"Strings\":[\"connect 12D3 success\"]}".to_vec()),
+            (
+                200,
+                format!(
+                    "{{\"Peers\":[{{\"Addr\":\"/ip4/10.0.0.2/tcp/4001\",\"Peer\":\"{}\"}}]}}",
+                    peer
+                )
+                .into_bytes(),
+            ),
+            (200, b"{\"Peers\":null}".to_vec()),
+        ])
+        .await;
+
+        let api = HttpApi::new(&url)?;
+        let addr = format!("/ip4/10.0.0.2/tcp/4001/p2p/{}", peer);
+        api.swarm_connect(&addr).await?;
+        assert_eq!(vec![peer.to_string()], api.swarm_peers().await?);
+        assert!(api.swarm_peers().await?.is_empty());
+
+        let requests = server.await;
+        assert!(requests[0].starts_with(
+            "POST /api/v0/swarm/connect?arg=%2Fip4%2F10.0.0.2%2Ftcp%2F4001%2Fp2p%2F12D3KooW"
+        ));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_routing_find_providers() -> Result<(), Box<dyn Error>> {
+        let block = libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &1)?;
+        let peer = "12D3KooWLPxG2WbFHApBsGV4WDrLnKJn2FY4ovnTbe4FwhiQBCs4";
+
+        let events = format!(
+            "{{\"Type\":0,\"Responses\":null}}\n{{\"Type\":4,\"Responses\":[{{\"ID\":\"{}\",\"Addrs\":[]}}]}}\n",
+            peer
+        );
+        let (url, server) = mock_server(vec![(200, Vec::new()), (200, events.into_bytes())]).await;
+
+        let api = HttpApi::new(&url)?;
+        api.routing_provide(block.cid()).await?;
+        assert_eq!(
+            vec![peer.to_string()],
+            api.routing_find_providers(block.cid()).await?
+        );
+
+        let requests = server.await;
+        assert!(requests[0].starts_with(&format!(
+            "POST /api/v0/routing/provide?arg={} ",
+            block.cid()
+        )));
+
+        Ok(())
+    }
+}
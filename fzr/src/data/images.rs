@@ -0,0 +1,390 @@
+#[cfg(not(feature = "webp"))]
+use anyhow::anyhow;
+use anyhow::{ensure, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::cmp::Reverse;
+
+use crate::data::content::{Fingerprint, ImageMetadata};
+
+/// Side length of the grid compared by `perceptual_hash`, giving a 64-bit hash.
+const PHASH_SIZE: u32 = 8;
+
+/// Longest side images are downscaled to before `dominant_colors` samples their pixels.
+const PALETTE_SAMPLE_SIZE: u32 = 64;
+
+/// Longest side images are downscaled to before computing a BlurHash, which keeps only the
+/// coarsest detail anyway.
+const BLURHASH_SAMPLE_SIZE: u32 = 32;
+
+/// BlurHash encodes between 1 and 9 components along each axis.
+const MAX_BLURHASH_COMPONENTS: u32 = 9;
+
+/// Quality thumbnails are encoded at in the lossy formats.
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Format thumbnails are encoded in. WebP is much smaller for photos, so it's the default when
+/// the `webp` feature provides an encoder, and PNG otherwise.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThumbnailFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl Default for ThumbnailFormat {
+    fn default() -> Self {
+        if cfg!(feature = "webp") {
+            ThumbnailFormat::WebP
+        } else {
+            ThumbnailFormat::Png
+        }
+    }
+}
+
+impl From<ThumbnailFormat> for ImageFormat {
+    fn from(format: ThumbnailFormat) -> Self {
+        match format {
+            ThumbnailFormat::Png => ImageFormat::Png,
+            ThumbnailFormat::Jpeg => ImageFormat::Jpeg,
+            ThumbnailFormat::WebP => ImageFormat::WebP,
+        }
+    }
+}
+
+/// Downscales an encoded image so its longest side is at most `max_dimension`, preserving the
+/// aspect ratio, and encodes the thumbnail as `format`.
+pub fn thumbnail(
+    buffer: &[u8],
+    max_dimension: u32,
+    format: ThumbnailFormat,
+) -> Result<(Vec<u8>, ImageMetadata)> {
+    let img = image::load_from_memory(buffer)?;
+
+    let thumbnail = downscale(img, max_dimension);
+
+    let encoded = encode_as(&thumbnail, format.into(), THUMBNAIL_QUALITY)?;
+    let (width_px, height_px) = thumbnail.dimensions();
+
+    let metadata = ImageMetadata {
+        size_bytes: encoded.len() as u64,
+        fingerprint: Fingerprint::of(&encoded),
+        mime_type: mime_type(&encoded),
+        width_px,
+        height_px,
+        exif: None,
+        phash: None,
+        dominant_colors: Vec::new(),
+        blurhash: None,
+        frame_count: None,
+        loop_count: None,
+        original_format: None,
+    };
+
+    Ok((encoded, metadata))
+}
+
+/// Re-encodes an image in its own format, dropping EXIF and any other metadata segments.
+pub fn strip_metadata(buffer: &[u8]) -> Result<Vec<u8>> {
+    let format = image::guess_format(buffer)?;
+    let img = image::load_from_memory_with_format(buffer, format)?;
+
+    encode(&img, format)
+}
+
+/// Rotates and flips an image according to its EXIF orientation (1-8) and re-encodes the
+/// upright result, returning it with its new `(width_px, height_px)`.
+pub fn apply_orientation(buffer: &[u8], orientation: u16) -> Result<(Vec<u8>, u32, u32)> {
+    let format = image::guess_format(buffer)?;
+    let img = image::load_from_memory_with_format(buffer, format)?;
+
+    let img = match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    };
+
+    let (width_px, height_px) = img.dimensions();
+
+    Ok((encode(&img, format)?, width_px, height_px))
+}
+
+/// Decodes an image and re-encodes it as `target`, returning it with its `(width_px, height_px)`.
+/// `quality` (1-100) applies to the lossy JPEG and WebP encoders and is ignored otherwise.
+/// Encoding WebP needs the `webp` feature.
+pub fn convert(buffer: &[u8], target: ImageFormat, quality: u8) -> Result<(Vec<u8>, u32, u32)> {
+    let img = image::load_from_memory(buffer)?;
+    let (width_px, height_px) = img.dimensions();
+
+    Ok((encode_as(&img, target, quality)?, width_px, height_px))
+}
+
+/// Encodes in `target`, with `quality` (1-100) for the lossy JPEG and WebP encoders.
+fn encode_as(img: &DynamicImage, target: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let encoded = match target {
+        // JPEG has no alpha channel.
+        ImageFormat::Jpeg => {
+            let mut encoded = Vec::new();
+            JpegEncoder::new_with_quality(&mut encoded, quality)
+                .encode_image(&DynamicImage::ImageRgb8(img.to_rgb8()))?;
+            encoded
+        }
+        ImageFormat::WebP => encode_webp(img, quality)?,
+        format => {
+            let mut encoded = Vec::new();
+            img.write_to(&mut encoded, format)?;
+            encoded
+        }
+    };
+
+    Ok(encoded)
+}
+
+/// `image` can only decode WebP, so encode with libwebp.
+#[cfg(feature = "webp")]
+fn encode_webp(img: &DynamicImage, quality: u8) -> Result<Vec<u8>> {
+    let rgba = img.to_rgba8();
+    let (width_px, height_px) = rgba.dimensions();
+
+    Ok(webp::Encoder::from_rgba(&rgba, width_px, height_px)
+        .encode(f32::from(quality))
+        .to_vec())
+}
+
+#[cfg(not(feature = "webp"))]
+fn encode_webp(_img: &DynamicImage, _quality: u8) -> Result<Vec<u8>> {
+    Err(anyhow!("Encoding WebP images needs the webp feature"))
+}
+
+/// Computes a 64-bit difference hash (dHash) of an image. Visually similar images have hashes
+/// a small [`phash_distance`] apart, even when their bytes differ.
+pub fn perceptual_hash(buffer: &[u8]) -> Result<u64> {
+    let img = image::load_from_memory(buffer)?;
+    let gray = img
+        .resize_exact(PHASH_SIZE + 1, PHASH_SIZE, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0;
+
+    for y in 0..PHASH_SIZE {
+        for x in 0..PHASH_SIZE {
+            hash <<= 1;
+
+            if gray.get_pixel(x, y)[0] < gray.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Extracts up to `count` dominant RGB colors by median cut, most common first. Images with
+/// fewer distinct colors give a shorter palette.
+pub fn dominant_colors(buffer: &[u8], count: usize) -> Result<Vec<(u8, u8, u8)>> {
+    let img = image::load_from_memory(buffer)?;
+    let sample = downscale(img, PALETTE_SAMPLE_SIZE);
+
+    let pixels: Vec<[u8; 3]> = sample.to_rgb8().pixels().map(|pixel| pixel.0).collect();
+    let mut boxes = vec![pixels];
+
+    while boxes.len() < count {
+        // Split the box spanning the widest range in any one channel at that channel's median,
+        // keeping equal values together so one color never ends up in two boxes.
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, pixels)| (widest_channel(pixels), index))
+            .max();
+
+        match widest {
+            Some(((range, channel), index)) if range > 0 => {
+                let mut pixels = boxes.swap_remove(index);
+                pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+
+                let median = pixels[pixels.len() / 2][channel];
+                let split = match pixels.partition_point(|pixel| pixel[channel] < median) {
+                    0 => pixels.partition_point(|pixel| pixel[channel] <= median),
+                    split => split,
+                };
+                let upper = pixels.split_off(split);
+
+                boxes.push(pixels);
+                boxes.push(upper);
+            }
+            _ => break,
+        }
+    }
+
+    boxes.retain(|pixels| !pixels.is_empty());
+    boxes.sort_by_key(|pixels| Reverse(pixels.len()));
+
+    Ok(boxes
+        .iter()
+        .take(count)
+        .map(|pixels| average(pixels))
+        .collect())
+}
+
+/// Computes a [BlurHash](https://blurha.sh) placeholder with `components_x` by `components_y`
+/// components, each between 1 and 9. More components keep more detail in a longer string.
+pub fn blurhash(buffer: &[u8], components_x: u32, components_y: u32) -> Result<String> {
+    ensure!(
+        (1..=MAX_BLURHASH_COMPONENTS).contains(&components_x)
+            && (1..=MAX_BLURHASH_COMPONENTS).contains(&components_y),
+        "BlurHash needs 1 to {} components per axis, got {}x{}",
+        MAX_BLURHASH_COMPONENTS,
+        components_x,
+        components_y
+    );
+
+    let img = image::load_from_memory(buffer)?;
+    let sample = downscale(img, BLURHASH_SAMPLE_SIZE);
+    let (width_px, height_px) = sample.dimensions();
+
+    Ok(blurhash::encode(
+        components_x,
+        components_y,
+        width_px,
+        height_px,
+        &sample.to_rgba8(),
+    ))
+}
+
+/// Shrinks an image so its longest side is at most `max_dimension`, preserving the aspect ratio.
+/// Smaller images are returned unchanged.
+fn downscale(img: DynamicImage, max_dimension: u32) -> DynamicImage {
+    let (width_px, height_px) = img.dimensions();
+
+    if width_px <= max_dimension && height_px <= max_dimension {
+        img
+    } else {
+        img.thumbnail(max_dimension, max_dimension)
+    }
+}
+
+/// Returns the `(range, channel)` of the channel whose values vary the most.
+fn widest_channel(pixels: &[[u8; 3]]) -> (u8, usize) {
+    (0..3)
+        .map(|channel| {
+            let values = pixels.iter().map(|pixel| pixel[channel]);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+
+            (max - min, channel)
+        })
+        .max()
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[[u8; 3]]) -> (u8, u8, u8) {
+    let len = pixels.len() as u64;
+    let sum = |channel: usize| {
+        pixels
+            .iter()
+            .map(|pixel| u64::from(pixel[channel]))
+            .sum::<u64>()
+    };
+
+    (
+        (sum(0) / len) as u8,
+        (sum(1) / len) as u8,
+        (sum(2) / len) as u8,
+    )
+}
+
+/// Hamming distance between two perceptual hashes; lower means more similar.
+pub fn phash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Encodes in `format`, falling back to PNG for formats `image` can only decode.
+fn encode(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+
+    if img.write_to(&mut encoded, format).is_err() {
+        encoded.clear();
+        img.write_to(&mut encoded, ImageFormat::Png)?;
+    }
+
+    Ok(encoded)
+}
+
+pub fn mime_type(encoded: &[u8]) -> String {
+    infer::get(encoded)
+        .map_or("image/png", |kind| kind.mime_type())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn encode_png(img: GrayImage) -> Vec<u8> {
+        encode(&DynamicImage::ImageLuma8(img), ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn test_perceptual_hash() {
+        let original = GrayImage::from_fn(64, 64, |x, y| {
+            Luma([((x * 7 + y * 13) % 97 * 2 + x / 8 * 20) as u8])
+        });
+        let mut modified = original.clone();
+        for x in 0..4 {
+            for y in 0..4 {
+                modified.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let unrelated = GrayImage::from_fn(64, 64, |x, y| Luma([((x * 31 + y * y) % 251) as u8]));
+
+        let hash = perceptual_hash(&encode_png(original.clone())).unwrap();
+        let same_hash = perceptual_hash(&encode_png(original)).unwrap();
+        let modified_hash = perceptual_hash(&encode_png(modified)).unwrap();
+        let unrelated_hash = perceptual_hash(&encode_png(unrelated)).unwrap();
+
+        assert_eq!(hash, same_hash);
+        assert!(phash_distance(hash, modified_hash) <= 4);
+        assert!(phash_distance(hash, unrelated_hash) > 16);
+    }
+
+    #[test]
+    fn test_blurhash_components() {
+        let png = encode_png(GrayImage::from_fn(8, 8, |x, y| Luma([(x * y) as u8])));
+
+        assert_eq!(
+            1 + 1 + 4 + 2 * (2 * 2 - 1),
+            blurhash(&png, 2, 2).unwrap().len()
+        );
+        assert!(blurhash(&png, 0, 3).is_err());
+        assert!(blurhash(&png, 4, 10).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let png = encode_png(GrayImage::from_fn(32, 16, |x, y| Luma([(x * y) as u8])));
+
+        let (jpeg, width_px, height_px) = convert(&png, ImageFormat::Jpeg, 90).unwrap();
+        assert_eq!("image/jpeg", mime_type(&jpeg));
+        assert_eq!((32, 16), (width_px, height_px));
+
+        let (rough_jpeg, _, _) = convert(&png, ImageFormat::Jpeg, 10).unwrap();
+        assert!(rough_jpeg.len() < jpeg.len());
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn test_convert_webp() {
+        let png = encode_png(GrayImage::from_fn(32, 16, |x, y| Luma([(x * y) as u8])));
+
+        let (webp, width_px, height_px) = convert(&png, ImageFormat::WebP, 80).unwrap();
+        assert_eq!("image/webp", mime_type(&webp));
+        assert_eq!((32, 16), (width_px, height_px));
+    }
+}
@@ -1,19 +1,35 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
-use ipfs_embed::{Config, Ipfs};
+use ipfs_embed::{Config, Ipfs, Key, Multiaddr};
 use libipld::cbor::DagCborCodec;
-use libipld::multihash::Code;
+use libipld::codec::Encode;
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::raw::RawCodec;
 use libipld::store::StoreParams;
 use libipld::{Cid, IpldCodec, Result};
 
-use anyhow::Error;
-use async_std::sync::{Arc, RwLock};
+use anyhow::{anyhow, Context, Error};
 use directories_next::ProjectDirs;
+use futures::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::warn;
 
-use crate::data::content::ContentItemBlock;
+use crate::data::block_db::BlockDatabase;
+use crate::data::car;
+use crate::data::content::{ChunkedFileBlock, ContentItemBlock, EncryptedBlock, MetadataBlock};
+use crate::data::http_api::HttpApi;
+use crate::data::runtime::{self, Arc, RwLock, Semaphore};
+
+pub use ipfs_embed::PeerId;
 
 pub type IpfsClientRef = Arc<RwLock<IpfsClient>>;
 
+type Block = libipld::Block<MaxBlockSizeStoreParams>;
+
 #[derive(Clone, Debug, Default)]
 struct MaxBlockSizeStoreParams;
 
@@ -23,46 +39,969 @@ impl StoreParams for MaxBlockSizeStoreParams {
     type Hashes = Code;
 }
 
+/// Storage footprint of a single block, as reported by [`IpfsClient::stat`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockStat {
+    /// Size of the encoded block, not including any blocks it links to.
+    pub size_bytes: u64,
+    pub num_links: usize,
+    /// Multicodec code of the block's encoding, e.g. `0x71` for DAG-CBOR.
+    pub codec: u64,
+}
+
+/// What [`IpfsClient::gc`] removed from the store.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GcReport {
+    pub blocks_removed: u64,
+    pub bytes_freed: u64,
+}
+
+/// What [`IpfsClient::health`] found, for a service's readiness and liveness probes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthStatus {
+    /// Whether the store accepted a write. For an external daemon, whether its API answered.
+    pub writable: bool,
+    /// Blocks in the store, or `None` if they couldn't be counted.
+    pub block_count: Option<u64>,
+    pub peer_count: usize,
+}
+
+impl HealthStatus {
+    /// Whether the store can be written to and read from. Having no peers doesn't count against
+    /// it, since a node can store and load content on its own.
+    pub fn is_healthy(&self) -> bool {
+        self.writable && self.block_count.is_some()
+    }
+}
+
+/// Alias the embedded store's health check clears, to check it accepts writes.
+const HEALTH_CHECK_ALIAS: &[u8] = b"health-check";
+
+/// Number of blocks the store keeps in its in-memory cache.
+const CACHE_SIZE_BLOCKS: u64 = 1000;
+
+/// How long `connect_peer` waits for a dialed peer to connect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Hash function blocks are stored under, which determines their CIDs. The same content stored
+/// under each gets a different CID.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum MultihashCode {
+    /// BLAKE3, much faster to compute than SHA-256.
+    #[default]
+    Blake3_256,
+    /// SHA-256, as kubo uses by default.
+    Sha2_256,
+}
+
+impl From<MultihashCode> for Code {
+    fn from(hash: MultihashCode) -> Self {
+        match hash {
+            MultihashCode::Blake3_256 => Code::Blake3_256,
+            MultihashCode::Sha2_256 => Code::Sha2_256,
+        }
+    }
+}
+
+/// Settings for [`IpfsClient::with_config`].
+#[derive(Clone, Debug)]
+pub struct IpfsClientConfig {
+    /// Directory holding the block store, created if it doesn't exist. Defaults to the platform's
+    /// local data directory.
+    pub repo_path: Option<PathBuf>,
+    /// Keep blocks in a temporary store instead of the repo, so nothing persists once the client
+    /// and its clones are dropped.
+    pub in_memory: bool,
+    /// Bytes of unpinned blocks the embedded store keeps before collecting the least recently
+    /// used ones.
+    pub max_cache_bytes: usize,
+    /// Hash function new blocks are stored under.
+    pub hash: MultihashCode,
+    /// Most blocks written at once, across every clone of the client. Further writes wait for a
+    /// slot, so callers storing many large files at once can't buffer them all in memory. Zero is
+    /// treated as one.
+    pub max_concurrent_writes: usize,
+}
+
+impl Default for IpfsClientConfig {
+    fn default() -> Self {
+        IpfsClientConfig {
+            repo_path: None,
+            in_memory: false,
+            max_cache_bytes: 64 * 1024 * 1024,
+            hash: MultihashCode::default(),
+            max_concurrent_writes: 16,
+        }
+    }
+}
+
+/// Stores and loads blocks, either in an embedded store or through an external daemon. Writes to
+/// the embedded store may be buffered, so call [`IpfsClient::flush`] before shutting down.
 #[derive(Clone)]
 pub struct IpfsClient {
-    ipfs: Ipfs<MaxBlockSizeStoreParams>,
+    backend: Backend,
+    hash: MultihashCode,
+    write_permits: Arc<Semaphore>,
+}
+
+/// Where blocks are actually stored: an embedded node, or an external daemon's HTTP API.
+#[derive(Clone)]
+enum Backend {
+    // The database comes second so the node closes it before an in-memory one's file is removed.
+    Embedded(Ipfs<MaxBlockSizeStoreParams>, Arc<BlockDatabase>),
+    // Boxed since the HTTP client is several times the size of an embedded node's handle.
+    Http(Box<HttpApi>),
 }
 
 impl IpfsClient {
     pub async fn new() -> Result<IpfsClient, Arc<Error>> {
-        let cache_size = 1000;
+        IpfsClient::with_config(IpfsClientConfig::default()).await
+    }
 
-        let config = match ProjectDirs::from("net", "FuzzrNet", "Fuzzr") {
-            Some(project_dirs) => Config::new(
-                Some(project_dirs.data_local_dir().join("sqlite")),
-                cache_size,
-            ),
-            None => Config::new(None, cache_size),
+    pub async fn with_config(client_config: IpfsClientConfig) -> Result<IpfsClient, Arc<Error>> {
+        let repo_path = if client_config.in_memory {
+            None
+        } else {
+            client_config.repo_path.clone().or_else(|| {
+                ProjectDirs::from("net", "FuzzrNet", "Fuzzr")
+                    .map(|project_dirs| project_dirs.data_local_dir().to_path_buf())
+            })
         };
 
+        let database = match repo_path {
+            Some(repo_path) => {
+                runtime::fs::create_dir_all(&repo_path)
+                    .await
+                    .with_context(|| format!("Failed to create repo: {}", repo_path.display()))?;
+
+                BlockDatabase::new(repo_path.join("sqlite"))
+            }
+            None => BlockDatabase::temporary()?,
+        };
+
+        let mut config = Config::new(Some(database.path().to_path_buf()), CACHE_SIZE_BLOCKS);
+        config.storage.cache_size_bytes = client_config.max_cache_bytes as u64;
+
         let ipfs = Ipfs::new(config).await?;
 
-        Ok(IpfsClient { ipfs })
+        Ok(IpfsClient::with_backend(
+            Backend::Embedded(ipfs, Arc::new(database)),
+            &client_config,
+        ))
+    }
+
+    /// Connects to an external IPFS daemon, such as kubo, through its HTTP API at `api_url`
+    /// (e.g. `http://127.0.0.1:5001`) instead of running an embedded store. Blocks are hashed
+    /// with BLAKE3.
+    pub fn connect_http(api_url: &str) -> Result<IpfsClient, Arc<Error>> {
+        let api = HttpApi::new(api_url)?;
+
+        Ok(IpfsClient::with_backend(
+            Backend::Http(Box::new(api)),
+            &IpfsClientConfig::default(),
+        ))
+    }
+
+    fn with_backend(backend: Backend, config: &IpfsClientConfig) -> IpfsClient {
+        IpfsClient {
+            backend,
+            hash: config.hash,
+            write_permits: Arc::new(Semaphore::new(config.max_concurrent_writes.max(1))),
+        }
+    }
+
+    /// Hash function this client stores new blocks under.
+    pub fn hash(&self) -> MultihashCode {
+        self.hash
     }
 
     pub async fn add(&self, block: &ContentItemBlock) -> Result<Cid, Arc<Error>> {
-        let ipld_block = libipld::Block::encode(DagCborCodec, Code::Blake3_256, block)?;
-        self.ipfs.insert(&ipld_block)?.await?;
+        let _permit = self.write_permits.acquire().await;
+        let ipld_block = self.encode_block(block)?;
+        self.insert(&ipld_block).await?;
+        let cid = *ipld_block.cid();
+
+        Ok(cid)
+    }
+
+    /// Stores one raw chunk of a file stored with `store_file_streaming`.
+    pub async fn add_chunk(&self, chunk: &[u8]) -> Result<Cid, Arc<Error>> {
+        let _permit = self.write_permits.acquire().await;
+        let ipld_block = Block::encode(RawCodec, self.hash.into(), chunk)?;
+        self.insert(&ipld_block).await?;
+        let cid = *ipld_block.cid();
+
+        Ok(cid)
+    }
+
+    /// Computes the CID a chunk would be stored under, without writing it.
+    pub fn compute_chunk_cid(&self, chunk: &[u8]) -> Result<Cid, Arc<Error>> {
+        let ipld_block = Block::encode(RawCodec, self.hash.into(), chunk)?;
+
+        Ok(*ipld_block.cid())
+    }
+
+    pub async fn get_chunk(&self, cid: &Cid) -> Result<Vec<u8>, Arc<Error>> {
+        let block = self.block(cid).await?;
+
+        Ok(block.data().to_vec())
+    }
+
+    pub async fn add_chunked_file(&self, block: &ChunkedFileBlock) -> Result<Cid, Arc<Error>> {
+        let _permit = self.write_permits.acquire().await;
+        let ipld_block = self.encode_block(block)?;
+        self.insert(&ipld_block).await?;
+        let cid = *ipld_block.cid();
+
+        Ok(cid)
+    }
+
+    pub async fn get_chunked_file(&self, cid: &Cid) -> Result<ChunkedFileBlock, Arc<Error>> {
+        let block = self.block(cid).await?;
+        let chunked_file = block.decode::<DagCborCodec, ChunkedFileBlock>()?;
+
+        Ok(chunked_file)
+    }
+
+    pub async fn add_encrypted(&self, block: &EncryptedBlock) -> Result<Cid, Arc<Error>> {
+        let _permit = self.write_permits.acquire().await;
+        let ipld_block = self.encode_block(block)?;
+        self.insert(&ipld_block).await?;
+        let cid = *ipld_block.cid();
+
+        Ok(cid)
+    }
+
+    pub async fn get_encrypted(&self, cid: &Cid) -> Result<EncryptedBlock, Arc<Error>> {
+        let block = self.block(cid).await?;
+        let encrypted = block.decode::<DagCborCodec, EncryptedBlock>()?;
+
+        Ok(encrypted)
+    }
+
+    pub async fn add_metadata(&self, block: &MetadataBlock) -> Result<Cid, Arc<Error>> {
+        let _permit = self.write_permits.acquire().await;
+        let ipld_block = self.encode_block(block)?;
+        self.insert(&ipld_block).await?;
         let cid = *ipld_block.cid();
 
         Ok(cid)
     }
 
-    pub fn get(&self, cid: &Cid) -> Result<ContentItemBlock, Arc<Error>> {
-        let block = self.ipfs.get(cid)?;
+    pub async fn get_metadata(&self, cid: &Cid) -> Result<MetadataBlock, Arc<Error>> {
+        let block = self.block(cid).await?;
+        let metadata = block.decode::<DagCborCodec, MetadataBlock>()?;
+
+        Ok(metadata)
+    }
+
+    /// Computes the CID a block would be stored under, without writing it.
+    pub fn compute_cid(&self, block: &ContentItemBlock) -> Result<Cid, Arc<Error>> {
+        let ipld_block = self.encode_block(block)?;
+
+        Ok(*ipld_block.cid())
+    }
+
+    /// Checks whether a block is present in the local store without fetching its data.
+    pub async fn has(&self, cid: &Cid) -> Result<bool, Arc<Error>> {
+        let present = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.contains(cid)?,
+            Backend::Http(api) => api.has_block(cid).await?,
+        };
+
+        Ok(present)
+    }
+
+    pub async fn get(&self, cid: &Cid) -> Result<ContentItemBlock, Arc<Error>> {
+        let block = self.block(cid).await?;
+        let content_item = block.decode::<DagCborCodec, ContentItemBlock>()?;
+
+        Ok(content_item)
+    }
+
+    /// Like [`get`](Self::get), but a block missing from the local store is requested from
+    /// connected peers over bitswap, and kept locally once it arrives so it can be served on. The
+    /// search gives up after `timeout`. An external daemon does its own fetching, so `timeout` just
+    /// bounds the request.
+    pub async fn fetch(
+        &self,
+        cid: &Cid,
+        timeout: Duration,
+    ) -> Result<ContentItemBlock, Arc<Error>> {
+        let timed_out = || anyhow!("Timed out after {:?} fetching block {}", timeout, cid);
+
+        let block = match &self.backend {
+            Backend::Embedded(ipfs, _) if !ipfs.contains(cid)? => {
+                // Bitswap gives up straight away with no one to ask, so wait for a peer first.
+                let search = async {
+                    while ipfs.peers().is_empty() {
+                        runtime::sleep(Duration::from_millis(10)).await;
+                    }
+
+                    ipfs.fetch(cid, ipfs.peers()).await
+                };
+                let block = runtime::timeout(timeout, search)
+                    .await
+                    .map_err(|_| timed_out())??;
+                ipfs.insert(&block)?;
+                block
+            }
+            _ => runtime::timeout(timeout, self.block(cid))
+                .await
+                .map_err(|_| timed_out())??,
+        };
         let content_item = block.decode::<DagCborCodec, ContentItemBlock>()?;
 
         Ok(content_item)
     }
+
+    /// Dials the peer at `addr`, a multiaddr ending in its ID such as
+    /// `/ip4/10.0.0.2/tcp/4001/p2p/12D3KooW...`, and waits until the connection is established.
+    /// The embedded node also joins the DHT through the peer, which [`IpfsClient::provide`] and
+    /// [`IpfsClient::find_providers`] need first.
+    pub async fn connect_peer(&self, addr: &str) -> Result<(), Arc<Error>> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => {
+                let (transport_addr, peer_id) = addr.rsplit_once("/p2p/").ok_or_else(|| {
+                    anyhow!("Multiaddr doesn't end with a /p2p/ peer ID: {}", addr)
+                })?;
+                let transport_addr = Multiaddr::from_str(transport_addr)
+                    .with_context(|| format!("Invalid multiaddr: {}", addr))?;
+                let peer_id = PeerId::from_str(peer_id)
+                    .with_context(|| format!("Invalid peer ID in multiaddr: {}", addr))?;
+
+                runtime::timeout(CONNECT_TIMEOUT, async {
+                    ipfs.bootstrap(&[(peer_id, transport_addr)]).await?;
+
+                    while !ipfs.is_connected(&peer_id) {
+                        runtime::sleep(Duration::from_millis(10)).await;
+                    }
+
+                    Ok::<_, Error>(())
+                })
+                .await
+                .map_err(|_| anyhow!("Timed out connecting to peer: {}", addr))??;
+            }
+            Backend::Http(api) => api.swarm_connect(addr).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Lists the peers currently connected to. An external daemon that can't be reached is
+    /// logged and reported as having no peers.
+    pub async fn connected_peers(&self) -> Vec<PeerId> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.peers(),
+            Backend::Http(api) => match api.swarm_peers().await {
+                Ok(peers) => peers
+                    .iter()
+                    .filter_map(|peer| PeerId::from_str(peer).ok())
+                    .collect(),
+                Err(err) => {
+                    warn!(
+                        error = format!("{:#}", err),
+                        "Failed to list connected peers"
+                    );
+                    Vec::new()
+                }
+            },
+        }
+    }
+
+    /// Announces this node to the DHT as a provider of `cid`, so peers looking for it can find and
+    /// fetch it from here.
+    pub async fn provide(&self, cid: &Cid) -> Result<(), Arc<Error>> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.provide(Key::new(&cid.to_bytes())).await?,
+            Backend::Http(api) => api.routing_provide(cid).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Asks the DHT which peers provide `cid`.
+    pub async fn find_providers(&self, cid: &Cid) -> Result<Vec<PeerId>, Arc<Error>> {
+        let providers = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs
+                .providers(Key::new(&cid.to_bytes()))
+                .await?
+                .into_iter()
+                .collect(),
+            Backend::Http(api) => api
+                .routing_find_providers(cid)
+                .await?
+                .iter()
+                .map(|peer| PeerId::from_str(peer))
+                .collect::<Result<_, _>>()
+                .context("Daemon returned an invalid peer ID")?,
+        };
+
+        Ok(providers)
+    }
+
+    /// Reports a stored block's encoded size and direct links without decoding its content.
+    pub async fn stat(&self, cid: &Cid) -> Result<BlockStat, Arc<Error>> {
+        let block = self.block(cid).await?;
+
+        let mut links = Vec::new();
+        block.references(&mut links)?;
+
+        Ok(BlockStat {
+            size_bytes: block.data().len() as u64,
+            num_links: links.len(),
+            codec: cid.codec(),
+        })
+    }
+
+    /// Re-hashes a stored block's bytes and checks they still match its CID, returning `false` if
+    /// they've been corrupted. Scrubbing a whole store is a matter of checking each of
+    /// [`IpfsClient::list_blocks`].
+    pub async fn verify_block(&self, cid: &Cid) -> Result<bool, Arc<Error>> {
+        // Read the bytes as stored, since reading through `block` would reject corrupt ones.
+        let data = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.get(cid)?.data().to_vec(),
+            Backend::Http(api) => api.get_block(cid).await?,
+        };
+        let code = Code::try_from(cid.hash().code())
+            .with_context(|| format!("Unsupported hash function in CID: {}", cid))?;
+
+        Ok(code.digest(&data) == *cid.hash())
+    }
+
+    /// Lists the CID of every block held in the local store, including chunks and thumbnails.
+    pub async fn list_blocks(&self) -> Result<Vec<Cid>, Arc<Error>> {
+        let cids = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.iter()?.collect(),
+            Backend::Http(api) => api.list_blocks().await?,
+        };
+
+        Ok(cids)
+    }
+
+    /// Checks the store accepts writes and counts its blocks and connected peers. Failures are
+    /// logged and reported in the status rather than returned. Counting blocks means listing them
+    /// all, so large stores shouldn't be probed too often.
+    pub async fn health(&self) -> HealthStatus {
+        let writable = match &self.backend {
+            // Clearing an alias that was never set is a write that leaves nothing behind.
+            Backend::Embedded(ipfs, _) => ipfs.alias(HEALTH_CHECK_ALIAS, None),
+            Backend::Http(api) => api.repo_size().await.map(|_| ()),
+        };
+        let block_count = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.iter().map(|cids| cids.count() as u64),
+            Backend::Http(api) => api.repo_num_objects().await,
+        };
+
+        if let Err(err) = &writable {
+            warn!(error = format!("{:#}", err), "Health check failed to write");
+        }
+        if let Err(err) = &block_count {
+            warn!(
+                error = format!("{:#}", err),
+                "Health check failed to count blocks"
+            );
+        }
+
+        HealthStatus {
+            writable: writable.is_ok(),
+            block_count: block_count.ok(),
+            peer_count: self.connected_peers().await.len(),
+        }
+    }
+
+    /// Writes the DAG rooted at `root`, including every block it links to, as a CARv1 stream.
+    pub async fn export_car(
+        &self,
+        root: &Cid,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<(), Arc<Error>> {
+        car::write_header(&mut writer, &[*root]).await?;
+
+        let mut visited = HashSet::new();
+        let mut pending = vec![*root];
+
+        while let Some(cid) = pending.pop() {
+            if !visited.insert(cid) {
+                continue;
+            }
+
+            let block = self.block(&cid).await?;
+            car::write_block(&mut writer, &cid, block.data()).await?;
+            block.references(&mut pending)?;
+        }
+
+        writer.flush().await.context("Failed to flush CAR writer")?;
+
+        Ok(())
+    }
+
+    /// Inserts every block from a CARv1 stream and returns the archive's root CIDs. Blocks whose
+    /// data doesn't hash to their claimed CID are rejected.
+    pub async fn import_car(
+        &self,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> Result<Vec<Cid>, Arc<Error>> {
+        let header = car::read_header(&mut reader).await?;
+
+        while let Some((cid, data)) = car::read_block(&mut reader).await? {
+            let block = Block::new(cid, data)
+                .with_context(|| format!("Block data does not match its CID: {}", cid))?;
+            self.insert(&block).await?;
+        }
+
+        Ok(header.roots)
+    }
+
+    /// Writes any buffered blocks and aliases durably to disk. Blocks added since the last flush
+    /// can be lost if the process exits first. An external daemon persists blocks as they're added,
+    /// so this does nothing for it.
+    pub async fn flush(&self) -> Result<(), Arc<Error>> {
+        if let Backend::Embedded(ipfs, _) = &self.backend {
+            ipfs.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Pins a block so it's retained when the store is garbage collected.
+    pub async fn pin(&self, cid: &Cid) -> Result<(), Arc<Error>> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.alias(pin_alias(cid), Some(cid))?,
+            Backend::Http(api) => api.pin(cid).await?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn unpin(&self, cid: &Cid) -> Result<(), Arc<Error>> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.alias(pin_alias(cid), None)?,
+            Backend::Http(api) => api.unpin(cid).await?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_pinned(&self, cid: &Cid) -> Result<bool, Arc<Error>> {
+        let pinned = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.resolve(pin_alias(cid))?.is_some(),
+            Backend::Http(api) => api.is_pinned(cid).await?,
+        };
+
+        Ok(pinned)
+    }
+
+    /// Deletes an unpinned block from the local store, returning whether it was present. Pinned
+    /// blocks are refused and must be unpinned first. Blocks it links to are left alone.
+    pub async fn remove(&self, cid: &Cid) -> Result<bool, Arc<Error>> {
+        if self.is_pinned(cid).await? {
+            return Err(Arc::new(anyhow!(
+                "Block {} is pinned; unpin it before removing it",
+                cid
+            )));
+        }
+
+        if !self.has(cid).await? {
+            return Ok(false);
+        }
+
+        let removed = match &self.backend {
+            Backend::Embedded(_, database) => !database.delete_blocks(&[*cid]).await?.is_empty(),
+            Backend::Http(api) => {
+                api.remove_block(cid).await?;
+                true
+            }
+        };
+
+        Ok(removed)
+    }
+
+    /// Deletes every block that isn't pinned or reachable from a pinned block, such as unpinned
+    /// files and the chunks, thumbnails and earlier versions nothing pinned links to. Locally
+    /// published names don't count as roots, so pin anything published too. An external daemon
+    /// runs its own collection, which also keeps whatever its MFS holds.
+    pub async fn gc(&self) -> Result<GcReport, Arc<Error>> {
+        let (ipfs, database) = match &self.backend {
+            Backend::Embedded(ipfs, database) => (ipfs, database),
+            Backend::Http(api) => {
+                let size_before = api.repo_size().await?;
+                let removed = api.repo_gc().await?;
+                let size_after = api.repo_size().await?;
+
+                return Ok(GcReport {
+                    blocks_removed: removed.len() as u64,
+                    bytes_freed: size_before.saturating_sub(size_after),
+                });
+            }
+        };
+
+        let cids: Vec<Cid> = ipfs.iter()?.collect();
+
+        let mut pending = Vec::new();
+        for cid in &cids {
+            if ipfs.resolve(pin_alias(cid))?.is_some() {
+                pending.push(*cid);
+            }
+        }
+
+        let mut live = HashSet::new();
+        while let Some(cid) = pending.pop() {
+            // Pinned DAGs may link to blocks that were never fetched, which have nothing to mark.
+            if !live.insert(cid) || !ipfs.contains(&cid)? {
+                continue;
+            }
+
+            ipfs.get(&cid)?.references(&mut pending)?;
+        }
+
+        let unreachable: Vec<Cid> = cids.into_iter().filter(|cid| !live.contains(cid)).collect();
+
+        let mut report = GcReport::default();
+        for (_, size_bytes) in database.delete_blocks(&unreachable).await? {
+            report.blocks_removed += 1;
+            report.bytes_freed += size_bytes;
+        }
+
+        Ok(report)
+    }
+
+    /// Points the mutable name `key_name` at `cid`, returning the name to resolve it by. An
+    /// external daemon publishes a signed IPNS record; the embedded store keeps the name locally.
+    pub async fn publish_ipns(&self, cid: &Cid, key_name: &str) -> Result<String, Arc<Error>> {
+        let name = match &self.backend {
+            Backend::Embedded(ipfs, _) => {
+                ipfs.alias(ipns_alias(key_name), Some(cid))?;
+                key_name.to_string()
+            }
+            Backend::Http(api) => api.publish_name(cid, key_name).await?,
+        };
+
+        Ok(name)
+    }
+
+    pub async fn resolve_ipns(&self, name: &str) -> Result<Cid, Arc<Error>> {
+        let cid = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs
+                .resolve(ipns_alias(name))?
+                .ok_or_else(|| anyhow!("No IPNS record published for name: {}", name))?,
+            Backend::Http(api) => api.resolve_name(name).await?,
+        };
+
+        Ok(cid)
+    }
+
+    async fn insert(&self, block: &Block) -> Result<(), Arc<Error>> {
+        match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.insert(block)?,
+            Backend::Http(api) => api.put_block(block.cid(), block.data()).await?,
+        }
+
+        Ok(())
+    }
+
+    fn encode_block<T: Encode<DagCborCodec>>(&self, block: &T) -> Result<Block, Arc<Error>> {
+        let ipld_block = Block::encode(DagCborCodec, self.hash.into(), block)?;
+
+        Ok(ipld_block)
+    }
+
+    /// Reads a raw block. Blocks from an external daemon are checked against their CID.
+    async fn block(&self, cid: &Cid) -> Result<Block, Arc<Error>> {
+        let block = match &self.backend {
+            Backend::Embedded(ipfs, _) => ipfs.get(cid)?,
+            Backend::Http(api) => Block::new(*cid, api.get_block(cid).await?)?,
+        };
+
+        Ok(block)
+    }
+}
+
+/// Pins are stored as block store aliases named after the pinned CID.
+fn pin_alias(cid: &Cid) -> Vec<u8> {
+    let mut alias = b"pin/".to_vec();
+    alias.extend(cid.to_bytes());
+    alias
+}
+
+/// Locally published names are aliases too, which also keeps the named DAG from being collected.
+fn ipns_alias(name: &str) -> Vec<u8> {
+    let mut alias = b"ipns/".to_vec();
+    alias.extend(name.as_bytes());
+    alias
 }
 
 impl fmt::Debug for IpfsClient {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "<TODO IpfsClient debug formatting>")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::classify::classify_bytes;
+
+    #[cfg(feature = "network-tests")]
+    use futures::StreamExt;
+    #[cfg(feature = "network-tests")]
+    use ipfs_embed::ListenerEvent;
+    use std::error::Error;
+
+    fn in_memory_config() -> IpfsClientConfig {
+        IpfsClientConfig {
+            in_memory: true,
+            ..IpfsClientConfig::default()
+        }
+    }
+
+    fn text_block(bytes: &[u8]) -> ContentItemBlock {
+        ContentItemBlock {
+            content: classify_bytes(bytes).unwrap(),
+            size_bytes: bytes.len() as u64,
+            metadata: Vec::new(),
+            metadata_cid: None,
+            previous_version: None,
+            transcoded_cid: None,
+            thumbnail_cid: None,
+            created_at: None,
+        }
+    }
+
+    fn embedded(client: &IpfsClient) -> &Ipfs<MaxBlockSizeStoreParams> {
+        match &client.backend {
+            Backend::Embedded(ipfs, _) => ipfs,
+            Backend::Http(_) => panic!("Expected an embedded store"),
+        }
+    }
+
+    /// Starts an embedded node listening on a loopback TCP port, returning its full multiaddr.
+    #[cfg(feature = "network-tests")]
+    async fn listen_addr(client: &IpfsClient) -> Result<String, Box<dyn Error>> {
+        let ipfs = embedded(client);
+        let mut events = Box::pin(ipfs.listen_on("/ip4/127.0.0.1/tcp/0".parse()?)?);
+        let addr = match events.next().await {
+            Some(ListenerEvent::NewListenAddr(addr)) => addr,
+            _ => return Err("Listener closed before it had an address".into()),
+        };
+
+        Ok(format!("{}/p2p/{}", addr, ipfs.local_peer_id()))
+    }
+
+    #[cfg(feature = "network-tests")]
+    #[runtime::test]
+    async fn test_connect_peer() -> Result<(), Box<dyn Error>> {
+        let first = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let second = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let second_addr = listen_addr(&second).await?;
+
+        first.connect_peer(&second_addr).await.unwrap();
+
+        let first_id = embedded(&first).local_peer_id();
+        let second_id = embedded(&second).local_peer_id();
+        assert_eq!(vec![second_id], first.connected_peers().await);
+
+        // The listening side sees the connection once it's been accepted.
+        runtime::timeout(Duration::from_secs(10), async {
+            while !second.connected_peers().await.contains(&first_id) {
+                runtime::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "network-tests")]
+    #[runtime::test]
+    async fn test_provide() -> Result<(), Box<dyn Error>> {
+        let provider = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let seeker = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let provider_addr = listen_addr(&provider).await?;
+        let seeker_addr = listen_addr(&seeker).await?;
+        // Both sides have to have joined the DHT to use it.
+        seeker.connect_peer(&provider_addr).await.unwrap();
+        provider.connect_peer(&seeker_addr).await.unwrap();
+
+        let block = text_block(b"findable");
+        let cid = provider.add(&block).await.unwrap();
+        provider.provide(&cid).await.unwrap();
+
+        let providers = seeker.find_providers(&cid).await.unwrap();
+        assert_eq!(vec![embedded(&provider).local_peer_id()], providers);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_connect_peer_rejects_bad_multiaddr() -> Result<(), Box<dyn Error>> {
+        let client = IpfsClient::with_config(in_memory_config()).await.unwrap();
+
+        assert!(client
+            .connect_peer("/ip4/127.0.0.1/tcp/4001")
+            .await
+            .is_err());
+        assert!(client
+            .connect_peer("not a multiaddr/p2p/12D3KooW")
+            .await
+            .is_err());
+        assert!(client.connected_peers().await.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "network-tests")]
+    #[runtime::test]
+    async fn test_fetch_from_peer() -> Result<(), Box<dyn Error>> {
+        let provider = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let fetcher = IpfsClient::with_config(in_memory_config()).await.unwrap();
+
+        let provider_addr = listen_addr(&provider).await?;
+        fetcher.connect_peer(&provider_addr).await.unwrap();
+
+        let block = text_block(b"howdy from a peer");
+        let cid = provider.add(&block).await.unwrap();
+        assert!(!fetcher.has(&cid).await.unwrap());
+
+        let fetched = fetcher.fetch(&cid, Duration::from_secs(10)).await.unwrap();
+        assert_eq!(block, fetched);
+        assert!(fetcher.has(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_fetch_times_out() -> Result<(), Box<dyn Error>> {
+        let client = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let block = text_block(b"nowhere to be found");
+        let cid = client.compute_cid(&block).unwrap();
+
+        let err = client
+            .fetch(&cid, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Timed out"));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_max_concurrent_writes() -> Result<(), Box<dyn Error>> {
+        let config = IpfsClientConfig {
+            max_concurrent_writes: 1,
+            ..in_memory_config()
+        };
+        let client = IpfsClient::with_config(config).await.unwrap();
+        let block = text_block(b"one at a time");
+
+        // While another write holds the only slot, this one waits rather than running alongside.
+        let permit = client.write_permits.acquire().await;
+        let writer = client.clone();
+        assert!(
+            runtime::timeout(Duration::from_millis(50), writer.add(&block))
+                .await
+                .is_err()
+        );
+        assert!(!client
+            .has(&client.compute_cid(&block).unwrap())
+            .await
+            .unwrap());
+
+        drop(permit);
+        let cid = runtime::timeout(Duration::from_secs(10), writer.add(&block))
+            .await?
+            .unwrap();
+        assert!(client.has(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_verify_block() -> Result<(), Box<dyn Error>> {
+        let client = IpfsClient::with_config(in_memory_config()).await.unwrap();
+        let block = text_block(b"intact");
+        let cid = client.add(&block).await.unwrap();
+        assert!(client.verify_block(&cid).await.unwrap());
+
+        // Store other bytes under a CID they don't hash to, as a corrupted disk would leave them.
+        let corrupted = client.compute_chunk_cid(b"original").unwrap();
+        let block = Block::new_unchecked(corrupted, b"bit rot".to_vec());
+        embedded(&client).insert(&block)?;
+        assert!(!client.verify_block(&corrupted).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_flush_persists_blocks() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let config = IpfsClientConfig {
+            repo_path: Some(dir.path().to_path_buf()),
+            ..IpfsClientConfig::default()
+        };
+        let block = text_block(b"still here");
+
+        let client = IpfsClient::with_config(config.clone()).await.unwrap();
+        let cid = client.add(&block).await.unwrap();
+        client.flush().await.unwrap();
+        drop(client);
+
+        let reopened = IpfsClient::with_config(config).await.unwrap();
+        assert_eq!(block, reopened.get(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_remove_from_repo() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let config = IpfsClientConfig {
+            repo_path: Some(dir.path().to_path_buf()),
+            ..IpfsClientConfig::default()
+        };
+        let block = text_block(b"gone for good");
+        let kept = text_block(b"here to stay");
+
+        let client = IpfsClient::with_config(config.clone()).await.unwrap();
+        let cid = client.add(&block).await.unwrap();
+        let kept_cid = client.add(&kept).await.unwrap();
+
+        assert!(client.remove(&cid).await.unwrap());
+        assert!(!client.has(&cid).await.unwrap());
+        assert_eq!(vec![kept_cid], client.list_blocks().await.unwrap());
+        // The store's collector checks its block counts against its rows in debug builds.
+        embedded(&client).evict().await.unwrap();
+
+        // A removed block can be stored again.
+        assert_eq!(cid, client.add(&block).await.unwrap());
+        assert_eq!(block, client.get(&cid).await.unwrap());
+        assert!(client.remove(&cid).await.unwrap());
+        client.flush().await.unwrap();
+        drop(client);
+
+        let reopened = IpfsClient::with_config(config).await.unwrap();
+        assert!(!reopened.has(&cid).await.unwrap());
+        assert_eq!(kept, reopened.get(&kept_cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_health() -> Result<(), Box<dyn Error>> {
+        let client = IpfsClient::with_config(in_memory_config()).await.unwrap();
+
+        let status = client.health().await;
+        assert!(status.is_healthy());
+        assert_eq!(Some(0), status.block_count);
+        assert_eq!(0, status.peer_count);
+
+        let block = text_block(b"healthy");
+        client.add(&block).await.unwrap();
+
+        let status = client.health().await;
+        assert!(status.is_healthy());
+        assert_eq!(Some(1), status.block_count);
+
+        Ok(())
+    }
+}
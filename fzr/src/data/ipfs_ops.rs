@@ -1,179 +1,4755 @@
-use anyhow::Error;
-use async_std::fs;
-use async_std::sync::Arc;
+use anyhow::{anyhow, Context};
+use futures::io::{AsyncRead, AsyncReadExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use image::ImageFormat;
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
 use libipld::{cid::Cid, Result};
-use log::{error, info};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Instant;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
 
+use crate::data::classify;
 use crate::data::content::{
-    ContentItem, ContentItemBlock, ImageContent, ImageMetadata, TextContent, TextMetadata,
+    ChunkedFileBlock, CompressedTextContent, Compression, ContentItem, ContentItemBlock,
+    ContentMetadata, CsvContent, DirectoryManifest, Fingerprint, ImageContent, JsonContent,
+    LineEndings, ManifestEntry, MarkdownContent, MetadataBlock, TextContent,
 };
-use crate::data::ipfs_client::IpfsClientRef;
+use crate::data::content_cid::ContentCid;
+use crate::data::crypto::{self, KEY_LEN};
+use crate::data::csv;
+use crate::data::error::FzrError;
+use crate::data::fs_ops::{walk_dir_with_options, PathFilter};
+use crate::data::heic;
+use crate::data::images::{self, ThumbnailFormat};
+use crate::data::ipfs_client::{IpfsClient, IpfsClientRef, MultihashCode};
+use crate::data::markdown;
+use crate::data::meta::MetadataItem;
+use crate::data::runtime::{self, fs, Arc};
+use crate::data::text_ops::{self, DiffLine};
+
+/// How content stored with `store_reader` is described in log and error messages.
+const READER_SOURCE: &str = "reader";
+
+/// How content stored with `store_bytes` without a name is described in log and error messages.
+const BYTES_SOURCE: &str = "bytes";
+
+/// EXIF orientation of an image that's already upright.
+const NORMAL_ORIENTATION: u16 = 1;
+
+/// Chunk size used by `store_file_streaming` when callers have no preference.
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Default quality for lossy `store_image_as` targets, on the 1-100 scale.
+const DEFAULT_IMAGE_QUALITY: u8 = 85;
+
+/// Number of dominant colors extracted from images when callers have no preference.
+pub const DEFAULT_PALETTE_SIZE: usize = 5;
+
+/// BlurHash `(components_x, components_y)` used when callers have no preference.
+pub const DEFAULT_BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// Number of files stored at once when storing a directory.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How long `load_file` waits for a block before reporting it as not found.
+const DEFAULT_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest read buffer `store_files` keeps for reuse. Buffers grown past this by a big file are
+/// freed rather than held for the rest of the batch.
+const MAX_POOLED_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
+/// Callback receiving `(bytes_processed, total_bytes)` as a store progresses.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(u64, u64) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        ProgressCallback(Arc::new(callback))
+    }
+
+    fn report(&self, bytes_processed: u64, total_bytes: u64) {
+        (self.0)(bytes_processed, total_bytes)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<ProgressCallback>")
+    }
+}
+
+/// Options controlling how [`store_file_with_options`] stores a file.
+#[derive(Clone, Debug, Default)]
+pub struct StoreOptions {
+    /// Skip the write when a block with the same CID is already in the local store.
+    pub skip_existing: bool,
+    /// Classify and encode the content and return the CID it would be stored under, without
+    /// writing anything. Pair with `IpfsClient::has` to see what's already stored.
+    pub dry_run: bool,
+    /// Metadata embedded in the stored block alongside the content.
+    pub metadata: Vec<MetadataItem>,
+    /// Shared metadata to link to rather than embed, as returned by [`store_metadata`].
+    pub metadata_cid: Option<Cid>,
+    /// Store content as this MIME type instead of detecting it, choosing the matching
+    /// [`ContentItem`] variant. Types without a variant of their own are stored as binary.
+    pub content_type_override: Option<String>,
+    /// Reject input larger than this with [`FzrError::TooLarge`], checked before a file is read
+    /// into memory.
+    pub max_size_bytes: Option<u64>,
+    /// Announce this node to the DHT as a provider of the stored block, so other nodes can find
+    /// it.
+    pub auto_provide: bool,
+    /// Called once with the full size after the file has been stored.
+    pub progress: Option<ProgressCallback>,
+    /// Re-encode images without EXIF or other metadata segments before storing them.
+    pub strip_exif: bool,
+    /// Rotate and flip images so they're upright according to their EXIF orientation.
+    pub auto_orient: bool,
+    /// Compute a perceptual hash for images, for near-duplicate detection.
+    pub compute_phash: bool,
+    /// Extract this many dominant colors from images, such as [`DEFAULT_PALETTE_SIZE`], for
+    /// placeholder backgrounds shown while the image loads.
+    pub palette_size: Option<usize>,
+    /// Compute a BlurHash placeholder for images with these `(components_x, components_y)`,
+    /// such as [`DEFAULT_BLURHASH_COMPONENTS`].
+    pub blurhash_components: Option<(u32, u32)>,
+    /// Convert CRLF and CR line endings in text, JSON, Markdown and CSV to LF, so copies of a
+    /// document from different platforms share a CID.
+    pub normalize_line_endings: bool,
+    /// Read the block back after adding it and fail if it doesn't match what was stored.
+    pub verify: bool,
+    /// Compress text, JSON, Markdown and CSV with zstd when that makes it smaller. `load_file`
+    /// decompresses it again.
+    pub compress_text: bool,
+    /// Also store a JPEG copy of HEIC images for browsers, which can't display HEIC, linked from
+    /// the original block's `transcoded_cid`. Needs the `heic` feature, without which HEIC is
+    /// stored as binary and this has no effect.
+    pub transcode_heic: bool,
+    /// Record when the content was created in the block's `created_at`, for browsing content
+    /// chronologically. The same content stored at different times then gets different CIDs.
+    pub created_at: Option<CreatedAt>,
+    /// Look content up by the fingerprint of its bytes before classifying it, returning the CID
+    /// identical content stored the same way was already stored under without encoding it again.
+    pub fingerprint_cache: Option<FingerprintCache>,
+}
+
+/// Where [`StoreOptions::created_at`] takes a block's creation time from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CreatedAt {
+    /// The file's modification time, or the current time for readers and files without one.
+    Modified,
+    /// A fixed time in Unix milliseconds.
+    At(i64),
+}
+
+/// CIDs of content already stored, keyed by the fingerprint of its bytes, for
+/// [`StoreOptions::fingerprint_cache`]. Clones share the same entries. The key also covers the
+/// content type, the client's hash function and every option that changes the stored block, so
+/// one cache can be shared between stores using different options. Entries aren't removed when
+/// their blocks are, so clear the cache after removing content.
+#[derive(Clone, Debug, Default)]
+pub struct FingerprintCache(Arc<FingerprintCacheEntries>);
+
+#[derive(Debug, Default)]
+struct FingerprintCacheEntries {
+    cids: Mutex<HashMap<CacheKey, ContentCid>>,
+    hits: AtomicUsize,
+}
+
+/// Everything a stored block's CID depends on, for looking it up in a [`FingerprintCache`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct CacheKey {
+    fingerprint: Fingerprint,
+    content_type: Option<String>,
+    hash: MultihashCode,
+    /// DAG-CBOR encoded, since metadata items can't be hashed themselves.
+    metadata: Vec<u8>,
+    metadata_cid: Option<Cid>,
+    created_at: Option<i64>,
+    strip_exif: bool,
+    auto_orient: bool,
+    compute_phash: bool,
+    palette_size: Option<usize>,
+    blurhash_components: Option<(u32, u32)>,
+    normalize_line_endings: bool,
+    compress_text: bool,
+    transcode_heic: bool,
+}
+
+impl CacheKey {
+    fn new(
+        buffer: &[u8],
+        content_type: Option<&str>,
+        hash: MultihashCode,
+        options: &StoreOptions,
+    ) -> Result<Self, FzrError> {
+        Ok(CacheKey {
+            fingerprint: Fingerprint::of(buffer),
+            content_type: content_type.map(str::to_string),
+            hash,
+            metadata: DagCborCodec
+                .encode(&options.metadata)
+                .map_err(FzrError::decode)?,
+            metadata_cid: options.metadata_cid,
+            created_at: match options.created_at {
+                Some(CreatedAt::At(millis)) => Some(millis),
+                _ => None,
+            },
+            strip_exif: options.strip_exif,
+            auto_orient: options.auto_orient,
+            compute_phash: options.compute_phash,
+            palette_size: options.palette_size,
+            blurhash_components: options.blurhash_components,
+            normalize_line_endings: options.normalize_line_endings,
+            compress_text: options.compress_text,
+            transcode_heic: options.transcode_heic,
+        })
+    }
+}
+
+/// A cache and the key content will be cached under once it's stored.
+struct CacheEntry(FingerprintCache, CacheKey);
+
+/// What looking content up in [`StoreOptions::fingerprint_cache`] found.
+enum CacheLookup {
+    /// Identical content was stored the same way before, under this CID.
+    Hit(ContentCid),
+    /// Nothing cached yet. The entry is `None` when there's no cache, or the content can't be
+    /// cached.
+    Miss(Option<CacheEntry>),
+}
+
+impl FingerprintCache {
+    pub fn new() -> Self {
+        FingerprintCache::default()
+    }
+
+    /// Number of stores answered from the cache, without classifying or encoding the file.
+    pub fn hits(&self) -> usize {
+        self.0.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        self.0.cids.lock().unwrap().clear();
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<ContentCid> {
+        let cid = self.0.cids.lock().unwrap().get(key).copied();
+
+        if cid.is_some() {
+            self.0.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        cid
+    }
+
+    fn insert(&self, key: CacheKey, cid: ContentCid) {
+        self.0.cids.lock().unwrap().insert(key, cid);
+    }
+}
+
+/// Looks content up in the options' fingerprint cache. On a hit, whatever storing it would have
+/// done besides the write, like providing it, is done here instead.
+async fn look_up_cached(
+    buffer: &[u8],
+    content_type: Option<&str>,
+    source: &str,
+    ipfs_client: &IpfsClientRef,
+    options: &StoreOptions,
+) -> Result<CacheLookup, FzrError> {
+    let cache = match &options.fingerprint_cache {
+        // A file's modification time isn't part of its fingerprint, so blocks stamped with it
+        // can't be looked up by one.
+        Some(cache) if options.created_at != Some(CreatedAt::Modified) => cache,
+        _ => return Ok(CacheLookup::Miss(None)),
+    };
+
+    let hash = ipfs_client.read().await.hash();
+    let key = CacheKey::new(buffer, content_type, hash, options)?;
+
+    let cid = match cache.get(&key) {
+        Some(cid) => cid,
+        None => return Ok(CacheLookup::Miss(Some(CacheEntry(cache.clone(), key)))),
+    };
+
+    info!(%cid, source, "Skipped storing content already cached");
+
+    if options.auto_provide && !options.dry_run {
+        ipfs_client.read().await.provide(&cid).await?;
+    }
+
+    if let Some(progress) = &options.progress {
+        let size_bytes = buffer.len() as u64;
+        progress.report(size_bytes, size_bytes);
+    }
+
+    Ok(CacheLookup::Hit(cid))
+}
+
+/// Records the CID content was stored under in the cache it was looked up in.
+fn remember_cached(entry: Option<CacheEntry>, cid: Option<ContentCid>, dry_run: bool) {
+    // Dry runs don't store anything, so a later store mustn't be skipped on their account.
+    if let (Some(CacheEntry(cache, key)), Some(cid), false) = (entry, cid, dry_run) {
+        cache.insert(key, cid);
+    }
+}
 
 pub async fn store_file(
     path: PathBuf,
     ipfs_client: IpfsClientRef,
-) -> Result<Option<Cid>, Arc<Error>> {
+) -> Result<Option<ContentCid>, FzrError> {
+    store_file_with_options(path, ipfs_client, StoreOptions::default()).await
+}
+
+pub async fn store_file_with_options(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    options: StoreOptions,
+) -> Result<Option<ContentCid>, FzrError> {
+    let buffer = read_file(&path, options.max_size_bytes).await?;
+    let source = format!("path: {:?}", &path);
+
+    let content_type = options
+        .content_type_override
+        .as_deref()
+        .or_else(|| extension_content_type(&path, &buffer));
+    let lookup = look_up_cached(&buffer, content_type, &source, &ipfs_client, &options).await?;
+    let entry = match lookup {
+        CacheLookup::Hit(cid) => return Ok(Some(cid)),
+        CacheLookup::Miss(entry) => entry,
+    };
+    let mut block = classify_block(&buffer, &source, content_type)?;
+
+    if options.created_at == Some(CreatedAt::Modified) {
+        block.created_at = fs::metadata(&path)
+            .await?
+            .modified()
+            .ok()
+            .and_then(unix_millis);
+    }
+
+    let dry_run = options.dry_run;
+    let cid = store_block(block, &source, ipfs_client, options).await?;
+    remember_cached(entry, cid, dry_run);
+
+    Ok(cid)
+}
+
+/// Stores content read from `reader`, classified the same way as files are, so something like an
+/// HTTP response body can be stored without writing it to disk first. `size_hint` is the expected
+/// length, if known, and only used to size the buffer.
+pub async fn store_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    size_hint: Option<u64>,
+    ipfs_client: IpfsClientRef,
+) -> Result<Option<ContentCid>, FzrError> {
+    store_reader_with_options(reader, size_hint, ipfs_client, StoreOptions::default()).await
+}
+
+pub async fn store_reader_with_options<R: AsyncRead + Unpin>(
+    mut reader: R,
+    size_hint: Option<u64>,
+    ipfs_client: IpfsClientRef,
+    options: StoreOptions,
+) -> Result<Option<ContentCid>, FzrError> {
+    let mut buffer = Vec::with_capacity(size_hint.unwrap_or(0) as usize);
+
+    match options.max_size_bytes {
+        // Reading one byte past the limit is enough to tell it's been exceeded.
+        Some(max_size_bytes) => {
+            (&mut reader)
+                .take(max_size_bytes.saturating_add(1))
+                .read_to_end(&mut buffer)
+                .await?;
+
+            if buffer.len() as u64 > max_size_bytes {
+                return Err(FzrError::TooLarge(max_size_bytes));
+            }
+        }
+        None => {
+            reader.read_to_end(&mut buffer).await?;
+        }
+    }
+
+    let content_type = options.content_type_override.as_deref();
+    let lookup =
+        look_up_cached(&buffer, content_type, READER_SOURCE, &ipfs_client, &options).await?;
+    let entry = match lookup {
+        CacheLookup::Hit(cid) => return Ok(Some(cid)),
+        CacheLookup::Miss(entry) => entry,
+    };
+    let block = classify_block(&buffer, READER_SOURCE, content_type)?;
+
+    let dry_run = options.dry_run;
+    let cid = store_block(block, READER_SOURCE, ipfs_client, options).await?;
+    remember_cached(entry, cid, dry_run);
+
+    Ok(cid)
+}
+
+/// Stores content already in memory, classified as a file called `name_hint` would be. The name's
+/// extension only breaks ties between formats that look alike, such as Markdown and plain text.
+pub async fn store_bytes(
+    bytes: Vec<u8>,
+    name_hint: Option<&str>,
+    ipfs_client: IpfsClientRef,
+) -> Result<Option<ContentCid>, FzrError> {
+    store_bytes_with_options(bytes, name_hint, ipfs_client, StoreOptions::default()).await
+}
+
+pub async fn store_bytes_with_options(
+    bytes: Vec<u8>,
+    name_hint: Option<&str>,
+    ipfs_client: IpfsClientRef,
+    options: StoreOptions,
+) -> Result<Option<ContentCid>, FzrError> {
+    if let Some(max_size_bytes) = options.max_size_bytes {
+        if bytes.len() as u64 > max_size_bytes {
+            return Err(FzrError::TooLarge(max_size_bytes));
+        }
+    }
+
+    let source = match name_hint {
+        Some(name) => format!("name: {:?}", name),
+        None => BYTES_SOURCE.to_string(),
+    };
+    let content_type = options
+        .content_type_override
+        .as_deref()
+        .or_else(|| name_hint.and_then(|name| extension_content_type(Path::new(name), &bytes)));
+    let lookup = look_up_cached(&bytes, content_type, &source, &ipfs_client, &options).await?;
+    let entry = match lookup {
+        CacheLookup::Hit(cid) => return Ok(Some(cid)),
+        CacheLookup::Miss(entry) => entry,
+    };
+    let block = classify_block(&bytes, &source, content_type)?;
+
+    let dry_run = options.dry_run;
+    let cid = store_block(block, &source, ipfs_client, options).await?;
+    remember_cached(entry, cid, dry_run);
+
+    Ok(cid)
+}
+
+/// Applies `options` to a classified block and adds it to the store. `source` describes where the
+/// block came from in log and error messages.
+async fn store_block(
+    mut block: ContentItemBlock,
+    source: &str,
+    ipfs_client: IpfsClientRef,
+    options: StoreOptions,
+) -> Result<Option<ContentCid>, FzrError> {
     let start = Instant::now();
 
-    let file_metadata = fs::metadata(&path).await.unwrap();
-    let size_bytes = file_metadata.len();
-    let buffer = fs::read(&path).await.unwrap(); // TODO: error handling
+    block.metadata = options.metadata;
+    block.metadata_cid = options.metadata_cid;
+    block.created_at = match options.created_at {
+        Some(CreatedAt::At(millis)) => Some(millis),
+        Some(CreatedAt::Modified) => block.created_at.or_else(|| unix_millis(SystemTime::now())),
+        None => None,
+    };
 
-    if infer::is_image(&buffer[0..4]) {
-        let (width_px, height_px) = image::image_dimensions(path).unwrap();
-        let mime_type = infer::get(&buffer[0..4]).unwrap().mime_type().to_string();
+    if options.auto_orient {
+        auto_orient(&mut block)
+            .with_context(|| format!("Failed to orient image at {}", source))
+            .map_err(FzrError::decode)?;
+    }
 
-        let buffer = buffer.into_boxed_slice();
+    if options.strip_exif {
+        strip_exif(&mut block)
+            .with_context(|| format!("Failed to strip EXIF data from {}", source))
+            .map_err(FzrError::decode)?;
+    }
 
-        let block = ContentItemBlock {
-            content: ContentItem::Image(
-                ImageContent { buffer },
-                ImageMetadata {
-                    size_bytes,
-                    mime_type,
-                    width_px,
-                    height_px,
-                },
-            ),
-            size_bytes,
-        };
+    if options.compute_phash {
+        if let ContentItem::Image(content, metadata) = &mut block.content {
+            let phash = images::perceptual_hash(&content.buffer)
+                .with_context(|| format!("Failed to hash image at {}", source))
+                .map_err(FzrError::decode)?;
+            metadata.phash = Some(phash);
+        }
+    }
+
+    if let Some(palette_size) = options.palette_size {
+        if let ContentItem::Image(content, metadata) = &mut block.content {
+            metadata.dominant_colors = images::dominant_colors(&content.buffer, palette_size)
+                .with_context(|| format!("Failed to extract colors from {}", source))
+                .map_err(FzrError::decode)?;
+        }
+    }
+
+    if let Some((components_x, components_y)) = options.blurhash_components {
+        if let ContentItem::Image(content, metadata) = &mut block.content {
+            let blurhash = images::blurhash(&content.buffer, components_x, components_y)
+                .with_context(|| format!("Failed to compute BlurHash for {}", source))
+                .map_err(FzrError::decode)?;
+            metadata.blurhash = Some(blurhash);
+        }
+    }
+
+    if options.normalize_line_endings {
+        normalize_line_endings(&mut block);
+    }
+
+    if options.compress_text {
+        compress_text(&mut block)
+            .with_context(|| format!("Failed to compress text at {}", source))
+            .map_err(FzrError::decode)?;
+    }
+
+    let transcoded = if options.transcode_heic {
+        transcode_heic(&mut block, source, &ipfs_client).await?
+    } else {
+        None
+    };
 
-        let ipfs_client = &ipfs_client.write().await;
-        let cid = ipfs_client.add(&block).await?;
+    if options.dry_run {
+        let cid = ContentCid::new(ipfs_client.read().await.compute_cid(&block)?)?;
 
         info!(
-            "Stored {:.2?}MB in {:.2?}.",
-            size_bytes as f32 / 1_048_576_f32,
-            start.elapsed()
+            %cid,
+            source,
+            size_bytes = block.size_bytes,
+            content_type = block.content.mime_type(),
+            "Dry run, not storing content"
         );
 
-        Ok(Some(cid))
-    } else {
-        match String::from_utf8(buffer) {
-            Ok(string) => {
-                let block = ContentItemBlock {
-                    content: ContentItem::Text(TextContent { string }, TextMetadata { size_bytes }),
-                    size_bytes,
-                };
+        return Ok(Some(cid));
+    }
+
+    // Only the adds themselves take the write lock, so loads aren't held up behind the work above
+    // or behind verifying and providing the block once it's written.
+    if let Some(transcoded) = &transcoded {
+        ipfs_client.write().await.add(transcoded).await?;
+    }
 
-                let ipfs_client = &ipfs_client.write().await;
-                let cid = ipfs_client.add(&block).await?;
+    if options.skip_existing {
+        let ipfs_client = &ipfs_client.read().await;
+        let cid = ContentCid::new(ipfs_client.compute_cid(&block)?)?;
 
-                info!(
-                    "Stored {:.2?}MB in {:.2?}.",
-                    size_bytes as f32 / 1_048_576_f32,
-                    start.elapsed()
-                );
+        if ipfs_client.has(&cid).await? {
+            info!(%cid, source, "Skipped storing content already present");
 
-                Ok(Some(cid))
+            if options.auto_provide {
+                ipfs_client.provide(&cid).await?;
             }
-            Err(err) => {
-                error!(
-                    "Error decoding file as text (probably an unhandled binary file): {}",
-                    err
-                );
-                Ok(None)
+
+            if let Some(progress) = &options.progress {
+                progress.report(block.size_bytes, block.size_bytes);
             }
+
+            return Ok(Some(cid));
         }
     }
+
+    let cid = ContentCid::new(ipfs_client.write().await.add(&block).await?)?;
+    let ipfs_client = &ipfs_client.read().await;
+
+    if options.verify {
+        verify_readback(&cid, &block, &ipfs_client.get(&cid).await?)?;
+    }
+
+    if options.auto_provide {
+        ipfs_client.provide(&cid).await?;
+    }
+
+    if let Some(progress) = &options.progress {
+        progress.report(block.size_bytes, block.size_bytes);
+    }
+
+    info!(
+        %cid,
+        size_bytes = block.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        content_type = block.content.mime_type(),
+        "Stored content"
+    );
+
+    Ok(Some(cid))
 }
 
-pub async fn load_file(
-    cid_string: String,
+/// Stores metadata as a block of its own, for content stored with [`StoreOptions::metadata_cid`]
+/// to link to. Metadata shared by many files, like a common originator, is then only stored once.
+pub async fn store_metadata(
+    items: Vec<MetadataItem>,
     ipfs_client: IpfsClientRef,
-) -> Result<ContentItem, Arc<Error>> {
+) -> Result<Cid, FzrError> {
+    let block = MetadataBlock { items };
+    let cid = ipfs_client.write().await.add_metadata(&block).await?;
+
+    Ok(cid)
+}
+
+/// Stores a file and pins it while still holding the client's write lock, so the block can't be
+/// collected between the two steps.
+pub async fn store_file_pinned(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+) -> Result<Option<ContentCid>, FzrError> {
     let start = Instant::now();
 
-    let ipfs_client = &ipfs_client.read().await;
-    let cid = Cid::from_str(&cid_string).unwrap();
-    let data = ipfs_client.get(&cid)?;
+    let block = read_block(&path, None, None).await?;
+
+    let ipfs_client = &ipfs_client.write().await;
+    let cid = ContentCid::new(ipfs_client.add(&block).await?)?;
+    ipfs_client.pin(&cid).await?;
 
     info!(
-        "Loaded {:.2?}MB in {:.2?}.",
-        data.size_bytes as f32 / 1_048_576_f32,
-        start.elapsed()
+        %cid,
+        size_bytes = block.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        content_type = block.content.mime_type(),
+        "Stored and pinned content"
     );
 
-    Ok(data.content)
+    Ok(Some(cid))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::data::ipfs_client::IpfsClient;
+/// Stores a file as a new version of `previous`, linking back to it so [`version_history`] can walk
+/// from the newest version to the first. `None` starts a new chain. Fails with
+/// [`FzrError::NotFound`] if `previous` isn't in the store, rather than leave the chain broken.
+pub async fn store_version(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    previous: Option<ContentCid>,
+) -> Result<ContentCid, FzrError> {
+    let mut block = read_block(&path, None, None).await?;
+    block.previous_version = previous.map(Cid::from);
 
-    use async_std::sync::RwLock;
-    use tempfile::tempdir;
+    let ipfs_client = &ipfs_client.write().await;
 
-    use std::{error::Error, fs::File};
-    use std::{io::Write, path::Path};
+    if let Some(previous) = previous {
+        if !ipfs_client.has(&previous).await? {
+            return Err(FzrError::NotFound(previous.into()));
+        }
+    }
 
-    /// Helper to create file in a directory and return full path.
-    fn write_file<P>(dir: P, data: &[u8], file_name: &str) -> Result<PathBuf, Box<dyn Error>>
-    where
-        P: AsRef<Path>,
-    {
-        let path = dir.as_ref().join(file_name);
-        let mut file = File::create(&path)?;
-        file.write_all(data)?;
-        Ok(path)
+    let cid = ContentCid::new(ipfs_client.add(&block).await?)?;
+
+    info!(
+        %cid,
+        previous = ?block.previous_version,
+        size_bytes = block.size_bytes,
+        content_type = block.content.mime_type(),
+        "Stored version"
+    );
+
+    Ok(cid)
+}
+
+/// The CIDs of every version in `cid`'s chain, newest first, ending with the version that started
+/// it.
+pub async fn version_history(
+    cid: ContentCid,
+    ipfs_client: IpfsClientRef,
+) -> Result<Vec<ContentCid>, FzrError> {
+    let ipfs_client = &ipfs_client.read().await;
+    let mut history = Vec::new();
+    let mut next = Some(cid);
+
+    while let Some(cid) = next {
+        if !ipfs_client.has(&cid).await? {
+            return Err(FzrError::NotFound(cid.into()));
+        }
+
+        let block = ipfs_client.get(&cid).await?;
+        history.push(cid);
+        next = block.previous_version.map(ContentCid::new).transpose()?;
     }
 
-    #[async_std::test]
-    async fn test_store_load() -> Result<(), Box<dyn Error>> {
-        let dir = tempdir()?;
-        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+    Ok(history)
+}
 
-        struct Test {
-            name: &'static str,
-            data: &'static [u8],
-            file_name: &'static str,
-            expected: ContentItem,
+/// Diffs the text stored as `old` against the text stored as `new`, line by line. Fails with
+/// [`FzrError::UnsupportedFormat`] if either isn't text, JSON, Markdown or CSV.
+pub async fn diff_text(
+    old: ContentCid,
+    new: ContentCid,
+    ipfs_client: IpfsClientRef,
+) -> Result<Vec<DiffLine>, FzrError> {
+    let old_text = load_text(old, ipfs_client.clone()).await?;
+    let new_text = load_text(new, ipfs_client).await?;
+
+    Ok(text_ops::diff_lines(&old_text, &new_text))
+}
+
+async fn load_text(cid: ContentCid, ipfs_client: IpfsClientRef) -> Result<String, FzrError> {
+    match load_file(cid, ipfs_client).await? {
+        ContentItem::Text(content, _) => Ok(content.string),
+        ContentItem::Json(content, _) => Ok(content.string),
+        ContentItem::Markdown(content, _) => Ok(content.string),
+        ContentItem::Csv(content, _) => Ok(content.string),
+        other => Err(FzrError::UnsupportedFormat(format!(
+            "Expected text at CID {} but found {}",
+            cid, other
+        ))),
+    }
+}
+
+/// Stores a file encrypted under `key`, so neither its content nor its metadata can be read by
+/// anyone fetching the block without the key. Every call picks a fresh nonce, so storing the same
+/// file twice gives two different CIDs.
+pub async fn store_file_encrypted(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    key: &[u8; KEY_LEN],
+) -> Result<Cid, FzrError> {
+    let start = Instant::now();
+
+    let block = read_block(&path, None, None).await?;
+    let plaintext = DagCborCodec.encode(&block).map_err(FzrError::decode)?;
+    let encrypted = crypto::encrypt(key, &plaintext)
+        .with_context(|| format!("Failed to encrypt file at path: {:?}", &path))
+        .map_err(FzrError::decode)?;
+
+    let cid = ipfs_client.write().await.add_encrypted(&encrypted).await?;
+
+    // The content type is left out, since it's part of what encryption hides.
+    info!(
+        %cid,
+        size_bytes = block.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        "Stored encrypted content"
+    );
+
+    Ok(cid)
+}
+
+/// Loads a file stored with `store_file_encrypted`. Fails with [`FzrError::DecryptionFailed`] if
+/// `key` isn't the one it was stored with.
+pub async fn load_file_encrypted(
+    cid: Cid,
+    ipfs_client: IpfsClientRef,
+    key: &[u8; KEY_LEN],
+) -> Result<ContentItem, FzrError> {
+    let encrypted = ipfs_client.read().await.get_encrypted(&cid).await?;
+    let plaintext = crypto::decrypt(key, &encrypted).ok_or(FzrError::DecryptionFailed(cid))?;
+    let block: ContentItemBlock = DagCborCodec.decode(&plaintext).map_err(FzrError::decode)?;
+
+    Ok(block.content)
+}
+
+/// Stores an image along with a downscaled thumbnail whose longest side is at most
+/// `max_dimension`, linked from the original block's `thumbnail_cid`, returning
+/// `(original_cid, thumbnail_cid)`. The thumbnail is encoded in the default [`ThumbnailFormat`].
+pub async fn store_image_with_thumbnail(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    max_dimension: u32,
+) -> Result<(ContentCid, ContentCid), FzrError> {
+    store_image_with_thumbnail_as(path, ipfs_client, max_dimension, ThumbnailFormat::default())
+        .await
+}
+
+/// Like [`store_image_with_thumbnail`], encoding the thumbnail as `format`.
+pub async fn store_image_with_thumbnail_as(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    max_dimension: u32,
+    format: ThumbnailFormat,
+) -> Result<(ContentCid, ContentCid), FzrError> {
+    let start = Instant::now();
+
+    let mut block = read_block(&path, None, None).await?;
+
+    let (buffer, metadata) = match &block.content {
+        ContentItem::Image(ImageContent { buffer }, _) => {
+            images::thumbnail(buffer, max_dimension, format)
+                .with_context(|| format!("Failed to create thumbnail for path: {:?}", &path))
+                .map_err(FzrError::decode)?
+        }
+        _ => {
+            return Err(FzrError::UnsupportedFormat(format!(
+                "Expected an image but found other content at path: {:?}",
+                &path
+            )))
         }
+    };
 
-        let tests = vec![
-            Test {
-                name: "round-trip smallest possible gif",
-                data: b"GIF89a\x01\0\x01\0\0\0\0;",
-                file_name: "smallest.gif",
-                expected: ContentItem::Image(
-                    ImageContent {
-                        buffer: Box::new(*b"GIF89a\x01\0\x01\0\0\0\0;"),
-                    },
-                    ImageMetadata {
-                        size_bytes: 14,
-                        mime_type: "image/gif".into(),
-                        width_px: 1,
-                        height_px: 1,
-                    },
-                ),
-            },
-            Test {
-                name: "round-trip text file",
-                data: b"howdy",
-                file_name: "howdy.txt",
-                expected: ContentItem::Text(
-                    TextContent {
-                        string: "howdy".into(),
-                    },
-                    TextMetadata { size_bytes: 5 },
-                ),
+    let thumbnail_block = ContentItemBlock {
+        size_bytes: metadata.size_bytes,
+        content: ContentItem::Image(
+            ImageContent {
+                buffer: buffer.into_boxed_slice(),
             },
-        ];
+            metadata,
+        ),
+        metadata: Vec::new(),
+        metadata_cid: None,
+        previous_version: None,
+        transcoded_cid: None,
+        thumbnail_cid: None,
+        created_at: None,
+    };
 
-        for test in tests.into_iter() {
-            let client_ref = client_ref.clone();
-            let path = write_file(dir.path(), test.data, test.file_name)?;
-            let cid = store_file(path, client_ref.clone()).await.unwrap();
-            let actual = load_file(cid.unwrap().to_string(), client_ref)
-                .await
-                .unwrap();
+    let ipfs_client = &ipfs_client.write().await;
+    let thumbnail_cid = ContentCid::new(ipfs_client.add(&thumbnail_block).await?)?;
+    block.thumbnail_cid = Some(*thumbnail_cid);
+    let cid = ContentCid::new(ipfs_client.add(&block).await?)?;
 
-            assert_eq!(test.expected, actual, "{}", test.name);
+    info!(
+        %cid,
+        %thumbnail_cid,
+        size_bytes = block.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        content_type = block.content.mime_type(),
+        "Stored image with thumbnail"
+    );
+
+    Ok((cid, thumbnail_cid))
+}
+
+/// Options controlling how [`store_image_as_with_options`] re-encodes an image.
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    /// Quality for the lossy JPEG and WebP targets, from 1 (smallest) to 100 (best).
+    pub quality: u8,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            quality: DEFAULT_IMAGE_QUALITY,
+        }
+    }
+}
+
+pub async fn store_image_as(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    target: ImageFormat,
+) -> Result<ContentCid, FzrError> {
+    store_image_as_with_options(path, ipfs_client, target, ConvertOptions::default()).await
+}
+
+/// Stores an image re-encoded as `target`, such as PNG, JPEG or WebP, with its metadata
+/// describing the converted image. Re-encoding drops EXIF data.
+pub async fn store_image_as_with_options(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    target: ImageFormat,
+    options: ConvertOptions,
+) -> Result<ContentCid, FzrError> {
+    let start = Instant::now();
+
+    let mut block = read_block(&path, None, None).await?;
+
+    match &mut block.content {
+        ContentItem::Image(content, metadata) => {
+            let (buffer, width_px, height_px) =
+                images::convert(&content.buffer, target, options.quality)
+                    .with_context(|| format!("Failed to convert image at path: {:?}", &path))
+                    .map_err(FzrError::decode)?;
+
+            metadata.size_bytes = buffer.len() as u64;
+            metadata.mime_type = images::mime_type(&buffer);
+            metadata.width_px = width_px;
+            metadata.height_px = height_px;
+            metadata.exif = None;
+            block.size_bytes = metadata.size_bytes;
+            content.buffer = buffer.into_boxed_slice();
+        }
+        _ => {
+            return Err(FzrError::UnsupportedFormat(format!(
+                "Expected an image but found other content at path: {:?}",
+                &path
+            )))
+        }
+    }
+
+    let cid = ContentCid::new(ipfs_client.write().await.add(&block).await?)?;
+
+    info!(
+        %cid,
+        size_bytes = block.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        content_type = block.content.mime_type(),
+        "Stored converted image"
+    );
+
+    Ok(cid)
+}
+
+/// Outcome of [`store_files`]: each path's own result, plus totals across the batch.
+#[derive(Clone, Debug, Default)]
+pub struct BatchStoreReport {
+    pub results: Vec<(PathBuf, Result<Option<ContentCid>, FzrError>)>,
+    pub total_files: usize,
+    pub unique_cids: usize,
+    /// Bytes of files whose CID hadn't already been seen in the batch.
+    pub bytes_stored: u64,
+    /// Bytes of files identical to one earlier in the batch, which shared its CID.
+    pub bytes_deduplicated: u64,
+}
+
+/// Stores many files, running up to `concurrency` stores at once. Each path is reported alongside
+/// its own result, so one failing file doesn't abort the rest of the batch.
+pub async fn store_files(
+    paths: Vec<PathBuf>,
+    ipfs_client: IpfsClientRef,
+    concurrency: usize,
+) -> BatchStoreReport {
+    let pool = &BufferPool::default();
+
+    let stored: Vec<_> = stream::iter(paths)
+        .map(|path| {
+            let ipfs_client = Arc::clone(&ipfs_client);
+            async move {
+                let (size_bytes, result) = store_file_pooled(path.clone(), ipfs_client, pool).await;
+                (path, size_bytes, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut report = BatchStoreReport {
+        total_files: stored.len(),
+        ..BatchStoreReport::default()
+    };
+    let mut seen = HashSet::new();
+
+    for (path, size_bytes, result) in stored {
+        if let Ok(Some(cid)) = &result {
+            if seen.insert(*cid) {
+                report.bytes_stored += size_bytes;
+            } else {
+                report.bytes_deduplicated += size_bytes;
+            }
+        }
+
+        report.results.push((path, result));
+    }
+
+    report.unique_cids = seen.len();
+
+    report
+}
+
+/// Read buffers shared by a batch's concurrent stores, so each file is read into a buffer an
+/// earlier one already grew instead of a freshly allocated one.
+#[derive(Default)]
+struct BufferPool {
+    buffers: std::sync::Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    fn take(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    fn put(&self, buffer: Vec<u8>) {
+        if buffer.capacity() <= MAX_POOLED_BUFFER_BYTES {
+            self.buffers.lock().unwrap().push(buffer);
+        }
+    }
+}
+
+/// Stores files one after another, all or nothing: if any fails, the blocks this call added are
+/// removed again before its error is returned. Blocks that were already present, such as another
+/// file's identical content, are left alone. The client's write lock is held for the whole batch,
+/// so no other store can add one of its blocks in the meantime and then lose it to the rollback.
+/// If the rollback can't remove everything it added, the error is
+/// [`FzrError::RollbackIncomplete`], listing the blocks left behind.
+pub async fn store_files_atomic(
+    paths: Vec<PathBuf>,
+    ipfs_client: IpfsClientRef,
+) -> Result<Vec<ContentCid>, FzrError> {
+    let ipfs_client = &ipfs_client.write().await;
+    let mut cids = Vec::with_capacity(paths.len());
+    let mut added = Vec::new();
+
+    for path in paths {
+        match store_file_tracked(&path, ipfs_client).await {
+            Ok((cid, newly_added)) => {
+                if newly_added {
+                    added.push(cid);
+                }
+                cids.push(cid);
+            }
+            Err(err) => return Err(roll_back(err, &added, ipfs_client).await),
+        }
+    }
+
+    Ok(cids)
+}
+
+/// Stores a file through a client the caller holds the write lock of, also returning whether its
+/// block wasn't in the store beforehand.
+async fn store_file_tracked(
+    path: &Path,
+    ipfs_client: &IpfsClient,
+) -> Result<(ContentCid, bool), FzrError> {
+    let block = read_block(path, None, None).await?;
+
+    let cid = ContentCid::new(ipfs_client.compute_cid(&block)?)?;
+    let newly_added = !ipfs_client.has(&cid).await?;
+    ipfs_client.add(&block).await?;
+
+    info!(
+        %cid,
+        size_bytes = block.size_bytes,
+        content_type = block.content.mime_type(),
+        "Stored content"
+    );
+
+    Ok((cid, newly_added))
+}
+
+/// Removes the blocks a failed batch added, returning the batch's error, wrapped in
+/// [`FzrError::RollbackIncomplete`] if any of them couldn't be removed.
+async fn roll_back(err: FzrError, cids: &[ContentCid], ipfs_client: &IpfsClient) -> FzrError {
+    let mut left_behind = Vec::new();
+
+    for cid in cids {
+        match ipfs_client.remove(cid).await {
+            Ok(true) => info!(%cid, "Rolled back stored content"),
+            Ok(false) => info!(%cid, "Rolled back content was already removed"),
+            Err(err) => {
+                warn!(%cid, error = %err, "Failed to roll back stored content");
+                left_behind.push(*cid);
+            }
+        }
+    }
+
+    if left_behind.is_empty() {
+        err
+    } else {
+        FzrError::RollbackIncomplete(Arc::new(err), left_behind)
+    }
+}
+
+/// Same as [`store_file`], but reads the file into a buffer taken from `pool`, and only an
+/// exact-sized copy of it goes into the block. Also returns the file's size, which is 0 if it
+/// couldn't be read.
+async fn store_file_pooled(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    pool: &BufferPool,
+) -> (u64, Result<Option<ContentCid>, FzrError>) {
+    let source = format!("path: {:?}", &path);
+    let mut buffer = pool.take();
+
+    let (path, buffer, read) = runtime::spawn_blocking(move || {
+        let read = read_file_into(&path, &mut buffer);
+        (path, buffer, read)
+    })
+    .await;
+
+    let block = read.and_then(|()| {
+        let content_type = extension_content_type(&path, &buffer);
+        classify_block(&buffer, &source, content_type)
+    });
+    let size_bytes = block.as_ref().map_or(0, |block| block.size_bytes);
+    pool.put(buffer);
+
+    let result = match block {
+        Ok(block) => store_block(block, &source, ipfs_client, StoreOptions::default()).await,
+        Err(err) => Err(err),
+    };
+
+    (size_bytes, result)
+}
+
+/// Reads a whole file into `buffer`, replacing what it held.
+fn read_file_into(path: &Path, buffer: &mut Vec<u8>) -> Result<(), FzrError> {
+    let mut file = std::fs::File::open(path)?;
+    let file_metadata = file.metadata()?;
+
+    if file_metadata.is_dir() {
+        return Err(FzrError::UnsupportedFormat(format!(
+            "Expected a file but found a directory at path: {:?}",
+            path
+        )));
+    }
+
+    buffer.clear();
+    buffer.reserve(file_metadata.len() as usize);
+    io::Read::read_to_end(&mut file, buffer)?;
+
+    Ok(())
+}
+
+/// Options controlling how [`store_directory_with_options`] walks and stores a directory.
+#[derive(Clone, Debug)]
+pub struct DirectoryOptions {
+    /// Skip dot-prefixed files and directories.
+    pub skip_hidden: bool,
+    /// Maximum number of files stored at once.
+    pub concurrency: usize,
+    /// Store what symlinks point to, instead of recording them as skipped. A directory reached
+    /// through more than one path, such as by a symlink cycle, is only walked the first time.
+    pub follow_symlinks: bool,
+    /// Only store files whose path relative to the root matches one of these globs, such as
+    /// `**/*.jpg`. Empty stores every file. Files left out don't appear in the manifest.
+    pub include: Vec<String>,
+    /// Leave out files whose relative path matches any of these globs, even if it's included.
+    pub exclude: Vec<String>,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        DirectoryOptions {
+            skip_hidden: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            follow_symlinks: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+pub async fn store_directory(
+    root: PathBuf,
+    ipfs_client: IpfsClientRef,
+) -> Result<DirectoryManifest, FzrError> {
+    store_directory_with_options(root, ipfs_client, DirectoryOptions::default()).await
+}
+
+/// Recursively stores every file under `root`. Files that fail to store, and symlinks unless
+/// `follow_symlinks` is set, are recorded in the manifest rather than failing the whole directory.
+/// Fails with [`FzrError::InvalidGlob`] before storing anything if a pattern can't be parsed.
+pub async fn store_directory_with_options(
+    root: PathBuf,
+    ipfs_client: IpfsClientRef,
+    options: DirectoryOptions,
+) -> Result<DirectoryManifest, FzrError> {
+    let start = Instant::now();
+
+    let root_metadata = fs::metadata(&root).await?;
+
+    if !root_metadata.is_dir() {
+        return Err(FzrError::UnsupportedFormat(format!(
+            "Expected a directory but found a file at path: {:?}",
+            &root
+        )));
+    }
+
+    let filter = PathFilter::new(&options.include, &options.exclude)?;
+
+    let walk_root = root.clone();
+    let (skip_hidden, follow_symlinks) = (options.skip_hidden, options.follow_symlinks);
+    let walk = runtime::spawn_blocking(move || {
+        walk_dir_with_options(&walk_root, skip_hidden, follow_symlinks)
+    })
+    .await;
+
+    let mut manifest = DirectoryManifest::default();
+    let relative_path = |path: &Path| path.strip_prefix(&root).unwrap_or(path).to_path_buf();
+
+    for (path, reason) in walk.skipped {
+        let relative_path = relative_path(&path);
+
+        if filter.matches(&relative_path) {
+            let relative_path = relative_path.to_string_lossy().into_owned();
+            manifest
+                .entries
+                .insert(relative_path, ManifestEntry::Skipped(reason));
+        }
+    }
+
+    let files = walk
+        .files
+        .into_iter()
+        .filter(|path| filter.matches(&relative_path(path)))
+        .collect();
+    let report = store_files(files, ipfs_client, options.concurrency).await;
+
+    for (path, result) in report.results {
+        let entry = match result {
+            Ok(Some(cid)) => ManifestEntry::Stored(cid.into()),
+            Ok(None) => ManifestEntry::Failed("No CID was returned".into()),
+            Err(err) => ManifestEntry::Failed(err.to_string()),
+        };
+
+        let relative_path = relative_path(&path).to_string_lossy().into_owned();
+        manifest.entries.insert(relative_path, entry);
+    }
+
+    info!(
+        root = ?&root,
+        file_count = manifest.entries.len(),
+        elapsed_ms = elapsed_ms(start),
+        "Stored directory"
+    );
+
+    Ok(manifest)
+}
+
+/// Lazily loads the content of every stored entry in a manifest, up to `DEFAULT_CONCURRENCY` at
+/// a time, yielding each relative path with its content as soon as it's loaded. Entries that
+/// failed to store or were skipped are left out.
+pub fn stream_manifest(
+    manifest: &DirectoryManifest,
+    ipfs_client: IpfsClientRef,
+) -> impl Stream<Item = (PathBuf, Result<ContentItem, FzrError>)> {
+    let entries: Vec<_> = manifest
+        .entries
+        .iter()
+        .filter_map(|(relative_path, entry)| match entry {
+            ManifestEntry::Stored(cid) => Some((PathBuf::from(relative_path), *cid)),
+            ManifestEntry::Failed(_) | ManifestEntry::Skipped(_) => None,
+        })
+        .collect();
+
+    stream::iter(entries)
+        .map(move |(path, cid)| {
+            let ipfs_client = Arc::clone(&ipfs_client);
+            async move {
+                let result = match ContentCid::new(cid) {
+                    Ok(cid) => load_file(cid, ipfs_client).await,
+                    Err(err) => Err(err),
+                };
+                (path, result)
+            }
+        })
+        .buffer_unordered(DEFAULT_CONCURRENCY)
+}
+
+/// Milliseconds since `start`, for the `elapsed_ms` log field.
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// Milliseconds between the Unix epoch and `time`, negative for times before it.
+fn unix_millis(time: SystemTime) -> Option<i64> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => i64::try_from(since.as_millis()).ok(),
+        Err(err) => i64::try_from(err.duration().as_millis())
+            .ok()
+            .map(|millis| -millis),
+    }
+}
+
+/// Checks that a block read back from the store is identical to the one that was added.
+fn verify_readback(
+    cid: &Cid,
+    expected: &ContentItemBlock,
+    actual: &ContentItemBlock,
+) -> Result<(), FzrError> {
+    if actual != expected {
+        return Err(FzrError::VerificationFailed(*cid));
+    }
+
+    Ok(())
+}
+
+/// Reads a file from disk and classifies it into a content block ready to be stored. Files over
+/// `max_size_bytes` are rejected before any of them is read.
+async fn read_block(
+    path: &Path,
+    max_size_bytes: Option<u64>,
+    content_type: Option<&str>,
+) -> Result<ContentItemBlock, FzrError> {
+    let buffer = read_file(path, max_size_bytes).await?;
+    let content_type = content_type.or_else(|| extension_content_type(path, &buffer));
+
+    classify_block(&buffer, &format!("path: {:?}", &path), content_type)
+}
+
+/// Reads a file's bytes, rejecting directories and files over `max_size_bytes` before reading.
+async fn read_file(path: &Path, max_size_bytes: Option<u64>) -> Result<Vec<u8>, FzrError> {
+    let file_metadata = fs::metadata(&path).await?;
+
+    if file_metadata.is_dir() {
+        return Err(FzrError::UnsupportedFormat(format!(
+            "Expected a file but found a directory at path: {:?}",
+            &path
+        )));
+    }
+
+    if let Some(max_size_bytes) = max_size_bytes {
+        if file_metadata.len() > max_size_bytes {
+            return Err(FzrError::TooLarge(max_size_bytes));
+        }
+    }
+
+    Ok(fs::read(&path).await?)
+}
+
+/// The content type a file's extension calls for, for formats that can't be told apart from
+/// plain text by their contents. Content with a magic number is always classified by that, so a
+/// misnamed image is still stored as an image.
+fn extension_content_type(path: &Path, buffer: &[u8]) -> Option<&'static str> {
+    if infer::get(buffer).is_some() {
+        return None;
+    }
+
+    if markdown::has_markdown_extension(path) {
+        Some(markdown::MARKDOWN_MIME_TYPE)
+    } else if csv::has_csv_extension(path) {
+        Some(csv::CSV_MIME_TYPE)
+    } else {
+        None
+    }
+}
+
+/// Classifies raw bytes into a content block ready to be stored. Unless the caller supplies a
+/// `content_type`, images, video and audio are detected by their magic numbers, falling back to
+/// text, then binary. The block gets its own exact-sized copy of `buffer`.
+fn classify_block(
+    buffer: &[u8],
+    source: &str,
+    content_type: Option<&str>,
+) -> Result<ContentItemBlock, FzrError> {
+    let size_bytes = buffer.len() as u64;
+    let content = classify::classify(buffer, source, content_type)?;
+
+    Ok(ContentItemBlock {
+        content,
+        size_bytes,
+        metadata: Vec::new(),
+        metadata_cid: None,
+        previous_version: None,
+        transcoded_cid: None,
+        thumbnail_cid: None,
+        created_at: None,
+    })
+}
+
+/// How many of a streamed file's chunks were new to the store. Chunks are content-addressed, so
+/// one already stored by this or any other file is linked rather than written again. Reports can
+/// be summed with `+=` to see how much a whole corpus deduplicates.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChunkStoreReport {
+    pub total_chunks: u64,
+    pub unique_chunks: u64,
+    /// Bytes of chunks that were already in the store.
+    pub bytes_saved: u64,
+}
+
+impl AddAssign for ChunkStoreReport {
+    fn add_assign(&mut self, other: Self) {
+        self.total_chunks += other.total_chunks;
+        self.unique_chunks += other.unique_chunks;
+        self.bytes_saved += other.bytes_saved;
+    }
+}
+
+/// Stores a file as a sequence of `chunk_size` raw blocks linked from a root block, so only one
+/// chunk is held in memory at a time regardless of file size. `progress` is called after each
+/// chunk is stored.
+pub async fn store_file_streaming(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    chunk_size: usize,
+    progress: Option<ProgressCallback>,
+) -> Result<Cid, FzrError> {
+    let (cid, _) =
+        store_file_streaming_with_report(path, ipfs_client, chunk_size, progress).await?;
+
+    Ok(cid)
+}
+
+/// Like [`store_file_streaming`], also reporting how many chunks were deduplicated.
+pub async fn store_file_streaming_with_report(
+    path: PathBuf,
+    ipfs_client: IpfsClientRef,
+    chunk_size: usize,
+    progress: Option<ProgressCallback>,
+) -> Result<(Cid, ChunkStoreReport), FzrError> {
+    let start = Instant::now();
+
+    let total_bytes = fs::metadata(&path).await?.len();
+    let mut file = runtime::open(&path).await?;
+
+    let mut buffer = vec![0; chunk_size.max(1)];
+    let mut chunks = Vec::new();
+    let mut size_bytes = 0;
+    let mut report = ChunkStoreReport::default();
+
+    loop {
+        let len = read_chunk(&mut file, &mut buffer).await?;
+
+        if len == 0 {
+            break;
+        }
+
+        let chunk = &buffer[..len];
+        let (cid, present) = {
+            let ipfs_client = ipfs_client.read().await;
+            let cid = ipfs_client.compute_chunk_cid(chunk)?;
+            (cid, ipfs_client.has(&cid).await?)
+        };
+
+        if present {
+            report.bytes_saved += len as u64;
+        } else {
+            ipfs_client.write().await.add_chunk(chunk).await?;
+            report.unique_chunks += 1;
+        }
+
+        report.total_chunks += 1;
+        chunks.push(cid);
+        size_bytes += len as u64;
+
+        if let Some(progress) = &progress {
+            progress.report(size_bytes, total_bytes);
+        }
+    }
+
+    let block = ChunkedFileBlock {
+        chunks,
+        chunk_size_bytes: buffer.len() as u64,
+        size_bytes,
+    };
+    let cid = ipfs_client.write().await.add_chunked_file(&block).await?;
+
+    info!(
+        %cid,
+        size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        chunk_count = block.chunks.len(),
+        unique_chunks = report.unique_chunks,
+        "Stored chunked file"
+    );
+
+    Ok((cid, report))
+}
+
+/// Fills `buffer` from `file`, returning fewer bytes only at the end of the file.
+async fn read_chunk<R: AsyncRead + Unpin>(file: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        let read = file.read(&mut buffer[filled..]).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        filled += read;
+    }
+
+    Ok(filled)
+}
+
+/// Loads a file stored with `store_file_streaming`. Chunks are fetched lazily as the returned
+/// reader is consumed.
+pub async fn load_file_streaming(
+    cid_string: String,
+    ipfs_client: IpfsClientRef,
+) -> Result<impl AsyncRead + Unpin, FzrError> {
+    let cid = Cid::from_str(&cid_string)?;
+    let block = ipfs_client.read().await.get_chunked_file(&cid).await?;
+
+    let reader = chunks(block.chunks, ipfs_client)
+        .map_err(io::Error::other)
+        .boxed()
+        .into_async_read();
+
+    Ok(reader)
+}
+
+/// Streams the chunks of a file stored with `store_file_streaming` in order, so callers can
+/// process or forward part of a large file without reassembling all of it. Each chunk is fetched
+/// only once the previous one has been consumed, and a missing root fails the first item.
+pub fn chunk_iter(
+    root: &Cid,
+    ipfs_client: IpfsClientRef,
+) -> impl Stream<Item = Result<Vec<u8>, FzrError>> {
+    let root = *root;
+    let root_client = Arc::clone(&ipfs_client);
+
+    stream::once(async move { root_client.read().await.get_chunked_file(&root).await })
+        .map_ok(move |block| chunks(block.chunks, Arc::clone(&ipfs_client)))
+        .map_err(FzrError::from)
+        .try_flatten()
+}
+
+/// Reads up to `len` bytes from offset `start` of a file stored with `store_file_streaming`,
+/// fetching only the chunks the range overlaps, as serving a byte range of a video needs. Ranges
+/// running past the end of the file are cut short there.
+pub async fn read_range(
+    root: &Cid,
+    start: u64,
+    len: u64,
+    ipfs_client: IpfsClientRef,
+) -> Result<Vec<u8>, FzrError> {
+    let ipfs_client = ipfs_client.read().await;
+    let block = ipfs_client.get_chunked_file(root).await?;
+
+    let end = start.saturating_add(len).min(block.size_bytes);
+    if start >= end {
+        return Ok(Vec::new());
+    }
+
+    let chunk_size = block.chunk_size_bytes.max(1);
+    let first_chunk = start / chunk_size;
+    let last_chunk = (end - 1) / chunk_size;
+
+    let mut bytes = Vec::with_capacity((end - start) as usize);
+    for index in first_chunk..=last_chunk {
+        let cid = block
+            .chunks
+            .get(index as usize)
+            .ok_or_else(|| FzrError::decode(anyhow!("Chunked file {} is truncated", root)))?;
+        let chunk = ipfs_client.get_chunk(cid).await?;
+
+        let chunk_start = index * chunk_size;
+        let from = start.saturating_sub(chunk_start) as usize;
+        let to = (end - chunk_start).min(chunk_size) as usize;
+        let range = chunk
+            .get(from..to)
+            .ok_or_else(|| FzrError::decode(anyhow!("Chunk {} is shorter than expected", cid)))?;
+        bytes.extend_from_slice(range);
+    }
+
+    Ok(bytes)
+}
+
+fn chunks(
+    cids: Vec<Cid>,
+    ipfs_client: IpfsClientRef,
+) -> impl Stream<Item = Result<Vec<u8>, FzrError>> {
+    stream::iter(cids).then(move |cid| {
+        let ipfs_client = Arc::clone(&ipfs_client);
+        async move { Ok(ipfs_client.read().await.get_chunk(&cid).await?) }
+    })
+}
+
+/// Applies an image block's EXIF orientation to its pixels, leaving an upright image whose
+/// orientation is reset to normal. Other content is left untouched.
+fn auto_orient(block: &mut ContentItemBlock) -> Result<()> {
+    if let ContentItem::Image(content, metadata) = &mut block.content {
+        if let Some(exif) = &mut metadata.exif {
+            let orientation = exif
+                .orientation
+                .filter(|&orientation| orientation != NORMAL_ORIENTATION);
+
+            if let Some(orientation) = orientation {
+                let (buffer, width_px, height_px) =
+                    images::apply_orientation(&content.buffer, orientation)?;
+
+                exif.orientation = Some(NORMAL_ORIENTATION);
+                metadata.size_bytes = buffer.len() as u64;
+                metadata.mime_type = images::mime_type(&buffer);
+                metadata.width_px = width_px;
+                metadata.height_px = height_px;
+                block.size_bytes = metadata.size_bytes;
+                content.buffer = buffer.into_boxed_slice();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces an image block's buffer with a re-encoded copy that carries no EXIF data. Other
+/// content is left untouched.
+fn strip_exif(block: &mut ContentItemBlock) -> Result<()> {
+    if let ContentItem::Image(content, metadata) = &mut block.content {
+        let buffer = images::strip_metadata(&content.buffer)?;
+
+        metadata.size_bytes = buffer.len() as u64;
+        metadata.mime_type = images::mime_type(&buffer);
+        metadata.exif = None;
+        block.size_bytes = metadata.size_bytes;
+        content.buffer = buffer.into_boxed_slice();
+    }
+
+    Ok(())
+}
+
+/// Converts the line endings of a text, JSON, Markdown or CSV block to LF, updating its sizes and
+/// counts to match. The fingerprint is left as it was, so it still matches the file on disk. Other
+/// content, and text that only uses LF already, is left untouched.
+fn normalize_line_endings(block: &mut ContentItemBlock) {
+    match &mut block.content {
+        ContentItem::Text(content, metadata) if needs_normalizing(metadata.line_endings) => {
+            content.string = text_ops::normalize_line_endings(&content.string);
+
+            metadata.size_bytes = content.string.len() as u64;
+            metadata.char_count = content.string.chars().count() as u64;
+            metadata.line_endings_normalized = true;
+            block.size_bytes = metadata.size_bytes;
+        }
+        // Line breaks can only be whitespace between values, so the shape is unchanged.
+        ContentItem::Json(content, metadata) if needs_normalizing(metadata.line_endings) => {
+            content.string = text_ops::normalize_line_endings(&content.string);
+
+            metadata.size_bytes = content.string.len() as u64;
+            metadata.line_endings_normalized = true;
+            block.size_bytes = metadata.size_bytes;
+        }
+        ContentItem::Markdown(content, metadata) if needs_normalizing(metadata.line_endings) => {
+            content.string = text_ops::normalize_line_endings(&content.string);
+
+            metadata.size_bytes = content.string.len() as u64;
+            metadata.outline = markdown::outline(&content.string);
+            metadata.line_endings_normalized = true;
+            block.size_bytes = metadata.size_bytes;
+        }
+        // Quoted fields can hold line breaks of their own, which change with the rest.
+        ContentItem::Csv(content, metadata) if needs_normalizing(metadata.line_endings) => {
+            content.string = text_ops::normalize_line_endings(&content.string);
+
+            let delimiter = metadata.delimiter.chars().next().unwrap_or(',');
+            let table = csv::read_table_with(&content.string, delimiter);
+
+            metadata.size_bytes = content.string.len() as u64;
+            metadata.row_count = table.row_count;
+            metadata.column_count = table.column_count;
+            metadata.has_header = table.has_header;
+            metadata.line_endings_normalized = true;
+            block.size_bytes = metadata.size_bytes;
+        }
+        _ => {}
+    }
+}
+
+fn needs_normalizing(line_endings: LineEndings) -> bool {
+    !matches!(line_endings, LineEndings::Lf | LineEndings::None)
+}
+
+/// Replaces a text, JSON, Markdown or CSV block's content with its zstd-compressed form, unless
+/// compressing wouldn't save space. Other content is left untouched.
+fn compress_text(block: &mut ContentItemBlock) -> Result<()> {
+    let compressed = match &block.content {
+        ContentItem::Text(content, metadata) => {
+            text_ops::compress(&content.string)?.map(|buffer| {
+                let mut metadata = metadata.clone();
+                metadata.compression = Compression::Zstd;
+                metadata.compressed_size_bytes = Some(buffer.len() as u64);
+
+                let size_bytes = buffer.len() as u64;
+                let content = ContentItem::CompressedText(compressed_content(buffer), metadata);
+                (size_bytes, content)
+            })
+        }
+        ContentItem::Json(content, metadata) => {
+            text_ops::compress(&content.string)?.map(|buffer| {
+                let mut metadata = metadata.clone();
+                metadata.compression = Compression::Zstd;
+                metadata.compressed_size_bytes = Some(buffer.len() as u64);
+
+                let size_bytes = buffer.len() as u64;
+                let content = ContentItem::CompressedJson(compressed_content(buffer), metadata);
+                (size_bytes, content)
+            })
+        }
+        ContentItem::Markdown(content, metadata) => {
+            text_ops::compress(&content.string)?.map(|buffer| {
+                let mut metadata = metadata.clone();
+                metadata.compression = Compression::Zstd;
+                metadata.compressed_size_bytes = Some(buffer.len() as u64);
+
+                let size_bytes = buffer.len() as u64;
+                let content = ContentItem::CompressedMarkdown(compressed_content(buffer), metadata);
+                (size_bytes, content)
+            })
+        }
+        ContentItem::Csv(content, metadata) => text_ops::compress(&content.string)?.map(|buffer| {
+            let mut metadata = metadata.clone();
+            metadata.compression = Compression::Zstd;
+            metadata.compressed_size_bytes = Some(buffer.len() as u64);
+
+            let size_bytes = buffer.len() as u64;
+            let content = ContentItem::CompressedCsv(compressed_content(buffer), metadata);
+            (size_bytes, content)
+        }),
+        _ => None,
+    };
+
+    if let Some((size_bytes, content)) = compressed {
+        block.size_bytes = size_bytes;
+        block.content = content;
+    }
+
+    Ok(())
+}
+
+fn compressed_content(buffer: Vec<u8>) -> CompressedTextContent {
+    CompressedTextContent {
+        buffer: buffer.into_boxed_slice(),
+    }
+}
+
+/// Builds a JPEG copy of a HEIC image and links it from the image's block, returning it to be
+/// stored alongside. Anything else is left as it is.
+async fn transcode_heic(
+    block: &mut ContentItemBlock,
+    source: &str,
+    ipfs_client: &IpfsClientRef,
+) -> Result<Option<ContentItemBlock>, FzrError> {
+    let (content, metadata) = match &block.content {
+        ContentItem::Image(content, metadata) if heic::is_heic(&metadata.mime_type) => {
+            (content, metadata)
+        }
+        _ => return Ok(None),
+    };
+
+    let jpeg = heic::to_jpeg(&content.buffer, DEFAULT_IMAGE_QUALITY)
+        .with_context(|| format!("Failed to transcode HEIC image at {}", source))
+        .map_err(FzrError::decode)?;
+    let mut transcoded = classify_block(&jpeg, source, None)?;
+
+    if let ContentItem::Image(_, transcoded_metadata) = &mut transcoded.content {
+        transcoded_metadata.original_format = Some(metadata.mime_type.clone());
+    }
+
+    block.transcoded_cid = Some(ipfs_client.read().await.compute_cid(&transcoded)?);
+
+    Ok(Some(transcoded))
+}
+
+/// Restores the text of a block stored with `compress_text`. Other content is returned as-is.
+fn decompress_text(content: ContentItem) -> Result<ContentItem> {
+    match content {
+        ContentItem::CompressedText(content, metadata) => {
+            let string = text_ops::decompress(&content.buffer)?;
+
+            Ok(ContentItem::Text(TextContent { string }, metadata))
+        }
+        ContentItem::CompressedJson(content, metadata) => {
+            let string = text_ops::decompress(&content.buffer)?;
+
+            Ok(ContentItem::Json(JsonContent { string }, metadata))
+        }
+        ContentItem::CompressedMarkdown(content, metadata) => {
+            let string = text_ops::decompress(&content.buffer)?;
+
+            Ok(ContentItem::Markdown(MarkdownContent { string }, metadata))
+        }
+        ContentItem::CompressedCsv(content, metadata) => {
+            let string = text_ops::decompress(&content.buffer)?;
+
+            Ok(ContentItem::Csv(CsvContent { string }, metadata))
+        }
+        content => Ok(content),
+    }
+}
+
+pub async fn load_file(
+    cid: ContentCid,
+    ipfs_client: IpfsClientRef,
+) -> Result<ContentItem, FzrError> {
+    let (content, _) = load_file_with_metadata(cid, ipfs_client).await?;
+
+    Ok(content)
+}
+
+/// Loads many files, running up to `concurrency` loads at once. Results come back in the order the
+/// CIDs were given, each alongside its own result, so one missing block doesn't fail the rest.
+pub async fn load_files(
+    cids: Vec<ContentCid>,
+    ipfs_client: IpfsClientRef,
+    concurrency: usize,
+) -> Vec<(ContentCid, Result<ContentItem, FzrError>)> {
+    stream::iter(cids)
+        .map(|cid| {
+            let ipfs_client = Arc::clone(&ipfs_client);
+            async move { (cid, load_file(cid, ipfs_client).await) }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+pub async fn load_file_with_metadata(
+    cid: ContentCid,
+    ipfs_client: IpfsClientRef,
+) -> Result<(ContentItem, Vec<MetadataItem>), FzrError> {
+    load_file_with_options(cid, ipfs_client, LoadOptions::default()).await
+}
+
+/// Options controlling how [`load_file_with_options`] looks up content.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// How long to wait for the block before giving up with [`FzrError::NotFound`].
+    pub timeout: Duration,
+    /// Also load metadata linked by CID, returning it after the embedded metadata.
+    pub resolve_metadata: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            timeout: DEFAULT_LOAD_TIMEOUT,
+            resolve_metadata: false,
+        }
+    }
+}
+
+pub async fn load_file_with_options(
+    cid: ContentCid,
+    ipfs_client: IpfsClientRef,
+    options: LoadOptions,
+) -> Result<(ContentItem, Vec<MetadataItem>), FzrError> {
+    let start = Instant::now();
+
+    let ipfs_client = &ipfs_client.read().await;
+    let data = get_block(cid, ipfs_client, options.timeout).await?;
+
+    info!(
+        %cid,
+        size_bytes = data.size_bytes,
+        elapsed_ms = elapsed_ms(start),
+        content_type = data.content.mime_type(),
+        "Loaded content"
+    );
+
+    let content = decompress_text(data.content)
+        .with_context(|| format!("Failed to decompress text from CID: {}", cid))
+        .map_err(FzrError::decode)?;
+
+    let mut metadata = data.metadata;
+
+    if options.resolve_metadata {
+        if let Some(metadata_cid) = data.metadata_cid {
+            metadata.extend(ipfs_client.get_metadata(&metadata_cid).await?.items);
+        }
+    }
+
+    Ok((content, metadata))
+}
+
+/// Loads just the metadata of stored content, such as an image's dimensions, for laying out a
+/// view before the content itself is needed. The content is dropped undecoded: compressed text
+/// isn't decompressed and images aren't decoded. It still arrives in the same block, so this
+/// saves decoding rather than fetching.
+pub async fn load_metadata(
+    cid: ContentCid,
+    ipfs_client: IpfsClientRef,
+) -> Result<ContentMetadata, FzrError> {
+    let block = get_block(cid, &*ipfs_client.read().await, DEFAULT_LOAD_TIMEOUT).await?;
+
+    Ok(block.content.into())
+}
+
+/// Reads a content block, giving up with [`FzrError::NotFound`] if it isn't found in `timeout`.
+async fn get_block(
+    cid: ContentCid,
+    ipfs_client: &IpfsClient,
+    timeout: Duration,
+) -> Result<ContentItemBlock, FzrError> {
+    let lookup = async {
+        if ipfs_client.has(&cid).await? {
+            Ok(Some(ipfs_client.get(&cid).await?))
+        } else {
+            Ok::<_, FzrError>(None)
+        }
+    };
+
+    runtime::timeout(timeout, lookup)
+        .await
+        .unwrap_or(Ok(None))?
+        .ok_or(FzrError::NotFound(cid.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::car::{CarHeader, CAR_VERSION};
+    use crate::data::content::{
+        AudioContent, AudioMetadata, BinaryContent, BinaryMetadata, FontFormat, Heading,
+        ImageMetadata, JsonKind, LineEndings, TextMetadata, VectorContent, VectorMetadata,
+        VideoContent, VideoMetadata,
+    };
+    use crate::data::exif_ops;
+    use crate::data::ipfs_client::{IpfsClientConfig, MultihashCode};
+    use crate::data::meta::{tag, tags_of, MetadataCategory};
+
+    use crate::data::runtime::RwLock;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use libipld::cbor::DagCborCodec;
+    use libipld::codec::Codec;
+    use libipld::multihash::Code;
+    use tempfile::tempdir;
+    use tracing::field::{Field, Visit};
+    use tracing::instrument::WithSubscriber;
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Layer, SubscriberExt};
+
+    use std::collections::HashMap;
+    use std::sync::{Barrier, Mutex};
+    use std::{error::Error, fs::File};
+    use std::{io::Write, path::Path};
+
+    /// A client whose blocks are gone once it's dropped, for tests that shouldn't see or leave
+    /// blocks in the shared repo.
+    async fn in_memory_client() -> IpfsClient {
+        let config = IpfsClientConfig {
+            in_memory: true,
+            ..IpfsClientConfig::default()
+        };
+
+        IpfsClient::with_config(config).await.unwrap()
+    }
+
+    /// Helper to create file in a directory and return full path.
+    fn write_file<P>(dir: P, data: &[u8], file_name: &str) -> Result<PathBuf, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = dir.as_ref().join(file_name);
+        let mut file = File::create(&path)?;
+        file.write_all(data)?;
+        Ok(path)
+    }
+
+    /// Records the fields of every event logged while it's subscribed.
+    #[derive(Clone, Default)]
+    struct CapturedEvents(Arc<Mutex<Vec<HashMap<String, String>>>>);
+
+    impl<S: Subscriber> Layer<S> for CapturedEvents {
+        fn on_event(&self, event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+            let mut fields = EventFields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+
+    #[derive(Default)]
+    struct EventFields(HashMap<String, String>);
+
+    impl Visit for EventFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    #[runtime::test]
+    async fn test_store_load() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        struct Test {
+            name: &'static str,
+            data: &'static [u8],
+            file_name: &'static str,
+            expected: ContentItem,
+        }
+
+        let tests = vec![
+            Test {
+                name: "round-trip smallest possible gif",
+                data: b"GIF89a\x01\0\x01\0\0\0\0;",
+                file_name: "smallest.gif",
+                expected: ContentItem::Image(
+                    ImageContent {
+                        buffer: Box::new(*b"GIF89a\x01\0\x01\0\0\0\0;"),
+                    },
+                    ImageMetadata {
+                        size_bytes: 14,
+                        fingerprint: Fingerprint::of(b"GIF89a\x01\0\x01\0\0\0\0;"),
+                        mime_type: "image/gif".into(),
+                        width_px: 1,
+                        height_px: 1,
+                        exif: None,
+                        phash: None,
+                        dominant_colors: Vec::new(),
+                        blurhash: None,
+                        frame_count: None,
+                        loop_count: None,
+                        original_format: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip text file",
+                data: b"howdy",
+                file_name: "howdy.txt",
+                expected: ContentItem::Text(
+                    TextContent {
+                        string: "howdy".into(),
+                    },
+                    TextMetadata {
+                        size_bytes: 5,
+                        fingerprint: Fingerprint::of(b"howdy"),
+                        detected_encoding: None,
+                        char_count: 5,
+                        word_count: 1,
+                        line_endings: LineEndings::None,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip multi-word text file",
+                data: b"hello world\n",
+                file_name: "hello.txt",
+                expected: ContentItem::Text(
+                    TextContent {
+                        string: "hello world\n".into(),
+                    },
+                    TextMetadata {
+                        size_bytes: 12,
+                        fingerprint: Fingerprint::of(b"hello world\n"),
+                        detected_encoding: None,
+                        char_count: 12,
+                        word_count: 2,
+                        line_endings: LineEndings::Lf,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip empty file",
+                data: b"",
+                file_name: "empty.txt",
+                expected: ContentItem::Text(
+                    TextContent { string: "".into() },
+                    TextMetadata {
+                        size_bytes: 0,
+                        fingerprint: Fingerprint::of(b""),
+                        detected_encoding: None,
+                        char_count: 0,
+                        word_count: 0,
+                        line_endings: LineEndings::None,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip 1-byte file",
+                data: b"a",
+                file_name: "one.txt",
+                expected: ContentItem::Text(
+                    TextContent { string: "a".into() },
+                    TextMetadata {
+                        size_bytes: 1,
+                        fingerprint: Fingerprint::of(b"a"),
+                        detected_encoding: None,
+                        char_count: 1,
+                        word_count: 1,
+                        line_endings: LineEndings::None,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip 3-byte file",
+                data: b"abc",
+                file_name: "three.txt",
+                expected: ContentItem::Text(
+                    TextContent {
+                        string: "abc".into(),
+                    },
+                    TextMetadata {
+                        size_bytes: 3,
+                        fingerprint: Fingerprint::of(b"abc"),
+                        detected_encoding: None,
+                        char_count: 3,
+                        word_count: 1,
+                        line_endings: LineEndings::None,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "transcode windows-1252 text file",
+                data: b"Caf\xe9 cr\xe8me br\xfbl\xe9e\n",
+                file_name: "latin.txt",
+                expected: ContentItem::Text(
+                    TextContent {
+                        string: "Café crème brûlée\n".into(),
+                    },
+                    TextMetadata {
+                        size_bytes: 18,
+                        fingerprint: Fingerprint::of(b"Caf\xe9 cr\xe8me br\xfbl\xe9e\n"),
+                        detected_encoding: Some("windows-1252".into()),
+                        char_count: 18,
+                        word_count: 3,
+                        line_endings: LineEndings::Lf,
+                        line_endings_normalized: false,
+                        compression: Compression::None,
+                        compressed_size_bytes: None,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip unrecognized binary file",
+                data: b"\x00\x9f\x92\x96\xc3\x28\xa0\xa1\xfe\xff",
+                file_name: "random.bin",
+                expected: ContentItem::Binary(
+                    BinaryContent {
+                        buffer: Box::new(*b"\x00\x9f\x92\x96\xc3\x28\xa0\xa1\xfe\xff"),
+                    },
+                    BinaryMetadata {
+                        size_bytes: 10,
+                        fingerprint: Fingerprint::of(b"\x00\x9f\x92\x96\xc3\x28\xa0\xa1\xfe\xff"),
+                        mime_type: "application/octet-stream".into(),
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip tiny mp4",
+                data: include_bytes!("../../tests/fixtures/tiny.mp4"),
+                file_name: "tiny.mp4",
+                expected: ContentItem::Video(
+                    VideoContent {
+                        buffer: Box::new(*include_bytes!("../../tests/fixtures/tiny.mp4")),
+                    },
+                    VideoMetadata {
+                        size_bytes: 654,
+                        fingerprint: Fingerprint::of(include_bytes!(
+                            "../../tests/fixtures/tiny.mp4"
+                        )),
+                        mime_type: "video/mp4".into(),
+                        width_px: 16,
+                        height_px: 16,
+                        duration_ms: 1000,
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip tiny svg",
+                data: include_bytes!("../../tests/fixtures/tiny.svg"),
+                file_name: "tiny.svg",
+                expected: ContentItem::Vector(
+                    VectorContent {
+                        markup: include_str!("../../tests/fixtures/tiny.svg").into(),
+                    },
+                    VectorMetadata {
+                        size_bytes: 215,
+                        fingerprint: Fingerprint::of(include_bytes!(
+                            "../../tests/fixtures/tiny.svg"
+                        )),
+                        mime_type: "image/svg+xml".into(),
+                        width_px: Some(32),
+                        height_px: Some(24), // Not set on the root element, so from the viewBox
+                        view_box: Some("0 0 16 24".into()),
+                    },
+                ),
+            },
+            Test {
+                name: "round-trip short wav",
+                data: include_bytes!("../../tests/fixtures/short.wav"),
+                file_name: "short.wav",
+                expected: ContentItem::Audio(
+                    AudioContent {
+                        buffer: Box::new(*include_bytes!("../../tests/fixtures/short.wav")),
+                    },
+                    AudioMetadata {
+                        size_bytes: 1644,
+                        fingerprint: Fingerprint::of(include_bytes!(
+                            "../../tests/fixtures/short.wav"
+                        )),
+                        mime_type: "audio/x-wav".into(),
+                        duration_ms: 100,
+                        sample_rate_hz: 8000,
+                        channels: 1,
+                    },
+                ),
+            },
+        ];
+
+        for test in tests.into_iter() {
+            let client_ref = client_ref.clone();
+            let path = write_file(dir.path(), test.data, test.file_name)?;
+            let cid = store_file(path, client_ref.clone()).await.unwrap();
+            let actual = load_file(cid.unwrap(), client_ref).await.unwrap();
+
+            assert_eq!(test.expected, actual, "{}", test.name);
+        }
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_fingerprint() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let options = StoreOptions {
+            compress_text: true,
+            ..StoreOptions::default()
+        };
+        let path = write_file(dir.path(), &b"abc".repeat(1000), "abc.txt")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options).await?;
+        let content = load_file(cid.unwrap(), client_ref).await?;
+
+        // Taken over the file as it was, not its compressed form.
+        assert_eq!(
+            "328de8f1895f8bb09f6e6b4c2012ef2b2a6f067cd002794b750aa040a6f6d8bd",
+            content.fingerprint_hex()
+        );
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_content_bytes() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 3).write_to(&mut png, ImageFormat::Png)?;
+        let text = "Caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e\n".repeat(100);
+
+        let files = [
+            (write_file(dir.path(), &png, "small.png")?, png.clone()),
+            (
+                write_file(dir.path(), text.as_bytes(), "dessert.txt")?,
+                text.into_bytes(),
+            ),
+        ];
+
+        for compress_text in [false, true] {
+            let options = StoreOptions {
+                compress_text,
+                ..StoreOptions::default()
+            };
+
+            for (path, bytes) in &files {
+                let cid =
+                    store_file_with_options(path.clone(), client_ref.clone(), options.clone())
+                        .await?;
+                let content = load_file(cid.unwrap(), client_ref.clone()).await?;
+                assert_eq!(*bytes, Vec::try_from(&content)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_image_dimensions_survive_file_removal() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        let cid = store_file(path.clone(), client_ref.clone()).await.unwrap();
+        std::fs::remove_file(&path)?;
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!((1, 1), (metadata.width_px, metadata.height_px));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_reader() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        for bytes in [&b"GIF89a\x01\0\x01\0\0\0\0;"[..], b"howdy", b"\xff\xfe\0\0"] {
+            let path = write_file(dir.path(), bytes, "stored")?;
+            let from_file = store_file(path, client_ref.clone()).await?;
+
+            let reader = futures::io::Cursor::new(bytes);
+            let from_reader = store_reader(reader, Some(bytes.len() as u64), client_ref.clone());
+
+            assert_eq!(from_file, from_reader.await?);
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_version_history() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let mut previous = None;
+        let mut versions = Vec::new();
+        for text in ["draft", "revised draft", "final"] {
+            let path = write_file(dir.path(), text.as_bytes(), "notes.txt")?;
+            let cid = store_version(path, client_ref.clone(), previous).await?;
+            versions.push(cid);
+            previous = Some(cid);
+        }
+
+        versions.reverse();
+        assert_eq!(
+            versions,
+            version_history(versions[0], client_ref.clone()).await?
+        );
+        assert_eq!(
+            versions[2..],
+            version_history(versions[2], client_ref.clone()).await?[..]
+        );
+
+        let missing = versions[0];
+        let other_client = Arc::new(RwLock::new(in_memory_client().await));
+        let path = write_file(dir.path(), b"orphan", "orphan.txt")?;
+        assert!(matches!(
+            store_version(path, other_client, Some(missing)).await,
+            Err(FzrError::NotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_diff_text() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"milk\neggs\nflour\n", "list.txt")?;
+        let old = store_version(path, client_ref.clone(), None).await?;
+        let path = write_file(dir.path(), b"milk\nbutter\nflour\nsugar\n", "list.txt")?;
+        let new = store_version(path, client_ref.clone(), Some(old)).await?;
+
+        assert_eq!(
+            vec![
+                DiffLine::Unchanged("milk".into()),
+                DiffLine::Removed("eggs".into()),
+                DiffLine::Added("butter".into()),
+                DiffLine::Unchanged("flour".into()),
+                DiffLine::Added("sugar".into()),
+            ],
+            diff_text(old, new, client_ref.clone()).await?
+        );
+
+        let path = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        let image = store_file(path, client_ref.clone()).await?.unwrap();
+        assert!(matches!(
+            diff_text(old, image, client_ref).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_logs_fields() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let path = write_file(dir.path(), b"howdy", "howdy.txt")?;
+
+        let events = CapturedEvents::default();
+        let subscriber = tracing_subscriber::registry().with(events.clone());
+        let cid = store_file(path, client_ref)
+            .with_subscriber(subscriber)
+            .await?
+            .unwrap();
+
+        let events = events.0.lock().unwrap();
+        let stored = events
+            .iter()
+            .find(|fields| fields.get("message").map(String::as_str) == Some("Stored content"))
+            .expect("No event logged for the store");
+
+        assert_eq!(Some(&cid.to_string()), stored.get("cid"));
+        assert_eq!(Some(&"5".to_string()), stored.get("size_bytes"));
+        assert_eq!(Some(&"text/plain".to_string()), stored.get("content_type"));
+        assert!(stored.contains_key("elapsed_ms"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "heic")]
+    #[runtime::test]
+    async fn test_store_heic() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let heic = include_bytes!("../../tests/fixtures/tiny.heic");
+        let path = write_file(dir.path(), heic, "tiny.heic")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref).await? {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!(&heic[..], &*content.buffer);
+                assert_eq!("image/heif", metadata.mime_type);
+                assert_eq!((64, 48), (metadata.width_px, metadata.height_px));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "heic")]
+    #[runtime::test]
+    async fn test_store_heic_transcoded() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let heic = include_bytes!("../../tests/fixtures/tiny.heic");
+        let path = write_file(dir.path(), heic, "tiny.heic")?;
+        let options = StoreOptions {
+            transcode_heic: true,
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await?
+            .unwrap();
+
+        let block = client_ref.read().await.get(&cid).await.unwrap();
+        let transcoded_cid = ContentCid::new(block.transcoded_cid.unwrap())?;
+
+        match load_file(transcoded_cid, client_ref).await? {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!(
+                    Some(ImageFormat::Jpeg),
+                    image::guess_format(&content.buffer).ok()
+                );
+                assert_eq!("image/jpeg", metadata.mime_type);
+                assert_eq!((64, 48), (metadata.width_px, metadata.height_px));
+                assert_eq!(Some("image/heif".into()), metadata.original_format);
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "heic"))]
+    #[runtime::test]
+    async fn test_store_heic_unsupported() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let heic = include_bytes!("../../tests/fixtures/tiny.heic");
+        let path = write_file(dir.path(), heic, "tiny.heic")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref).await? {
+            ContentItem::Binary(content, metadata) => {
+                assert_eq!(&heic[..], &*content.buffer);
+                assert_eq!("image/heif", metadata.mime_type);
+            }
+            other => panic!("Expected binary content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_pdf() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let pdf = include_bytes!("../../tests/fixtures/minimal.pdf");
+        let path = write_file(dir.path(), pdf, "minimal.pdf")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref).await? {
+            ContentItem::Document(content, metadata) => {
+                assert_eq!(&pdf[..], &*content.buffer);
+                assert_eq!("application/pdf", metadata.mime_type);
+                assert_eq!(Some(1), metadata.page_count);
+                assert_eq!(Some("Tiny Test".into()), metadata.title);
+                assert_eq!(Some("Fuzzr".into()), metadata.author);
+            }
+            other => panic!("Expected document content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_font() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let fonts: [(&[u8], &str, FontFormat, &str); 3] = [
+            (
+                include_bytes!("../../tests/fixtures/tiny.ttf"),
+                "tiny.ttf",
+                FontFormat::TrueType,
+                "font/ttf",
+            ),
+            (
+                include_bytes!("../../tests/fixtures/tiny.woff"),
+                "tiny.woff",
+                FontFormat::Woff,
+                "font/woff",
+            ),
+            (
+                include_bytes!("../../tests/fixtures/tiny.woff2"),
+                "tiny.woff2",
+                FontFormat::Woff2,
+                "font/woff2",
+            ),
+        ];
+
+        for (font, file_name, format, mime_type) in fonts {
+            let path = write_file(dir.path(), font, file_name)?;
+            let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+            match load_file(cid, client_ref.clone()).await? {
+                ContentItem::Font(content, metadata) => {
+                    assert_eq!(font, &*content.buffer);
+                    assert_eq!(mime_type, metadata.mime_type);
+                    assert_eq!(format, metadata.format);
+                    assert_eq!(Some("Fzr Tiny".into()), metadata.family_name);
+                    // The US English Windows name wins over the Mac and German ones.
+                    assert_eq!(Some("Bold Italic".into()), metadata.style);
+                }
+                other => panic!("Expected font content, got {:?}", other),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_animated_gif_frames() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let animated = include_bytes!("../../tests/fixtures/animated.gif");
+        let path = write_file(dir.path(), animated, "animated.gif")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref.clone()).await? {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!(&animated[..], &*content.buffer);
+                assert_eq!(Some(3), metadata.frame_count);
+                assert_eq!(Some(0), metadata.loop_count);
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        let path = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "still.gif")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref).await? {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!((None, None), (metadata.frame_count, metadata.loop_count));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_pin_unpin() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"keep me", "pinned.txt")?;
+        let cid = store_file_pinned(path, client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let client = client_ref.read().await;
+        assert!(client.is_pinned(&cid).await.unwrap());
+
+        client.unpin(&cid).await.unwrap();
+        assert!(!client.is_pinned(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_gc() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"first draft", "kept.txt")?;
+        let draft = store_version(path, client_ref.clone(), None).await?;
+        let path = write_file(dir.path(), b"second draft", "kept.txt")?;
+        let kept = store_version(path, client_ref.clone(), Some(draft)).await?;
+
+        let path = write_file(dir.path(), b"throw me away", "dropped.txt")?;
+        let dropped = store_file(path, client_ref.clone()).await?.unwrap();
+
+        let client = client_ref.read().await;
+        client.pin(&kept).await.unwrap();
+        let dropped_size = client.stat(&dropped).await.unwrap().size_bytes;
+
+        let report = client.gc().await.unwrap();
+        assert_eq!(1, report.blocks_removed);
+        assert_eq!(dropped_size, report.bytes_freed);
+
+        assert!(!client.has(&dropped).await.unwrap());
+        assert!(client.has(&kept).await.unwrap());
+        // Still reachable from the pinned version, so not collected either.
+        assert!(client.has(&draft).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_in_memory_client() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"ephemeral", "ephemeral.txt")?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+        assert!(client_ref.read().await.has(&cid).await.unwrap());
+        drop(client_ref);
+
+        assert!(!in_memory_client().await.has(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_publish_resolve_ipns() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let first = write_file(dir.path(), b"profile v1", "v1.txt")?;
+        let second = write_file(dir.path(), b"profile v2", "v2.txt")?;
+        let first_cid = store_file(first, client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let second_cid = store_file(second, client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let client = client_ref.read().await;
+        let name = client.publish_ipns(&first_cid, "profile").await.unwrap();
+        assert_eq!(first_cid, client.resolve_ipns(&name).await.unwrap());
+
+        // Republishing moves the name to the new content.
+        let name = client.publish_ipns(&second_cid, "profile").await.unwrap();
+        assert_eq!(second_cid, client.resolve_ipns(&name).await.unwrap());
+
+        assert!(client.resolve_ipns("unpublished").await.is_err());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_list_blocks() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let mut cids = Vec::new();
+        for (data, file_name) in &[
+            (&b"listed one"[..], "one.txt"),
+            (b"listed two", "two.txt"),
+            (b"listed three", "three.txt"),
+        ] {
+            let path = write_file(dir.path(), data, file_name)?;
+            cids.push(store_file(path, client_ref.clone()).await.unwrap().unwrap());
+        }
+
+        let listed = client_ref.read().await.list_blocks().await.unwrap();
+        for cid in &cids {
+            assert!(listed.contains(cid));
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_stat() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let gif = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "stat.gif")?;
+        let cid = store_file(gif.clone(), client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let client = client_ref.read().await;
+        let encoded = DagCborCodec.encode(&client.get(&cid).await.unwrap())?;
+        let stat = client.stat(&cid).await.unwrap();
+        assert_eq!(encoded.len() as u64, stat.size_bytes);
+        assert_eq!(0, stat.num_links);
+        assert_eq!(u64::from(DagCborCodec), stat.codec);
+        drop(client);
+
+        let chunked_cid = store_file_streaming(gif, client_ref.clone(), 4, None)
+            .await
+            .unwrap();
+        let stat = client_ref.read().await.stat(&chunked_cid).await.unwrap();
+        assert_eq!(4, stat.num_links);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_remove_block() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"remove me", "removed.txt")?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+        let path = write_file(dir.path(), b"leave me be", "kept.txt")?;
+        let kept_cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+
+        let client = client_ref.read().await;
+        assert!(client.remove(&cid).await.unwrap());
+        assert!(!client.has(&cid).await.unwrap());
+        assert!(!client.remove(&cid).await.unwrap());
+        assert!(client.has(&kept_cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_remove_pinned_block() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"keep me around", "kept.txt")?;
+        let cid = store_file_pinned(path, client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let client = client_ref.read().await;
+        assert!(client.remove(&cid).await.is_err());
+        assert!(client.has(&cid).await.unwrap());
+
+        client.unpin(&cid).await.unwrap();
+        assert!(client.remove(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_skip_existing() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let options = StoreOptions {
+            skip_existing: true,
+            ..StoreOptions::default()
+        };
+
+        let path = write_file(dir.path(), b"stored once", "once.txt")?;
+        let block = read_block(&path, None, None).await.unwrap();
+        let expected_cid = client_ref.read().await.compute_cid(&block).unwrap();
+        assert!(!client_ref.read().await.has(&expected_cid).await.unwrap());
+
+        let first = store_file_with_options(path.clone(), client_ref.clone(), options.clone())
+            .await
+            .unwrap();
+        assert_eq!(Some(expected_cid), first.map(Cid::from));
+        assert!(client_ref.read().await.has(&expected_cid).await.unwrap());
+
+        let second = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_fingerprint_cache() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let cache = FingerprintCache::new();
+        let options = StoreOptions {
+            fingerprint_cache: Some(cache.clone()),
+            ..StoreOptions::default()
+        };
+
+        let path = write_file(dir.path(), b"stored twice", "twice.txt")?;
+        let first =
+            store_file_with_options(path.clone(), client_ref.clone(), options.clone()).await?;
+        assert_eq!(0, cache.hits());
+
+        let second =
+            store_file_with_options(path.clone(), client_ref.clone(), options.clone()).await?;
+        assert_eq!(first, second);
+        assert_eq!(1, cache.hits());
+
+        // Storing the same bytes from memory hits the same entry.
+        let bytes = b"stored twice".to_vec();
+        let from_bytes = store_bytes_with_options(
+            bytes,
+            Some("twice.txt"),
+            client_ref.clone(),
+            options.clone(),
+        )
+        .await?;
+        assert_eq!(first, from_bytes);
+        assert_eq!(2, cache.hits());
+
+        // Metadata changes the block, so it's a different entry.
+        let with_metadata = StoreOptions {
+            metadata: vec![tag("draft")],
+            ..options.clone()
+        };
+        let tagged =
+            store_file_with_options(path.clone(), client_ref.clone(), with_metadata).await?;
+        assert_ne!(first, tagged);
+        assert_eq!(2, cache.hits());
+
+        fs::write(&path, b"changed since").await?;
+        let changed = store_file_with_options(path, client_ref.clone(), options).await?;
+        assert_ne!(first, changed);
+        assert_eq!(2, cache.hits());
+
+        match load_file(changed.unwrap(), client_ref).await? {
+            ContentItem::Text(content, _) => assert_eq!("changed since", content.string),
+            other => panic!("Expected text, found {}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_during_store() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let small_path = write_file(dir.path(), b"small", "small.txt")?;
+        let small_cid = store_file(small_path, client_ref.clone()).await?.unwrap();
+        let busy_path = write_file(dir.path(), b"busy", "busy.txt")?;
+
+        // The store stops once it's added its block, and waits there until the load is done.
+        let barrier = Arc::new(Barrier::new(2));
+        let store_barrier = barrier.clone();
+        let options = StoreOptions {
+            progress: Some(ProgressCallback::new(move |_, _| {
+                store_barrier.wait();
+                store_barrier.wait();
+            })),
+            ..StoreOptions::default()
+        };
+
+        let store_client = client_ref.clone();
+        let store = std::thread::spawn(move || {
+            runtime::block_on(store_file_with_options(busy_path, store_client, options))
+        });
+        barrier.wait();
+
+        let load = runtime::timeout(
+            Duration::from_secs(10),
+            load_file(small_cid, client_ref.clone()),
+        );
+        let content = load.await.expect("Load waited for the store to finish")?;
+        assert_eq!(&Fingerprint::of(b"small"), content.fingerprint());
+        barrier.wait();
+
+        let busy_cid = store.join().unwrap()?.unwrap();
+        assert!(client_ref.read().await.has(&busy_cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_hash_functions() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = write_file(dir.path(), b"hashed two ways", "hashed.txt")?;
+
+        let mut cids = Vec::new();
+        for hash in [MultihashCode::Blake3_256, MultihashCode::Sha2_256] {
+            let config = IpfsClientConfig {
+                in_memory: true,
+                hash,
+                ..IpfsClientConfig::default()
+            };
+            let client_ref = Arc::new(RwLock::new(IpfsClient::with_config(config).await.unwrap()));
+
+            let cid = store_file(path.clone(), client_ref.clone()).await?.unwrap();
+            assert_eq!(u64::from(Code::from(hash)), cid.hash().code());
+
+            match load_file(cid, client_ref).await? {
+                ContentItem::Text(content, _) => assert_eq!("hashed two ways", content.string),
+                other => panic!("Expected text content, got {:?}", other),
+            }
+            cids.push(cid);
+        }
+
+        assert_ne!(cids[0], cids[1]);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_dry_run() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let options = StoreOptions {
+            dry_run: true,
+            ..StoreOptions::default()
+        };
+
+        let path = write_file(dir.path(), b"planned", "planned.txt")?;
+        let planned = store_file_with_options(path.clone(), client_ref.clone(), options).await?;
+        assert!(client_ref
+            .read()
+            .await
+            .list_blocks()
+            .await
+            .unwrap()
+            .is_empty());
+
+        let stored = store_file(path, client_ref.clone()).await?;
+        assert_eq!(planned, stored);
+        assert_eq!(
+            vec![Cid::from(stored.unwrap())],
+            client_ref.read().await.list_blocks().await.unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_verify() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"verified", "verified.txt")?;
+        let options = StoreOptions {
+            verify: true,
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Simulate a corrupted read by comparing against a block with different content.
+        let client = client_ref.read().await;
+        let stored = client.get(&cid).await.unwrap();
+        let mut corrupted = stored.clone();
+        if let ContentItem::Text(content, _) = &mut corrupted.content {
+            content.string = "verifiet".into();
+        }
+
+        assert!(verify_readback(&cid, &stored, &stored).is_ok());
+        assert!(matches!(
+            verify_readback(&cid, &stored, &corrupted),
+            Err(FzrError::VerificationFailed(failed)) if failed == cid
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_verify_corrupted() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let repo = tempdir()?;
+        let config = IpfsClientConfig {
+            repo_path: Some(repo.path().to_path_buf()),
+            ..IpfsClientConfig::default()
+        };
+        let client_ref = Arc::new(RwLock::new(IpfsClient::with_config(config).await.unwrap()));
+
+        // Have the store swap in a different block whenever one is written, as a failing disk
+        // might, so reading it back returns something other than what was added.
+        let corrupted = DagCborCodec.encode(&classify_block(b"verifiet", "test", None)?)?;
+        let hex: String = corrupted
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        rusqlite::Connection::open(repo.path().join("sqlite"))?.execute_batch(&format!(
+            "CREATE TRIGGER corrupt AFTER INSERT ON blocks BEGIN \
+                UPDATE blocks SET block = X'{}' WHERE block_id = NEW.block_id; \
+            END",
+            hex
+        ))?;
+
+        let path = write_file(dir.path(), b"verified", "verified.txt")?;
+        let expected = client_ref
+            .read()
+            .await
+            .compute_cid(&classify_block(b"verified", "test", None)?)
+            .unwrap();
+        let options = StoreOptions {
+            verify: true,
+            ..StoreOptions::default()
+        };
+
+        assert!(matches!(
+            store_file_with_options(path, client_ref, options).await,
+            Err(FzrError::VerificationFailed(failed)) if failed == expected
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_load_metadata() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let metadata = vec![MetadataItem {
+            parent: None,
+            value: "alice".into(),
+            category: MetadataCategory::Originator,
+        }];
+        let options = StoreOptions {
+            metadata: metadata.clone(),
+            ..StoreOptions::default()
+        };
+
+        let path = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+        let (content, loaded_metadata) = load_file_with_metadata(cid.unwrap(), client_ref)
+            .await
+            .unwrap();
+
+        assert!(matches!(content, ContentItem::Image(_, _)));
+        assert_eq!(metadata, loaded_metadata);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_created_at() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let path = write_file(dir.path(), b"dated", "dated.txt")?;
+
+        let options = StoreOptions {
+            created_at: Some(CreatedAt::At(1_600_000_000_000)),
+            ..StoreOptions::default()
+        };
+        let dated = store_file_with_options(path.clone(), client_ref.clone(), options).await?;
+        let block = client_ref.read().await.get(&dated.unwrap()).await.unwrap();
+        assert_eq!(Some(1_600_000_000_000), block.created_at);
+
+        let options = StoreOptions {
+            created_at: Some(CreatedAt::Modified),
+            ..StoreOptions::default()
+        };
+        let modified = store_file_with_options(path.clone(), client_ref.clone(), options).await?;
+        let block = client_ref
+            .read()
+            .await
+            .get(&modified.unwrap())
+            .await
+            .unwrap();
+        let mtime = unix_millis(std::fs::metadata(&path)?.modified()?);
+        assert_eq!(mtime, block.created_at);
+
+        // Without the option nothing is recorded, so the CID depends on the content alone.
+        let undated = store_file(path, client_ref.clone()).await?.unwrap();
+        assert_eq!(
+            None,
+            client_ref
+                .read()
+                .await
+                .get(&undated)
+                .await
+                .unwrap()
+                .created_at
+        );
+        assert_ne!(dated.unwrap(), undated);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_tags() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let originator = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Originator)
+            .build()?;
+        let options = StoreOptions {
+            metadata: vec![tag("sunset"), originator, tag("beach"), tag("sunset")],
+            ..StoreOptions::default()
+        };
+
+        let path = write_file(dir.path(), b"a day at the beach", "caption.txt")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options).await?;
+        let (_, metadata) = load_file_with_metadata(cid.unwrap(), client_ref).await?;
+
+        assert_eq!(vec!["sunset", "beach"], tags_of(&metadata));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_metadata() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(64, 48).write_to(&mut png, ImageFormat::Png)?;
+        let path = write_file(dir.path(), &png, "grid.png")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_metadata(cid, client_ref.clone()).await? {
+            ContentMetadata::Image(metadata) => {
+                assert_eq!((64, 48), (metadata.width_px, metadata.height_px));
+                assert_eq!(png.len() as u64, metadata.size_bytes);
+            }
+            other => panic!("Expected image metadata, got {:?}", other),
+        }
+
+        // Content that can't be decompressed still has readable metadata, since it's never read.
+        let metadata = TextMetadata {
+            size_bytes: 1000,
+            fingerprint: Fingerprint::of(b"unused"),
+            detected_encoding: None,
+            char_count: 1000,
+            word_count: 200,
+            line_endings: LineEndings::None,
+            line_endings_normalized: false,
+            compression: Compression::Zstd,
+            compressed_size_bytes: Some(8),
+        };
+        let block = ContentItemBlock {
+            content: ContentItem::CompressedText(
+                CompressedTextContent {
+                    buffer: b"not zstd".to_vec().into_boxed_slice(),
+                },
+                metadata.clone(),
+            ),
+            size_bytes: 1000,
+            metadata: Vec::new(),
+            metadata_cid: None,
+            previous_version: None,
+            transcoded_cid: None,
+            thumbnail_cid: None,
+            created_at: None,
+        };
+        let cid = ContentCid::new(client_ref.write().await.add(&block).await.unwrap())?;
+
+        assert!(load_file(cid, client_ref.clone()).await.is_err());
+        assert_eq!(
+            ContentMetadata::Text(metadata),
+            load_metadata(cid, client_ref).await?
+        );
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_linked_metadata() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let originator = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Originator)
+            .build()?;
+        let metadata_cid = store_metadata(vec![originator.clone()], client_ref.clone()).await?;
+        let options = StoreOptions {
+            metadata_cid: Some(metadata_cid),
+            ..StoreOptions::default()
+        };
+
+        let mut cids = Vec::new();
+        for (name, text) in [("first.txt", "first"), ("second.txt", "second")] {
+            let path = write_file(dir.path(), text.as_bytes(), name)?;
+            let cid = store_file_with_options(path, client_ref.clone(), options.clone()).await?;
+            cids.push(cid.unwrap());
+        }
+
+        for cid in &cids {
+            let block = client_ref.read().await.get(cid).await.unwrap();
+            assert_eq!(Some(metadata_cid), block.metadata_cid);
+            assert!(block.metadata.is_empty());
+
+            let options = LoadOptions {
+                resolve_metadata: true,
+                ..LoadOptions::default()
+            };
+            let (_, metadata) = load_file_with_options(*cid, client_ref.clone(), options).await?;
+            assert_eq!(vec![originator.clone()], metadata);
+        }
+
+        // Unless asked to, loading leaves linked metadata unresolved.
+        let (_, metadata) = load_file_with_metadata(cids[0], client_ref).await?;
+        assert!(metadata.is_empty());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_image_with_thumbnail() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(100, 50).write_to(&mut png, ImageFormat::Png)?;
+        let path = write_file(dir.path(), &png, "wide.png")?;
+
+        let (cid, thumbnail_cid) = store_image_with_thumbnail(path, client_ref.clone(), 10)
+            .await
+            .unwrap();
+
+        let block = client_ref.read().await.get(&cid).await.unwrap();
+        assert_eq!(Some(*thumbnail_cid), block.thumbnail_cid);
+
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!((100, 50), (metadata.width_px, metadata.height_px));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        match load_file(thumbnail_cid, client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!((10, 5), (metadata.width_px, metadata.height_px));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "webp")]
+    #[runtime::test]
+    async fn test_store_webp_thumbnail() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        // Smooth gradients under sensor-like noise compress about as badly as a real photo.
+        let mut noise = 0x2545_f491_u32;
+        let photo = RgbImage::from_fn(400, 300, |x, y| {
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            let grain = noise % 24;
+            Rgb([
+                ((x * 255 / 400 + grain) % 256) as u8,
+                ((y * 255 / 300 + grain) % 256) as u8,
+                ((128 + grain * 2) % 256) as u8,
+            ])
+        });
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(photo).write_to(&mut jpeg, ImageFormat::Jpeg)?;
+        let path = write_file(dir.path(), &jpeg, "photo.jpg")?;
+
+        let mut sizes = Vec::new();
+        for (format, mime_type) in [
+            (ThumbnailFormat::WebP, "image/webp"),
+            (ThumbnailFormat::Png, "image/png"),
+        ] {
+            let (_, thumbnail_cid) =
+                store_image_with_thumbnail_as(path.clone(), client_ref.clone(), 200, format)
+                    .await?;
+
+            match load_file(thumbnail_cid, client_ref.clone()).await? {
+                ContentItem::Image(_, metadata) => {
+                    assert_eq!(mime_type, metadata.mime_type);
+                    assert_eq!((200, 150), (metadata.width_px, metadata.height_px));
+                    sizes.push(metadata.size_bytes);
+                }
+                other => panic!("Expected image content, got {:?}", other),
+            }
+        }
+
+        assert!(
+            sizes[0] < sizes[1],
+            "WebP {} vs PNG {} bytes",
+            sizes[0],
+            sizes[1]
+        );
+        assert_eq!(ThumbnailFormat::WebP, ThumbnailFormat::default());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_image_as() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let mut gif = Vec::new();
+        DynamicImage::new_rgb8(4, 3).write_to(&mut gif, ImageFormat::Gif)?;
+        let path = write_file(dir.path(), &gif, "small.gif")?;
+
+        let cid = store_image_as(path, client_ref.clone(), ImageFormat::Png)
+            .await
+            .unwrap();
+
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!("image/png", metadata.mime_type);
+                assert_eq!((4, 3), (metadata.width_px, metadata.height_px));
+                assert_eq!(content.buffer.len() as u64, metadata.size_bytes);
+                assert!(matches!(
+                    image::guess_format(&content.buffer),
+                    Ok(ImageFormat::Png)
+                ));
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        let path = write_file(dir.path(), b"howdy", "howdy.txt")?;
+        assert!(matches!(
+            store_image_as(path, client_ref, ImageFormat::Png).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_jpeg_exif() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(
+            dir.path(),
+            include_bytes!("../../tests/fixtures/exif.jpg"),
+            "exif.jpg",
+        )?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap();
+
+        let exif = match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => metadata.exif.unwrap(),
+            other => panic!("Expected image content, got {:?}", other),
+        };
+
+        assert_eq!(Some("FuzzrCam".into()), exif.camera_make);
+        assert_eq!(Some("One".into()), exif.camera_model);
+        assert_eq!(Some("2021:03:04 05:06:07".into()), exif.datetime);
+        assert_eq!(Some(6), exif.orientation);
+        assert_eq!(Some("1/125".into()), exif.exposure_time);
+        assert!((exif.gps_latitude.unwrap() - 37.775).abs() < 1e-6);
+        assert!((exif.gps_longitude.unwrap() + 122.42).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_strip_exif() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let original = include_bytes!("../../tests/fixtures/exif.jpg");
+        assert!(exif_ops::read_exif(original)
+            .unwrap()
+            .gps_latitude
+            .is_some());
+
+        let path = write_file(dir.path(), original, "exif.jpg")?;
+        let options = StoreOptions {
+            strip_exif: true,
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(content, metadata) => {
+                assert_eq!(None, metadata.exif);
+                assert!(exif_ops::read_exif(&content.buffer).is_none());
+                assert!(content.buffer.len() < original.len());
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_json() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let json = r#"{"name": "alice", "tags": ["a", "b"], "age": 30}"#;
+        let path = write_file(dir.path(), json.as_bytes(), "profile.json")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref.clone()).await? {
+            ContentItem::Json(content, metadata) => {
+                assert_eq!(json, content.string);
+                assert_eq!(JsonKind::Object, metadata.kind);
+                assert_eq!(3, metadata.top_level_count);
+            }
+            other => panic!("Expected JSON content, got {:?}", other),
+        }
+
+        let path = write_file(dir.path(), b"{\"unterminated\": ", "broken.json")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+        assert!(matches!(
+            load_file(cid, client_ref).await?,
+            ContentItem::Text(..)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_markdown() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let markdown = "# Title\n\nIntro.\n\n```\n# not a heading\n```\n\n## Section ##\n\nBody.\n";
+        let path = write_file(dir.path(), markdown.as_bytes(), "README.md")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref.clone()).await? {
+            ContentItem::Markdown(content, metadata) => {
+                assert_eq!(markdown, content.string);
+                assert_eq!(
+                    vec![
+                        Heading {
+                            level: 1,
+                            text: "Title".into()
+                        },
+                        Heading {
+                            level: 2,
+                            text: "Section".into()
+                        },
+                    ],
+                    metadata.outline
+                );
+            }
+            other => panic!("Expected Markdown content, got {:?}", other),
+        }
+
+        // The same text under another extension is just text.
+        let path = write_file(dir.path(), markdown.as_bytes(), "README.txt")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+        assert!(matches!(
+            load_file(cid, client_ref).await?,
+            ContentItem::Text(..)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_csv() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let csv = "name,born\nAda,1815\n\"Turing, Alan\",1912\n";
+        let path = write_file(dir.path(), csv.as_bytes(), "people.csv")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref.clone()).await? {
+            ContentItem::Csv(content, metadata) => {
+                assert_eq!(csv, content.string);
+                assert_eq!(",", metadata.delimiter);
+                assert_eq!(3, metadata.row_count);
+                assert_eq!(2, metadata.column_count);
+                assert!(metadata.has_header);
+            }
+            other => panic!("Expected CSV content, got {:?}", other),
+        }
+
+        // Evenly delimited columns, three of them, are a table whatever the file is called.
+        let path = write_file(dir.path(), b"1;2;3\n4;5;6\n", "numbers.txt")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+
+        match load_file(cid, client_ref.clone()).await? {
+            ContentItem::Csv(_, metadata) => {
+                assert_eq!(";", metadata.delimiter);
+                assert_eq!((2, 3), (metadata.row_count, metadata.column_count));
+                assert!(!metadata.has_header);
+            }
+            other => panic!("Expected CSV content, got {:?}", other),
+        }
+
+        // A comma on every line isn't enough when nothing else says it's a table.
+        let letter = b"Dear Bob,\nThanks for the photos, they came out great.\nBest, Alice\n";
+        let path = write_file(dir.path(), letter, "letter.txt")?;
+        let cid = store_file(path, client_ref.clone()).await?.unwrap();
+        assert!(matches!(
+            load_file(cid, client_ref).await?,
+            ContentItem::Text(..)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_bytes() -> Result<(), Box<dyn Error>> {
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let markdown = b"Notes\n=====\n\n- one\n- two\n";
+
+        let cid = store_bytes(markdown.to_vec(), Some("notes.md"), client_ref.clone()).await?;
+        match load_file(cid.unwrap(), client_ref.clone()).await? {
+            ContentItem::Markdown(content, metadata) => {
+                assert_eq!(&markdown[..], content.string.as_bytes());
+                assert_eq!(1, metadata.outline.len());
+            }
+            other => panic!("Expected Markdown content, got {:?}", other),
+        }
+
+        for name_hint in [None, Some("notes.txt")] {
+            let cid = store_bytes(markdown.to_vec(), name_hint, client_ref.clone()).await?;
+            assert!(matches!(
+                load_file(cid.unwrap(), client_ref.clone()).await?,
+                ContentItem::Text(..)
+            ));
+        }
+
+        // A single line is too little to sniff as a table, but the name says it's one.
+        let header = b"name,born\n";
+        let cid = store_bytes(header.to_vec(), Some("empty.csv"), client_ref.clone()).await?;
+        match load_file(cid.unwrap(), client_ref.clone()).await? {
+            ContentItem::Csv(_, metadata) => {
+                assert_eq!((1, 2), (metadata.row_count, metadata.column_count));
+            }
+            other => panic!("Expected CSV content, got {:?}", other),
+        }
+
+        let cid = store_bytes(header.to_vec(), None, client_ref.clone()).await?;
+        assert!(matches!(
+            load_file(cid.unwrap(), client_ref.clone()).await?,
+            ContentItem::Text(..)
+        ));
+
+        // A magic number outranks the name.
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 3).write_to(&mut png, ImageFormat::Png)?;
+        let cid = store_bytes(png, Some("misnamed.md"), client_ref.clone()).await?;
+        assert!(matches!(
+            load_file(cid.unwrap(), client_ref).await?,
+            ContentItem::Image(..)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_content_type_override() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let path = write_file(dir.path(), b"key = value", "custom.foo")?;
+        let options = StoreOptions {
+            content_type_override: Some("application/x-foo".into()),
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path.clone(), client_ref.clone(), options).await?;
+
+        match load_file(cid.unwrap(), client_ref.clone()).await? {
+            ContentItem::Binary(content, metadata) => {
+                assert_eq!(b"key = value", &*content.buffer);
+                assert_eq!("application/x-foo", metadata.mime_type);
+            }
+            other => panic!("Expected binary content, got {:?}", other),
+        }
+
+        // Without the override the same file is sniffed as text.
+        let cid = store_file(path, client_ref.clone()).await?;
+        assert!(matches!(
+            load_file(cid.unwrap(), client_ref.clone()).await?,
+            ContentItem::Text(..)
+        ));
+
+        let path = write_file(dir.path(), b"\0\x9f\x92\x96", "random.bin")?;
+        let options = StoreOptions {
+            content_type_override: Some("text/plain".into()),
+            ..StoreOptions::default()
+        };
+        assert!(matches!(
+            store_file_with_options(path, client_ref, options).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_normalize_line_endings() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let options = StoreOptions {
+            normalize_line_endings: true,
+            ..StoreOptions::default()
+        };
+
+        let crlf_path = write_file(dir.path(), b"one\r\ntwo\r\n", "crlf.txt")?;
+        let lf_path = write_file(dir.path(), b"one\ntwo\n", "lf.txt")?;
+
+        let crlf_cid = store_file_with_options(crlf_path, client_ref.clone(), options.clone())
+            .await
+            .unwrap();
+        let lf_cid = store_file_with_options(lf_path.clone(), client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(crlf_cid.unwrap(), client_ref.clone())
+            .await
+            .unwrap()
+        {
+            ContentItem::Text(content, metadata) => {
+                assert_eq!("one\ntwo\n", content.string);
+                assert_eq!(8, metadata.size_bytes);
+                assert_eq!(Fingerprint::of(b"one\r\ntwo\r\n"), metadata.fingerprint);
+                assert_eq!(LineEndings::Crlf, metadata.line_endings);
+                assert!(metadata.line_endings_normalized);
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        // Text that already uses LF is stored exactly as it would be without the option.
+        let plain_cid = store_file(lf_path, client_ref.clone()).await.unwrap();
+        assert_eq!(plain_cid, lf_cid);
+
+        match load_file(lf_cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Text(content, metadata) => {
+                assert_eq!("one\ntwo\n", content.string);
+                assert_eq!(LineEndings::Lf, metadata.line_endings);
+                assert!(!metadata.line_endings_normalized);
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_compress_text() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let options = StoreOptions {
+            compress_text: true,
+            ..StoreOptions::default()
+        };
+
+        let text = "all work and no play makes fzr a dull crate\n".repeat(100);
+        let path = write_file(dir.path(), text.as_bytes(), "dull.txt")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let stat = client_ref.read().await.stat(&cid).await.unwrap();
+        assert!(stat.size_bytes < text.len() as u64 / 10);
+
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Text(content, metadata) => {
+                assert_eq!(text, content.string);
+                assert_eq!(text.len() as u64, metadata.size_bytes);
+                assert_eq!(Compression::Zstd, metadata.compression);
+                assert!(metadata.compressed_size_bytes.unwrap() < metadata.size_bytes);
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        // Tiny texts aren't worth compressing.
+        let path = write_file(dir.path(), b"howdy", "tiny.txt")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap()
+            .unwrap();
+
+        match load_file(cid, client_ref).await.unwrap() {
+            ContentItem::Text(_, metadata) => {
+                assert_eq!(Compression::None, metadata.compression);
+                assert_eq!(None, metadata.compressed_size_bytes);
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_normalize_and_compress_json() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let options = StoreOptions {
+            normalize_line_endings: true,
+            compress_text: true,
+            ..StoreOptions::default()
+        };
+
+        let json = format!(
+            "[\r\n{}  0\r\n]\r\n",
+            "  \"all work and no play\",\r\n".repeat(200)
+        );
+        let path = write_file(dir.path(), json.as_bytes(), "dull.json")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let stat = client_ref.read().await.stat(&cid).await.unwrap();
+        assert!(stat.size_bytes < json.len() as u64 / 10);
+
+        let normalized = json.replace("\r\n", "\n");
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Json(content, metadata) => {
+                assert_eq!(normalized, content.string);
+                assert_eq!(normalized.len() as u64, metadata.size_bytes);
+                assert_eq!(Fingerprint::of(json.as_bytes()), metadata.fingerprint);
+                assert_eq!(JsonKind::Array, metadata.kind);
+                assert_eq!(201, metadata.top_level_count);
+                assert_eq!(LineEndings::Crlf, metadata.line_endings);
+                assert!(metadata.line_endings_normalized);
+                assert_eq!(Compression::Zstd, metadata.compression);
+            }
+            other => panic!("Expected JSON content, got {:?}", other),
+        }
+
+        assert!(matches!(
+            load_metadata(cid, client_ref).await.unwrap(),
+            ContentMetadata::Json(_)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_normalize_and_compress_markdown() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let options = StoreOptions {
+            normalize_line_endings: true,
+            compress_text: true,
+            ..StoreOptions::default()
+        };
+
+        let markdown = format!(
+            "# Dull\r\n\r\n{}\r\nDuller\r\n------\r\n",
+            "All work and no play.\r\n".repeat(200)
+        );
+        let path = write_file(dir.path(), markdown.as_bytes(), "dull.md")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let stat = client_ref.read().await.stat(&cid).await.unwrap();
+        assert!(stat.size_bytes < markdown.len() as u64 / 10);
+
+        let normalized = markdown.replace("\r\n", "\n");
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Markdown(content, metadata) => {
+                assert_eq!(normalized, content.string);
+                assert_eq!(normalized.len() as u64, metadata.size_bytes);
+                assert_eq!(Fingerprint::of(markdown.as_bytes()), metadata.fingerprint);
+                assert_eq!(
+                    vec![
+                        Heading {
+                            level: 1,
+                            text: "Dull".into()
+                        },
+                        Heading {
+                            level: 2,
+                            text: "Duller".into()
+                        },
+                    ],
+                    metadata.outline
+                );
+                assert_eq!(LineEndings::Crlf, metadata.line_endings);
+                assert!(metadata.line_endings_normalized);
+                assert_eq!(Compression::Zstd, metadata.compression);
+            }
+            other => panic!("Expected Markdown content, got {:?}", other),
+        }
+
+        assert!(matches!(
+            load_metadata(cid, client_ref).await.unwrap(),
+            ContentMetadata::Markdown(_)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_normalize_and_compress_csv() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let options = StoreOptions {
+            normalize_line_endings: true,
+            compress_text: true,
+            ..StoreOptions::default()
+        };
+
+        let csv = format!(
+            "name,quote,count\r\n{}",
+            "jack,\"all work\r\nand no play\",1\r\n".repeat(200)
+        );
+        let path = write_file(dir.path(), csv.as_bytes(), "dull.csv")?;
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let stat = client_ref.read().await.stat(&cid).await.unwrap();
+        assert!(stat.size_bytes < csv.len() as u64 / 10);
+
+        let normalized = csv.replace("\r\n", "\n");
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Csv(content, metadata) => {
+                assert_eq!(normalized, content.string);
+                assert_eq!(normalized.len() as u64, metadata.size_bytes);
+                assert_eq!(Fingerprint::of(csv.as_bytes()), metadata.fingerprint);
+                assert_eq!(",", metadata.delimiter);
+                assert_eq!(201, metadata.row_count);
+                assert_eq!(3, metadata.column_count);
+                assert!(metadata.has_header);
+                assert_eq!(LineEndings::Crlf, metadata.line_endings);
+                assert!(metadata.line_endings_normalized);
+                assert_eq!(Compression::Zstd, metadata.compression);
+            }
+            other => panic!("Expected CSV content, got {:?}", other),
+        }
+
+        assert!(matches!(
+            load_metadata(cid, client_ref).await.unwrap(),
+            ContentMetadata::Csv(_)
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_load_encrypted() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+        let key = [42; KEY_LEN];
+
+        let path = write_file(dir.path(), b"the password is swordfish", "secret.txt")?;
+        let cid = store_file_encrypted(path, client_ref.clone(), &key)
+            .await
+            .unwrap();
+
+        // The stored block is opaque without the key.
+        let encrypted = client_ref.read().await.get_encrypted(&cid).await.unwrap();
+        assert!(!encrypted
+            .ciphertext
+            .windows(9)
+            .any(|window| window == b"swordfish"));
+
+        match load_file_encrypted(cid, client_ref, &key).await.unwrap() {
+            ContentItem::Text(content, _) => {
+                assert_eq!("the password is swordfish", content.string)
+            }
+            other => panic!("Expected text content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_encrypted_wrong_key() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"the password is swordfish", "secret.txt")?;
+        let cid = store_file_encrypted(path, client_ref.clone(), &[42; KEY_LEN])
+            .await
+            .unwrap();
+
+        match load_file_encrypted(cid, client_ref, &[43; KEY_LEN]).await {
+            Err(FzrError::DecryptionFailed(failed)) => assert_eq!(cid, failed),
+            other => panic!("Expected a decryption failure, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_auto_orient() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        // The fixture is 8x4 pixels with orientation 6 (rotate 90°).
+        let path = write_file(
+            dir.path(),
+            include_bytes!("../../tests/fixtures/exif.jpg"),
+            "exif.jpg",
+        )?;
+        let options = StoreOptions {
+            auto_orient: true,
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!((4, 8), (metadata.width_px, metadata.height_px));
+                assert_eq!(Some(1), metadata.exif.unwrap().orientation);
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_phash() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(16, 16).write_to(&mut png, ImageFormat::Png)?;
+        let path = write_file(dir.path(), &png, "black.png")?;
+
+        let options = StoreOptions {
+            compute_phash: true,
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                assert_eq!(Some(images::perceptual_hash(&png).unwrap()), metadata.phash);
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_dominant_colors() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        // Three quarters red, with a blue stripe down the right-hand side.
+        let img = RgbImage::from_fn(80, 80, |x, _| {
+            if x < 60 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(img).write_to(&mut png, ImageFormat::Png)?;
+        let path = write_file(dir.path(), &png, "mostly_red.png")?;
+
+        let options = StoreOptions {
+            palette_size: Some(DEFAULT_PALETTE_SIZE),
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                let colors = metadata.dominant_colors;
+                assert!(!colors.is_empty() && colors.len() <= DEFAULT_PALETTE_SIZE);
+                assert_eq!((255, 0, 0), colors[0]);
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_blurhash() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let img = RgbImage::from_fn(40, 30, |x, y| Rgb([(x * 6) as u8, (y * 8) as u8, 128]));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(img).write_to(&mut png, ImageFormat::Png)?;
+        let path = write_file(dir.path(), &png, "gradient.png")?;
+
+        let options = StoreOptions {
+            blurhash_components: Some(DEFAULT_BLURHASH_COMPONENTS),
+            ..StoreOptions::default()
+        };
+        let cid = store_file_with_options(path, client_ref.clone(), options)
+            .await
+            .unwrap();
+
+        match load_file(cid.unwrap(), client_ref).await.unwrap() {
+            ContentItem::Image(_, metadata) => {
+                let blurhash = metadata.blurhash.unwrap();
+                // One size character, one maximum value character, four for the average color
+                // and two for each remaining component.
+                assert_eq!(1 + 1 + 4 + 2 * (4 * 3 - 1), blurhash.len());
+
+                let pixels = blurhash::decode(&blurhash, 8, 6, 1.0);
+                assert_eq!(8 * 6 * 4, pixels.len());
+            }
+            other => panic!("Expected image content, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let text = write_file(dir.path(), b"howdy", "howdy.txt")?;
+        let gif = write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        let missing = dir.path().join("missing.txt");
+        let directory = dir.path().to_path_buf();
+
+        let paths = vec![
+            text.clone(),
+            missing.clone(),
+            gif.clone(),
+            directory.clone(),
+        ];
+        let report = store_files(paths, client_ref, 2).await;
+        assert_eq!(4, report.total_files);
+
+        let results: HashMap<_, _> = report.results.into_iter().collect();
+        assert_eq!(4, results.len());
+        assert!(matches!(results[&text], Ok(Some(_))));
+        assert!(matches!(results[&gif], Ok(Some(_))));
+        assert!(matches!(results[&missing], Err(FzrError::Io(_))));
+        assert!(matches!(
+            results[&directory],
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_files_atomic() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let repo = tempdir()?;
+        let config = IpfsClientConfig {
+            repo_path: Some(repo.path().to_path_buf()),
+            ..IpfsClientConfig::default()
+        };
+        let client_ref = Arc::new(RwLock::new(IpfsClient::with_config(config).await.unwrap()));
+
+        let text = write_file(dir.path(), b"roll me back", "first.txt")?;
+        let missing = dir.path().join("missing.txt");
+        let paths = vec![text.clone(), missing];
+
+        let result = store_files_atomic(paths, client_ref.clone()).await;
+        assert!(matches!(result, Err(FzrError::Io(_))));
+
+        let dry_run = StoreOptions {
+            dry_run: true,
+            ..StoreOptions::default()
+        };
+        let text_cid = store_file_with_options(text.clone(), client_ref.clone(), dry_run)
+            .await?
+            .unwrap();
+        assert!(!client_ref.read().await.has(&text_cid).await.unwrap());
+
+        let other = write_file(dir.path(), b"keep me", "second.txt")?;
+        let cids = store_files_atomic(vec![text, other], client_ref.clone()).await?;
+        assert_eq!(2, cids.len());
+        assert_eq!(text_cid, cids[0]);
+        assert!(client_ref.read().await.has(&text_cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_files_atomic_keeps_shared_blocks() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let shared = write_file(dir.path(), b"already here", "shared.txt")?;
+        let shared_cid = store_file(shared.clone(), client_ref.clone())
+            .await?
+            .unwrap();
+
+        let text = write_file(dir.path(), b"roll me back", "first.txt")?;
+        let missing = dir.path().join("missing.txt");
+        let paths = vec![shared, text.clone(), missing];
+
+        let result = store_files_atomic(paths, client_ref.clone()).await;
+        assert!(matches!(result, Err(FzrError::Io(_))));
+
+        // Only the block the batch added was rolled back; the shared one was never touched.
+        let dry_run = StoreOptions {
+            dry_run: true,
+            ..StoreOptions::default()
+        };
+        let text_cid = store_file_with_options(text, client_ref.clone(), dry_run)
+            .await?
+            .unwrap();
+        let client = client_ref.read().await;
+        assert!(!client.has(&text_cid).await.unwrap());
+        assert!(client.has(&shared_cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_files_deduplication() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let paths = vec![
+            write_file(dir.path(), b"same photo", "one.txt")?,
+            write_file(dir.path(), b"same photo", "copy.txt")?,
+            write_file(dir.path(), b"another photo", "two.txt")?,
+        ];
+        let report = store_files(paths, client_ref, 2).await;
+
+        assert_eq!(3, report.total_files);
+        assert_eq!(2, report.unique_cids);
+        assert_eq!(23, report.bytes_stored);
+        assert_eq!(10, report.bytes_deduplicated);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_files_pooled_cids() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        // Larger files first, so later ones are read into buffers still holding their bytes.
+        let paths = vec![
+            write_file(dir.path(), &[0xff; 4096], "large.bin")?,
+            write_file(dir.path(), "longer text".repeat(100).as_bytes(), "long.txt")?,
+            write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?,
+            write_file(dir.path(), b"howdy", "howdy.txt")?,
+            write_file(dir.path(), b"", "empty.txt")?,
+        ];
+        let report = store_files(paths.clone(), client_ref.clone(), 1).await;
+
+        for (path, result) in report.results {
+            let expected = store_file(path, client_ref.clone()).await?;
+            assert_eq!(expected, result?);
+        }
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let mut cids = Vec::new();
+        for (name, text) in [
+            ("one.txt", "one"),
+            ("two.txt", "two"),
+            ("three.txt", "three"),
+        ] {
+            let path = write_file(dir.path(), text.as_bytes(), name)?;
+            cids.push(store_file(path, client_ref.clone()).await?.unwrap());
+        }
+
+        let dry_run = StoreOptions {
+            dry_run: true,
+            ..StoreOptions::default()
+        };
+        let path = write_file(dir.path(), b"never stored", "missing.txt")?;
+        let missing = store_file_with_options(path, client_ref.clone(), dry_run)
+            .await?
+            .unwrap();
+        cids.insert(1, missing);
+
+        let results = load_files(cids.clone(), client_ref, 2).await;
+        assert_eq!(
+            cids,
+            results.iter().map(|(cid, _)| *cid).collect::<Vec<_>>()
+        );
+
+        let texts: Vec<_> = results
+            .into_iter()
+            .map(|(_, result)| match result {
+                Ok(ContentItem::Text(content, _)) => Ok(content.string),
+                Ok(other) => panic!("Expected text content, got {:?}", other),
+                Err(err) => Err(err),
+            })
+            .collect();
+        assert_eq!(Some("one"), texts[0].as_deref().ok());
+        assert!(matches!(texts[1], Err(FzrError::NotFound(_))));
+        assert_eq!(Some("two"), texts[2].as_deref().ok());
+        assert_eq!(Some("three"), texts[3].as_deref().ok());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_directory() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let nested = dir.path().join("sub").join("deeper");
+        std::fs::create_dir_all(&nested)?;
+
+        let top = write_file(dir.path(), b"top", "top.txt")?;
+        let middle = write_file(dir.path().join("sub"), b"middle", "middle.txt")?;
+        let gif = write_file(&nested, b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        write_file(dir.path(), b"secret", ".hidden")?;
+
+        let options = DirectoryOptions {
+            skip_hidden: true,
+            ..DirectoryOptions::default()
+        };
+        let manifest =
+            store_directory_with_options(dir.path().to_path_buf(), client_ref.clone(), options)
+                .await
+                .unwrap();
+
+        let mut expected = DirectoryManifest::default();
+        for (relative_path, path) in [
+            ("top.txt", top),
+            ("sub/middle.txt", middle),
+            ("sub/deeper/smallest.gif", gif),
+        ] {
+            let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+            expected
+                .entries
+                .insert(relative_path.into(), ManifestEntry::Stored(cid.into()));
+        }
+
+        assert_eq!(expected, manifest);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_directory_globs() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        std::fs::create_dir_all(dir.path().join("notes").join("drafts"))?;
+        write_file(dir.path(), b"top", "top.txt")?;
+        write_file(dir.path(), b"GIF89a\x01\0\x01\0\0\0\0;", "smallest.gif")?;
+        write_file(dir.path().join("notes"), b"note", "note.txt")?;
+        write_file(dir.path().join("notes"), b"# Note", "note.md")?;
+        write_file(
+            dir.path().join("notes").join("drafts"),
+            b"draft",
+            "draft.txt",
+        )?;
+
+        let options = DirectoryOptions {
+            include: vec!["*.txt".into()],
+            ..DirectoryOptions::default()
+        };
+        let manifest =
+            store_directory_with_options(dir.path().to_path_buf(), client_ref.clone(), options)
+                .await?;
+        assert_eq!(
+            vec!["notes/drafts/draft.txt", "notes/note.txt", "top.txt"],
+            manifest.entries.keys().collect::<Vec<_>>()
+        );
+
+        // Excludes win over includes.
+        let options = DirectoryOptions {
+            include: vec!["**/*.txt".into()],
+            exclude: vec!["**/drafts/**".into()],
+            ..DirectoryOptions::default()
+        };
+        let manifest =
+            store_directory_with_options(dir.path().to_path_buf(), client_ref.clone(), options)
+                .await?;
+        assert_eq!(
+            vec!["notes/note.txt", "top.txt"],
+            manifest.entries.keys().collect::<Vec<_>>()
+        );
+
+        let options = DirectoryOptions {
+            include: vec!["[".into()],
+            ..DirectoryOptions::default()
+        };
+        let result =
+            store_directory_with_options(dir.path().to_path_buf(), client_ref, options).await;
+        assert!(matches!(result, Err(FzrError::InvalidGlob(_))));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[runtime::test]
+    async fn test_store_directory_symlinks() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        std::fs::create_dir(dir.path().join("sub"))?;
+        let top = write_file(dir.path(), b"top", "top.txt")?;
+        write_file(dir.path().join("sub"), b"middle", "middle.txt")?;
+        symlink(&top, dir.path().join("link.txt"))?;
+        symlink(dir.path().join("sub"), dir.path().join("alias"))?;
+        symlink(dir.path(), dir.path().join("sub").join("loop"))?;
+
+        let top_cid = Cid::from(store_file(top, client_ref.clone()).await?.unwrap());
+        let skipped = |manifest: &DirectoryManifest, path: &str| match &manifest.entries[path] {
+            ManifestEntry::Skipped(reason) => reason.clone(),
+            other => panic!("Expected {} to be skipped, got {:?}", path, other),
+        };
+
+        let manifest = store_directory(dir.path().to_path_buf(), client_ref.clone()).await?;
+        assert_eq!(5, manifest.entries.len());
+        assert_eq!(ManifestEntry::Stored(top_cid), manifest.entries["top.txt"]);
+        assert!(matches!(
+            manifest.entries["sub/middle.txt"],
+            ManifestEntry::Stored(_)
+        ));
+        for path in ["link.txt", "alias", "sub/loop"] {
+            assert_eq!("Symlink not followed", skipped(&manifest, path));
+        }
+
+        let options = DirectoryOptions {
+            follow_symlinks: true,
+            ..DirectoryOptions::default()
+        };
+        let manifest =
+            store_directory_with_options(dir.path().to_path_buf(), client_ref, options).await?;
+        assert_eq!(5, manifest.entries.len());
+        assert_eq!(ManifestEntry::Stored(top_cid), manifest.entries["link.txt"]);
+        assert!(matches!(
+            manifest.entries["alias/middle.txt"],
+            ManifestEntry::Stored(_)
+        ));
+        // `alias` sorts first, so `sub` is the second path to the same directory.
+        assert!(skipped(&manifest, "sub").starts_with("Directory already walked"));
+        assert!(skipped(&manifest, "alias/loop").starts_with("Symlink loops back"));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_stream_manifest() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        std::fs::create_dir(dir.path().join("sub"))?;
+        write_file(dir.path(), b"first", "first.txt")?;
+        write_file(dir.path(), b"second", "second.txt")?;
+        write_file(dir.path().join("sub"), b"third", "third.txt")?;
+
+        let manifest = store_directory(dir.path().to_path_buf(), client_ref.clone())
+            .await
+            .unwrap();
+        let mut manifest_with_failure = manifest.clone();
+        manifest_with_failure.entries.insert(
+            "unreadable.txt".into(),
+            ManifestEntry::Failed("Permission denied".into()),
+        );
+
+        let mut loaded: Vec<_> = stream_manifest(&manifest_with_failure, client_ref)
+            .map(|(path, result)| match result.unwrap() {
+                ContentItem::Text(content, _) => (path, content.string),
+                other => panic!("Expected text content, got {:?}", other),
+            })
+            .collect()
+            .await;
+
+        loaded.sort();
+
+        let expected = vec![
+            (PathBuf::from("first.txt"), "first".to_string()),
+            (PathBuf::from("second.txt"), "second".to_string()),
+            (PathBuf::from("sub/third.txt"), "third".to_string()),
+        ];
+        assert_eq!(expected, loaded);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_load_streaming() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let data: Vec<u8> = (0..=255).cycle().take(2500).collect();
+        let path = write_file(dir.path(), &data, "large.bin")?;
+        let cid = store_file_streaming(path, client_ref.clone(), 1024, None)
+            .await
+            .unwrap();
+
+        let block = client_ref
+            .read()
+            .await
+            .get_chunked_file(&cid)
+            .await
+            .unwrap();
+        assert_eq!(3, block.chunks.len());
+        assert_eq!(2500, block.size_bytes);
+
+        let mut reader = load_file_streaming(cid.to_string(), client_ref)
+            .await
+            .unwrap();
+        let mut loaded = Vec::new();
+        reader.read_to_end(&mut loaded).await?;
+
+        assert_eq!(data, loaded);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_chunk_iter() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let data: Vec<u8> = (0..=255).cycle().take(2500).collect();
+        let path = write_file(dir.path(), &data, "large.bin")?;
+        let cid = store_file_streaming(path, client_ref.clone(), 1024, None)
+            .await
+            .unwrap();
+
+        let chunks: Vec<Vec<u8>> = chunk_iter(&cid, client_ref.clone()).try_collect().await?;
+        assert_eq!(
+            vec![1024, 1024, 452],
+            chunks.iter().map(Vec::len).collect::<Vec<_>>()
+        );
+        assert_eq!(data, chunks.concat());
+
+        let other_client = Arc::new(RwLock::new(in_memory_client().await));
+        let mut missing = Box::pin(chunk_iter(&cid, other_client));
+        assert!(missing.next().await.unwrap().is_err());
+        assert!(missing.next().await.is_none());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_read_range() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        let data: Vec<u8> = (0..=255).cycle().take(2500).collect();
+        let path = write_file(dir.path(), &data, "large.bin")?;
+        let cid = store_file_streaming(path, client_ref.clone(), 1024, None)
+            .await
+            .unwrap();
+
+        let mid_chunk = read_range(&cid, 100, 50, client_ref.clone()).await?;
+        assert_eq!(data[100..150], mid_chunk[..]);
+
+        let spanning = read_range(&cid, 1000, 100, client_ref.clone()).await?;
+        assert_eq!(data[1000..1100], spanning[..]);
+
+        let past_end = read_range(&cid, 2400, 1000, client_ref.clone()).await?;
+        assert_eq!(data[2400..], past_end[..]);
+
+        assert!(read_range(&cid, 3000, 10, client_ref.clone())
+            .await?
+            .is_empty());
+        assert!(read_range(&cid, 100, 0, client_ref).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_streaming_chunk_deduplication() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+
+        // The same two chunks, then a different tail each.
+        let prefix = vec![7; 2048];
+        let first = write_file(dir.path(), &[&prefix[..], b"first"].concat(), "first.bin")?;
+        let second = write_file(dir.path(), &[&prefix[..], b"second"].concat(), "second.bin")?;
+
+        let mut report = ChunkStoreReport::default();
+        for path in [first, second] {
+            let (_, file_report) =
+                store_file_streaming_with_report(path, client_ref.clone(), 1024, None).await?;
+            report += file_report;
+        }
+
+        // The prefix is two identical chunks, so only the first of its four copies is stored.
+        assert_eq!(6, report.total_chunks);
+        assert_eq!(3, report.unique_chunks);
+        assert!(report.unique_chunks < report.total_chunks);
+        assert_eq!(3 * 1024, report.bytes_saved);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_progress_callbacks() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let data: Vec<u8> = (0..=255).cycle().take(2500).collect();
+        let path = write_file(dir.path(), &data, "progress.bin")?;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&calls);
+        let progress = ProgressCallback::new(move |processed, total| {
+            recorded.lock().unwrap().push((processed, total))
+        });
+
+        store_file_streaming(
+            path.clone(),
+            client_ref.clone(),
+            1024,
+            Some(progress.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![(1024, 2500), (2048, 2500), (2500, 2500)],
+            *calls.lock().unwrap()
+        );
+
+        calls.lock().unwrap().clear();
+        let options = StoreOptions {
+            progress: Some(progress),
+            ..StoreOptions::default()
+        };
+        store_file_with_options(path, client_ref, options)
+            .await
+            .unwrap();
+        assert_eq!(vec![(2500, 2500)], *calls.lock().unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_export_car() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"howdy", "howdy.txt")?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+
+        let mut car = Vec::new();
+        client_ref
+            .read()
+            .await
+            .export_car(&cid, &mut car)
+            .await
+            .unwrap();
+
+        let (header_len, rest) = unsigned_varint::decode::usize(&car)?;
+        let header: CarHeader = DagCborCodec.decode(&rest[..header_len])?;
+        assert_eq!(vec![cid], header.roots);
+        assert_eq!(CAR_VERSION, header.version);
+
+        let mut sections = &rest[header_len..];
+        let mut block_count = 0;
+        while !sections.is_empty() {
+            let (section_len, rest) = unsigned_varint::decode::usize(sections)?;
+            sections = &rest[section_len..];
+            block_count += 1;
+        }
+        assert_eq!(1, block_count);
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_import_car() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let path = write_file(dir.path(), b"archived", "archived.txt")?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+
+        let mut car = Vec::new();
+        let client = client_ref.read().await;
+        client.export_car(&cid, &mut car).await.unwrap();
+
+        drop(client);
+
+        // Import into a fresh store so the block can only have come from the archive.
+        let fresh_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let roots = fresh_ref
+            .read()
+            .await
+            .import_car(car.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(vec![cid], roots);
+
+        let loaded = load_file(cid, fresh_ref).await.unwrap();
+        assert!(matches!(loaded, ContentItem::Text(_, _)));
+
+        // Flip the last byte of the block data so it no longer matches its CID.
+        let last = car.len() - 1;
+        car[last] ^= 0xff;
+        assert!(in_memory_client()
+            .await
+            .import_car(car.as_slice())
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_invalid_cid() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        assert!(matches!(
+            "not-a-cid".parse::<ContentCid>(),
+            Err(FzrError::CidParse(_))
+        ));
+
+        // Stored content round-trips through ContentCid, but a raw chunk's CID is rejected.
+        let path = write_file(dir.path(), b"content", "content.txt")?;
+        let cid = store_file(path, client_ref.clone()).await.unwrap().unwrap();
+        assert_eq!(cid, ContentCid::new(cid.into())?);
+
+        let chunk_cid = client_ref.read().await.add_chunk(b"chunk").await.unwrap();
+        assert!(matches!(
+            ContentCid::new(chunk_cid),
+            Err(FzrError::NotContentCid(_))
+        ));
+
+        assert!(matches!(
+            load_file_streaming("not-a-cid".into(), client_ref).await,
+            Err(FzrError::CidParse(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_load_missing_cid() -> Result<(), Box<dyn Error>> {
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let block = ContentItemBlock {
+            content: ContentItem::Text(
+                TextContent {
+                    string: "never stored".into(),
+                },
+                TextMetadata {
+                    size_bytes: 12,
+                    fingerprint: Fingerprint::of(b"never stored"),
+                    detected_encoding: None,
+                    char_count: 12,
+                    word_count: 2,
+                    line_endings: LineEndings::None,
+                    line_endings_normalized: false,
+                    compression: Compression::None,
+                    compressed_size_bytes: None,
+                },
+            ),
+            size_bytes: 12,
+            metadata: Vec::new(),
+            metadata_cid: None,
+            previous_version: None,
+            transcoded_cid: None,
+            thumbnail_cid: None,
+            created_at: None,
+        };
+        let cid = ContentCid::new(client_ref.read().await.compute_cid(&block).unwrap())?;
+
+        let options = LoadOptions {
+            timeout: Duration::from_secs(5),
+            ..LoadOptions::default()
+        };
+        let start = Instant::now();
+
+        match load_file_with_options(cid, client_ref, options).await {
+            Err(FzrError::NotFound(missing)) => assert_eq!(cid, missing),
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    /// Runs against a kubo daemon on the default API port, and passes trivially if none is running.
+    #[cfg(feature = "kubo-tests")]
+    #[runtime::test]
+    async fn test_store_load_kubo() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client = IpfsClient::connect_http("http://127.0.0.1:5001").unwrap();
+
+        // The identity CID of empty data, which any reachable daemon can answer for.
+        if client.has(&Cid::from_str("bafkqaaa")?).await.is_err() {
+            eprintln!("No IPFS daemon reachable at 127.0.0.1:5001, skipping.");
+            return Ok(());
+        }
+
+        let client_ref = Arc::new(RwLock::new(client));
+        let path = write_file(dir.path(), b"howdy kubo", "kubo.txt")?;
+        let cid = store_file_pinned(path, client_ref.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        match load_file(cid, client_ref.clone()).await.unwrap() {
+            ContentItem::Text(content, _) => assert_eq!("howdy kubo", content.string),
+            other => panic!("Expected text content, got {:?}", other),
         }
+
+        let client = client_ref.read().await;
+        assert!(client.is_pinned(&cid).await.unwrap());
+        client.unpin(&cid).await.unwrap();
+        assert!(client.remove(&cid).await.unwrap());
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_unreadable_paths() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        let missing = dir.path().join("does-not-exist.txt");
+        assert!(matches!(
+            store_file(missing, client_ref.clone()).await,
+            Err(FzrError::Io(_))
+        ));
+
+        let directory = dir.path().to_path_buf();
+        assert!(matches!(
+            store_file(directory, client_ref).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_max_size() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(in_memory_client().await));
+        let options = StoreOptions {
+            max_size_bytes: Some(1024),
+            ..StoreOptions::default()
+        };
+
+        let small = write_file(dir.path(), b"howdy", "small.txt")?;
+        let cid = store_file_with_options(small, client_ref.clone(), options.clone()).await?;
+        assert!(cid.is_some());
+
+        // Sparse, so it takes no space on disk but would exhaust memory if it were read.
+        let huge = dir.path().join("huge.bin");
+        File::create(&huge)?.set_len(64 * 1024 * 1024 * 1024)?;
+        assert!(matches!(
+            store_file_with_options(huge, client_ref.clone(), options.clone()).await,
+            Err(FzrError::TooLarge(1024))
+        ));
+
+        let reader = futures::io::Cursor::new(vec![b'a'; 1025]);
+        assert!(matches!(
+            store_reader_with_options(reader, None, client_ref, options).await,
+            Err(FzrError::TooLarge(1024))
+        ));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_store_error_variants() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let client_ref = Arc::new(RwLock::new(IpfsClient::new().await.unwrap()));
+
+        // PNG magic number followed by garbage, so it's classified as an image but can't be read.
+        let corrupt = write_file(dir.path(), b"\x89PNG garbage", "corrupt.png")?;
+        assert!(matches!(
+            store_file(corrupt, client_ref.clone()).await,
+            Err(FzrError::Decode(_))
+        ));
+
+        let text = write_file(dir.path(), b"howdy", "howdy.txt")?;
+        assert!(matches!(
+            store_image_with_thumbnail(text, client_ref.clone(), 10).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
+        let file = write_file(dir.path(), b"howdy", "file.txt")?;
+        assert!(matches!(
+            store_directory(file, client_ref).await,
+            Err(FzrError::UnsupportedFormat(_))
+        ));
+
         Ok(())
     }
 }
\ No newline at end of file
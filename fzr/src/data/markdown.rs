@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use crate::data::content::Heading;
+
+pub const MARKDOWN_MIME_TYPE: &str = "text/markdown";
+
+/// Markdown looks too much like plain text to sniff reliably, so it's recognized by file
+/// extension or a caller-supplied content type instead.
+const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+pub fn is_markdown(mime_type: &str) -> bool {
+    mime_type == MARKDOWN_MIME_TYPE || mime_type == "text/x-markdown"
+}
+
+pub fn has_markdown_extension(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => MARKDOWN_EXTENSIONS
+            .iter()
+            .any(|markdown| markdown.eq_ignore_ascii_case(extension)),
+        None => false,
+    }
+}
+
+/// Lists a document's ATX (`## Section`) and setext (underlined) headings in order. Lines inside
+/// fenced code blocks are skipped; emphasis and links in heading text are kept as written.
+pub fn outline(markdown: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut fence: Option<&str> = None;
+    // The last line, if it could be the text of a setext heading underlined by this one.
+    let mut paragraph: Option<&str> = None;
+
+    for line in markdown.lines() {
+        let trimmed = strip_indent(line);
+
+        if let Some(marker) = fence {
+            if matches!(trimmed, Some(trimmed) if trimmed.starts_with(marker)) {
+                fence = None;
+            }
+            continue;
+        }
+
+        let trimmed = match trimmed {
+            Some(trimmed) if !trimmed.trim().is_empty() => trimmed,
+            // Blank lines end paragraphs, and indented code can't be a heading.
+            _ => {
+                paragraph = None;
+                continue;
+            }
+        };
+
+        if let Some(marker) = ["```", "~~~"]
+            .iter()
+            .find(|marker| trimmed.starts_with(*marker))
+        {
+            fence = Some(marker);
+            paragraph = None;
+        } else if let Some(heading) = atx_heading(trimmed) {
+            headings.push(heading);
+            paragraph = None;
+        } else if let (Some(text), Some(level)) = (paragraph, setext_level(trimmed)) {
+            headings.push(Heading {
+                level,
+                text: text.trim().to_string(),
+            });
+            paragraph = None;
+        } else {
+            paragraph = Some(trimmed);
+        }
+    }
+
+    headings
+}
+
+/// Strips up to three spaces of indentation, or returns `None` for lines indented further.
+fn strip_indent(line: &str) -> Option<&str> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+
+    if indent > 3 {
+        None
+    } else {
+        Some(&line[indent..])
+    }
+}
+
+/// Parses `# Title`, where the hashes must be followed by a space or end the line, and any
+/// closing hashes are dropped.
+fn atx_heading(line: &str) -> Option<Heading> {
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    let text = rest.trim();
+    let text = match text.trim_end_matches('#') {
+        // Closing hashes only count when separated from the text.
+        unclosed if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) => unclosed.trim_end(),
+        _ => text,
+    };
+
+    Some(Heading {
+        level: level as u8,
+        text: text.to_string(),
+    })
+}
+
+/// Level of a setext underline: `===` for 1, `---` for 2.
+fn setext_level(line: &str) -> Option<u8> {
+    let underline = line.trim_end();
+
+    if !underline.is_empty() && underline.bytes().all(|byte| byte == b'=') {
+        Some(1)
+    } else if !underline.is_empty() && underline.bytes().all(|byte| byte == b'-') {
+        Some(2)
+    } else {
+        None
+    }
+}
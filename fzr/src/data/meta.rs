@@ -1,19 +1,559 @@
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+use libipld::{Cid, DagCbor};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+use crate::data::error::MetadataError;
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum MetadataRelationship {
     Is,
     Has,
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum MetadataCategory {
     Originator,
     Attribute,
     Relation(MetadataRelationship),
 }
 
-#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
-pub enum MetadataItem {
-    parent: Option<MetadataItem>
-    value: String
-    category: MetadataCategory,
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MetadataItem {
+    /// Shared rather than boxed, since libipld only implements DAG-CBOR for `Arc`.
+    pub parent: Option<Arc<MetadataItem>>,
+    pub value: String,
+    pub category: MetadataCategory,
+}
+
+impl MetadataItem {
+    pub fn builder() -> MetadataItemBuilder {
+        MetadataItemBuilder::default()
+    }
+
+    /// Returns this item followed by each of its ancestors, ending at the root. Reverse it for a
+    /// root-first breadcrumb like "Collection → Album → Photo".
+    pub fn ancestry(&self) -> Vec<&MetadataItem> {
+        // Iterative rather than recursive, so deeply nested chains can't overflow the stack.
+        let mut ancestry = vec![self];
+        let mut current = self;
+
+        while let Some(parent) = current.parent.as_deref() {
+            ancestry.push(parent);
+            current = parent;
+        }
+
+        ancestry
+    }
+
+    /// Checks that no item appears among its own ancestors. Items are identified by category and
+    /// value, which is all a chain has to go on once parents are linked by CID, so a repeat means
+    /// resolving the chain would loop forever.
+    pub fn validate_acyclic(&self) -> Result<(), MetadataError> {
+        let mut visited = HashSet::new();
+
+        for item in self.ancestry() {
+            if !visited.insert((&item.category, item.value.as_str())) {
+                return Err(MetadataError::Cycle(item.value.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A free-form tag, such as "sunset", stored as a parentless `Attribute` item.
+pub fn tag(name: &str) -> MetadataItem {
+    MetadataItem {
+        parent: None,
+        value: name.to_string(),
+        category: MetadataCategory::Attribute,
+    }
+}
+
+/// The tags among `items`, in order and without repeats. Attributes with a parent are part of a
+/// hierarchy, not tags, so they're left out.
+pub fn tags_of(items: &[MetadataItem]) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    items
+        .iter()
+        .filter(|item| item.category == MetadataCategory::Attribute && item.parent.is_none())
+        .filter(|item| seen.insert(item.value.as_str()))
+        .map(|item| item.value.clone())
+        .collect()
+}
+
+/// Describes the graph formed by `items` and their ancestors in Graphviz DOT, for piping into
+/// `dot -Tpng`. Items with the same category and value become a single node, and each child
+/// points at its parent with an edge styled by the child's relationship.
+pub fn to_dot(items: &[MetadataItem]) -> String {
+    let mut nodes: HashMap<(&MetadataCategory, &str), usize> = HashMap::new();
+    let mut edges = BTreeSet::new();
+    let mut dot = String::from("digraph metadata {\n");
+
+    for item in items {
+        let mut child = None;
+
+        for ancestor in item.ancestry() {
+            let next_id = nodes.len();
+            let id = *nodes
+                .entry((&ancestor.category, ancestor.value.as_str()))
+                .or_insert_with(|| {
+                    dot.push_str(&format!(
+                        "    n{} [label=\"{}\"];\n",
+                        next_id,
+                        escape_dot(&ancestor.value)
+                    ));
+                    next_id
+                });
+
+            if let Some((child_id, category)) = child {
+                if edges.insert((child_id, id)) {
+                    dot.push_str(&format!(
+                        "    n{} -> n{} [{}];\n",
+                        child_id,
+                        id,
+                        edge_attributes(category)
+                    ));
+                }
+            }
+
+            child = Some((id, &ancestor.category));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn edge_attributes(category: &MetadataCategory) -> &'static str {
+    match category {
+        MetadataCategory::Relation(MetadataRelationship::Is) => "label=\"is\", style=solid",
+        MetadataCategory::Relation(MetadataRelationship::Has) => "label=\"has\", style=dashed",
+        MetadataCategory::Originator | MetadataCategory::Attribute => "style=dotted",
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Fluent construction of [`MetadataItem`]s, validated when built.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataItemBuilder {
+    parent: Option<MetadataItem>,
+    value: String,
+    category: Option<MetadataCategory>,
+}
+
+impl MetadataItemBuilder {
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.value = value.into();
+        self
+    }
+
+    pub fn category(mut self, category: MetadataCategory) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn child_of(mut self, parent: MetadataItem) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn build(self) -> Result<MetadataItem, MetadataError> {
+        if self.value.is_empty() {
+            return Err(MetadataError::EmptyValue);
+        }
+
+        let category = self.category.ok_or(MetadataError::MissingCategory)?;
+
+        Ok(MetadataItem {
+            parent: self.parent.map(Arc::new),
+            value: self.value,
+            category,
+        })
+    }
+}
+
+/// Filters metadata items, matching those that satisfy every predicate that's been set.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataQuery {
+    category: Option<MetadataCategory>,
+    value_contains: Option<String>,
+    relationship: Option<MetadataRelationship>,
+}
+
+impl MetadataQuery {
+    pub fn new() -> Self {
+        MetadataQuery::default()
+    }
+
+    pub fn category(mut self, category: MetadataCategory) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn value_contains(mut self, needle: &str) -> Self {
+        self.value_contains = Some(needle.to_string());
+        self
+    }
+
+    /// Matches only `Relation` items with this relationship.
+    pub fn relationship(mut self, relationship: MetadataRelationship) -> Self {
+        self.relationship = Some(relationship);
+        self
+    }
+
+    pub fn matches(&self, item: &MetadataItem) -> bool {
+        if let Some(category) = &self.category {
+            if &item.category != category {
+                return false;
+            }
+        }
+
+        if let Some(needle) = &self.value_contains {
+            if !item.value.contains(needle.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(relationship) = &self.relationship {
+            if !matches!(&item.category, MetadataCategory::Relation(r) if r == relationship) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Maps metadata values and categories to the CIDs of the content they're attached to, for
+/// answering "which stored items have metadata X" without loading every block.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataIndex {
+    by_value: HashMap<String, BTreeSet<Cid>>,
+    by_category: HashMap<MetadataCategory, BTreeSet<Cid>>,
+}
+
+impl MetadataIndex {
+    pub fn new() -> Self {
+        MetadataIndex::default()
+    }
+
+    /// Indexes the metadata attached to `cid`. Only the items themselves are indexed, not their
+    /// parents.
+    pub fn insert(&mut self, cid: Cid, items: &[MetadataItem]) {
+        for item in items {
+            self.by_value
+                .entry(item.value.clone())
+                .or_default()
+                .insert(cid);
+            self.by_category
+                .entry(item.category.clone())
+                .or_default()
+                .insert(cid);
+        }
+    }
+
+    /// CIDs of content with a metadata item whose value is exactly `value`, in CID order.
+    pub fn lookup_value(&self, value: &str) -> Vec<Cid> {
+        self.by_value
+            .get(value)
+            .map(|cids| cids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// CIDs of content with a metadata item in `category`, in CID order.
+    pub fn lookup_category(&self, category: &MetadataCategory) -> Vec<Cid> {
+        self.by_category
+            .get(category)
+            .map(|cids| cids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl FromIterator<(Cid, Vec<MetadataItem>)> for MetadataIndex {
+    fn from_iter<I: IntoIterator<Item = (Cid, Vec<MetadataItem>)>>(iter: I) -> Self {
+        let mut index = MetadataIndex::new();
+
+        for (cid, items) in iter {
+            index.insert(cid, &items);
+        }
+
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::cbor::DagCborCodec;
+    use libipld::codec::Codec;
+    use libipld::multihash::Code;
+    use libipld::store::DefaultParams;
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let originator = MetadataItem {
+            parent: None,
+            value: "alice".into(),
+            category: MetadataCategory::Originator,
+        };
+        let item = MetadataItem {
+            parent: Some(Arc::new(originator)),
+            value: "holiday photos".into(),
+            category: MetadataCategory::Relation(MetadataRelationship::Has),
+        };
+
+        let bytes = DagCborCodec.encode(&item).unwrap();
+        let decoded: MetadataItem = DagCborCodec.decode(&bytes).unwrap();
+
+        assert_eq!(item, decoded);
+    }
+
+    #[test]
+    fn test_ancestry() {
+        let collection = MetadataItem {
+            parent: None,
+            value: "Collection".into(),
+            category: MetadataCategory::Attribute,
+        };
+        let album = MetadataItem {
+            parent: Some(Arc::new(collection.clone())),
+            value: "Album".into(),
+            category: MetadataCategory::Attribute,
+        };
+        let photo = MetadataItem {
+            parent: Some(Arc::new(album.clone())),
+            value: "Photo".into(),
+            category: MetadataCategory::Attribute,
+        };
+
+        let values: Vec<_> = photo
+            .ancestry()
+            .iter()
+            .map(|item| item.value.as_str())
+            .collect();
+
+        assert_eq!(vec!["Photo", "Album", "Collection"], values);
+        assert_eq!(&album, photo.ancestry()[1]);
+        assert_eq!(&collection, photo.ancestry()[2]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let root = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Originator)
+            .build()
+            .unwrap();
+        let child = MetadataItem::builder()
+            .value("landscape")
+            .category(MetadataCategory::Attribute)
+            .child_of(root)
+            .build()
+            .unwrap();
+
+        let expected = MetadataItem {
+            parent: Some(Arc::new(MetadataItem {
+                parent: None,
+                value: "alice".into(),
+                category: MetadataCategory::Originator,
+            })),
+            value: "landscape".into(),
+            category: MetadataCategory::Attribute,
+        };
+
+        assert_eq!(expected, child);
+        assert_eq!(
+            Err(MetadataError::EmptyValue),
+            MetadataItem::builder()
+                .category(MetadataCategory::Attribute)
+                .build()
+        );
+        assert_eq!(
+            Err(MetadataError::MissingCategory),
+            MetadataItem::builder().value("untyped").build()
+        );
+    }
+
+    #[test]
+    fn test_query_predicates() {
+        let item = |value: &str, category| MetadataItem {
+            parent: None,
+            value: value.into(),
+            category,
+        };
+        let alice = item("alice", MetadataCategory::Originator);
+        let bob = item("bob", MetadataCategory::Originator);
+        let alice_album = item(
+            "alice's album",
+            MetadataCategory::Relation(MetadataRelationship::Has),
+        );
+        let landscape = item("landscape", MetadataCategory::Attribute);
+
+        let matching = |query: MetadataQuery| -> Vec<_> {
+            [&alice, &bob, &alice_album, &landscape]
+                .iter()
+                .filter(|item| query.matches(item))
+                .map(|item| item.value.as_str())
+                .collect()
+        };
+
+        assert_eq!(4, matching(MetadataQuery::new()).len());
+        assert_eq!(
+            vec!["alice", "bob"],
+            matching(MetadataQuery::new().category(MetadataCategory::Originator))
+        );
+        assert_eq!(
+            vec!["alice", "alice's album"],
+            matching(MetadataQuery::new().value_contains("alice"))
+        );
+        assert_eq!(
+            vec!["alice's album"],
+            matching(MetadataQuery::new().relationship(MetadataRelationship::Has))
+        );
+        assert!(matching(MetadataQuery::new().relationship(MetadataRelationship::Is)).is_empty());
+
+        assert_eq!(
+            vec!["alice"],
+            matching(
+                MetadataQuery::new()
+                    .category(MetadataCategory::Originator)
+                    .value_contains("alice")
+            )
+        );
+        assert!(matching(
+            MetadataQuery::new()
+                .category(MetadataCategory::Attribute)
+                .value_contains("alice")
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_index_lookups() {
+        let cid = |n: u64| {
+            *libipld::Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &n)
+                .unwrap()
+                .cid()
+        };
+        let item = |value: &str, category| MetadataItem {
+            parent: None,
+            value: value.into(),
+            category,
+        };
+        let (first, second, third) = (cid(1), cid(2), cid(3));
+        let mut by_alice = vec![first, second];
+        by_alice.sort();
+
+        let index: MetadataIndex = vec![
+            (
+                first,
+                vec![
+                    item("alice", MetadataCategory::Originator),
+                    item("landscape", MetadataCategory::Attribute),
+                ],
+            ),
+            (second, vec![item("alice", MetadataCategory::Originator)]),
+            (
+                third,
+                vec![item(
+                    "bob",
+                    MetadataCategory::Relation(MetadataRelationship::Is),
+                )],
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(by_alice, index.lookup_value("alice"));
+        assert_eq!(vec![first], index.lookup_value("landscape"));
+        assert!(index.lookup_value("ali").is_empty());
+
+        assert_eq!(
+            by_alice,
+            index.lookup_category(&MetadataCategory::Originator)
+        );
+        assert_eq!(
+            vec![third],
+            index.lookup_category(&MetadataCategory::Relation(MetadataRelationship::Is))
+        );
+        assert!(index
+            .lookup_category(&MetadataCategory::Relation(MetadataRelationship::Has))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_acyclic() {
+        let alice = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Originator)
+            .build()
+            .unwrap();
+        let album = MetadataItem::builder()
+            .value("album")
+            .category(MetadataCategory::Relation(MetadataRelationship::Has))
+            .child_of(alice.clone())
+            .build()
+            .unwrap();
+        assert_eq!(Ok(()), album.validate_acyclic());
+
+        // The same value under another category is a different item, not a cycle.
+        let namesake = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Attribute)
+            .child_of(album.clone())
+            .build()
+            .unwrap();
+        assert_eq!(Ok(()), namesake.validate_acyclic());
+
+        let cyclic = MetadataItem {
+            parent: Some(Arc::new(album)),
+            ..alice
+        };
+        assert_eq!(
+            Err(MetadataError::Cycle("alice".into())),
+            cyclic.validate_acyclic()
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let alice = MetadataItem::builder()
+            .value("alice")
+            .category(MetadataCategory::Originator)
+            .build()
+            .unwrap();
+        let album = MetadataItem::builder()
+            .value("holiday \"album\"")
+            .category(MetadataCategory::Relation(MetadataRelationship::Has))
+            .child_of(alice.clone())
+            .build()
+            .unwrap();
+        let portrait = MetadataItem::builder()
+            .value("portrait")
+            .category(MetadataCategory::Relation(MetadataRelationship::Is))
+            .child_of(alice)
+            .build()
+            .unwrap();
+
+        let dot = to_dot(&[album.clone(), portrait, album]);
+
+        assert!(dot.starts_with("digraph metadata {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("n0 [label=\"holiday \\\"album\\\"\"];"));
+        assert!(dot.contains("n1 [label=\"alice\"];"));
+        assert!(dot.contains("n2 [label=\"portrait\"];"));
+        assert!(dot.contains("n0 -> n1 [label=\"has\", style=dashed];"));
+        assert!(dot.contains("n2 -> n1 [label=\"is\", style=solid];"));
+
+        // Shared nodes and repeated edges are only declared once.
+        assert_eq!(1, dot.matches("label=\"alice\"").count());
+        assert_eq!(2, dot.matches(" -> ").count());
+    }
 }
\ No newline at end of file
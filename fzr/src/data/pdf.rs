@@ -0,0 +1,238 @@
+pub const PDF_MIME_TYPE: &str = "application/pdf";
+
+/// Reads `(page_count, title, author)` from a PDF. Only objects written out as plain text are
+/// seen, so anything packed into a compressed object stream, as some PDF 1.5+ writers do, is
+/// reported as missing.
+pub fn pdf_info(buffer: &[u8]) -> (Option<u32>, Option<String>, Option<String>) {
+    let info = info_object(buffer);
+    let info_string = |key: &[u8]| info.and_then(|info| string_value(info, key));
+
+    (
+        page_count(buffer),
+        info_string(b"/Title"),
+        info_string(b"/Author"),
+    )
+}
+
+/// The page tree root's `/Count` is the document's page count. Every other `/Pages` node counts
+/// only its own subtree, so the largest count is the root's. PDFs without a readable page tree
+/// fall back to counting page objects.
+fn page_count(buffer: &[u8]) -> Option<u32> {
+    let mut tree_count = None;
+    let mut page_objects = 0;
+
+    for object in objects(buffer) {
+        if has_type(object, b"/Pages") {
+            if let Some(count) = integer_value(object, b"/Count") {
+                tree_count = tree_count.max(Some(count));
+            }
+        } else if has_type(object, b"/Page") {
+            page_objects += 1;
+        }
+    }
+
+    tree_count.or(Some(page_objects).filter(|&count| count > 0))
+}
+
+/// The document information dictionary the trailer's `/Info` entry refers to.
+fn info_object(buffer: &[u8]) -> Option<&[u8]> {
+    // The last trailer wins, since incremental updates append new ones.
+    let start = rfind(buffer, b"/Info")? + b"/Info".len();
+    let mut fields = buffer[start..]
+        .split(|&byte| is_whitespace(byte) || is_delimiter(byte))
+        .filter(|field| !field.is_empty());
+
+    let number = fields.next()?;
+    let generation = fields.next()?;
+    if fields.next()? != b"R" {
+        return None;
+    }
+
+    let header = [number, b" ", generation, b" obj"].concat();
+    let mut from = 0;
+
+    while let Some(pos) = find(buffer, &header, from) {
+        if pos == 0 || is_whitespace(buffer[pos - 1]) {
+            let body = &buffer[pos + header.len()..];
+            return Some(&body[..find(body, b"endobj", 0).unwrap_or(body.len())]);
+        }
+
+        from = pos + 1;
+    }
+
+    None
+}
+
+/// The bodies of every `N G obj ... endobj` object in the file.
+fn objects(buffer: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut from = 0;
+
+    std::iter::from_fn(move || {
+        let start = find(buffer, b"obj", from)? + b"obj".len();
+        let end = find(buffer, b"endobj", start).unwrap_or(buffer.len());
+        from = end + b"endobj".len();
+
+        Some(&buffer[start..end])
+    })
+}
+
+/// Whether a dictionary's `/Type` is exactly `name`, so `/Page` doesn't match `/Pages`.
+fn has_type(object: &[u8], name: &[u8]) -> bool {
+    match value_after(object, b"/Type") {
+        Some(value) => {
+            value.starts_with(name)
+                && !matches!(value.get(name.len()), Some(&byte) if is_regular(byte))
+        }
+        None => false,
+    }
+}
+
+fn integer_value(object: &[u8], key: &[u8]) -> Option<u32> {
+    let value = value_after(object, key)?;
+    let len = value
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+
+    std::str::from_utf8(&value[..len]).ok()?.parse().ok()
+}
+
+/// Reads a literal `(...)` or hex `<...>` string value, decoding UTF-16 text marked by a byte
+/// order mark, and treating anything else as Latin-1, which PDFDocEncoding nearly is.
+fn string_value(object: &[u8], key: &[u8]) -> Option<String> {
+    let value = value_after(object, key)?;
+
+    let bytes = match value.first()? {
+        b'(' => literal_string(&value[1..]),
+        b'<' => hex_string(&value[1..])?,
+        _ => return None,
+    };
+
+    let string = match bytes.strip_prefix(b"\xfe\xff") {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&byte| byte as char).collect(),
+    };
+
+    Some(string).filter(|string| !string.is_empty())
+}
+
+/// Unescapes a literal string's bytes up to its closing parenthesis. Unescaped parentheses
+/// inside it must be balanced.
+fn literal_string(value: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut depth = 0;
+    let mut iter = value.iter().copied().peekable();
+
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'\\' => match iter.next() {
+                Some(b'n') => bytes.push(b'\n'),
+                Some(b'r') => bytes.push(b'\r'),
+                Some(b't') => bytes.push(b'\t'),
+                Some(b'b') => bytes.push(0x08),
+                Some(b'f') => bytes.push(0x0c),
+                // A backslash before a line break continues the string on the next line.
+                Some(b'\r') => {
+                    iter.next_if_eq(&b'\n');
+                }
+                Some(b'\n') => {}
+                Some(digit @ b'0'..=b'7') => {
+                    let mut code = (digit - b'0') as u32;
+                    for _ in 0..2 {
+                        match iter.next_if(|byte| (b'0'..=b'7').contains(byte)) {
+                            Some(digit) => code = code * 8 + (digit - b'0') as u32,
+                            None => break,
+                        }
+                    }
+                    bytes.push(code as u8);
+                }
+                Some(other) => bytes.push(other),
+                None => break,
+            },
+            b'(' => {
+                depth += 1;
+                bytes.push(byte);
+            }
+            b')' if depth == 0 => break,
+            b')' => {
+                depth -= 1;
+                bytes.push(byte);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+
+    bytes
+}
+
+/// Decodes a hex string's digits up to its closing `>`. A missing final digit counts as 0.
+fn hex_string(value: &[u8]) -> Option<Vec<u8>> {
+    let end = value.iter().position(|&byte| byte == b'>')?;
+    let digits: Vec<u8> = value[..end]
+        .iter()
+        .filter(|&&byte| !is_whitespace(byte))
+        .map(|&byte| (byte as char).to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<_>>()?;
+
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+            .collect(),
+    )
+}
+
+/// The bytes after a dictionary key and any whitespace following it.
+fn value_after<'a>(object: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let mut from = 0;
+
+    while let Some(pos) = find(object, key, from) {
+        let rest = &object[pos + key.len()..];
+
+        // Skip longer keys that merely start with this one, like `/Counter` for `/Count`.
+        if !matches!(rest.first(), Some(&byte) if is_regular(byte)) {
+            let len = rest.iter().take_while(|&&byte| is_whitespace(byte)).count();
+            return Some(&rest[len..]);
+        }
+
+        from = pos + 1;
+    }
+
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | 0x0c | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+/// Whether a byte can be part of a name or number, rather than ending it.
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
@@ -0,0 +1,162 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use anyhow::Error;
+use libipld::Result;
+
+use crate::data::runtime::{self, Arc, TimeoutError};
+
+/// How many times to attempt a fallible IPFS operation, and how long to back off between
+/// attempts. Only transient failures are retried; see [`is_transient`].
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Factor each successive delay is multiplied by.
+    pub multiplier: u32,
+    /// Longest delay between two attempts, however many have failed.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2,
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retrying after `failures` failed attempts.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .checked_pow(failures.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    /// Runs `operation` until it succeeds, fails permanently or runs out of attempts, e.g.
+    /// `policy.retry(|| client.add(&block)).await`.
+    pub async fn retry<T, F, Fut>(&self, mut operation: F) -> Result<T, Arc<Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Arc<Error>>>,
+    {
+        let mut failures = 0;
+
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    failures += 1;
+
+                    if failures >= self.max_attempts || !is_transient(&err) {
+                        return Err(err);
+                    }
+
+                    runtime::sleep(self.delay(failures)).await;
+                }
+            }
+        }
+    }
+}
+
+/// Whether an error is worth retrying: timeouts and dropped connections rather than malformed
+/// data or missing blocks.
+pub fn is_transient(err: &Error) -> bool {
+    err.chain()
+        .any(|cause| match cause.downcast_ref::<io::Error>() {
+            Some(err) => matches!(
+                err.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::Interrupted
+            ),
+            None => cause.is::<TimeoutError>(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    use std::error::Error;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn flaky(attempts: &AtomicU32, failures: u32) -> Result<&'static str, Arc<anyhow::Error>> {
+        if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+            Err(Arc::new(
+                io::Error::new(io::ErrorKind::ConnectionReset, "connection reset").into(),
+            ))
+        } else {
+            Ok("stored")
+        }
+    }
+
+    #[runtime::test]
+    async fn test_retry_transient_failures() -> Result<(), Box<dyn Error>> {
+        let attempts = AtomicU32::new(0);
+        let result = policy(3).retry(|| async { flaky(&attempts, 2) }).await;
+        assert_eq!("stored", result.unwrap());
+        assert_eq!(3, attempts.load(Ordering::SeqCst));
+
+        let attempts = AtomicU32::new(0);
+        let result = policy(2).retry(|| async { flaky(&attempts, 2) }).await;
+        assert!(result.is_err());
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[runtime::test]
+    async fn test_retry_fails_fast_on_permanent_errors() -> Result<(), Box<dyn Error>> {
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), _> = policy(3)
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(Arc::new(anyhow!("Failed to decode block")))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(1, attempts.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2,
+            max_delay: Duration::from_millis(500),
+        };
+
+        assert_eq!(Duration::from_millis(100), policy.delay(1));
+        assert_eq!(Duration::from_millis(200), policy.delay(2));
+        assert_eq!(Duration::from_millis(400), policy.delay(3));
+        assert_eq!(Duration::from_millis(500), policy.delay(4));
+        assert_eq!(Duration::from_millis(500), policy.delay(40));
+    }
+}
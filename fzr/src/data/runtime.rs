@@ -0,0 +1,112 @@
+//! The async runtime behind the data layer: async-std by default, or tokio with the `tokio`
+//! feature. Other modules take their filesystem, locking, timer and task primitives from here, so
+//! public signatures are the same whichever runtime is chosen.
+
+use futures::io::AsyncRead;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// async-std has no semaphore, so both runtimes use async-lock's, which works on any executor.
+pub use async_lock::Semaphore;
+
+#[cfg(not(feature = "tokio"))]
+pub use async_std::{
+    fs,
+    future::TimeoutError,
+    main,
+    sync::{Arc, RwLock},
+    test,
+};
+
+#[cfg(feature = "tokio")]
+pub use std::sync::Arc;
+#[cfg(feature = "tokio")]
+pub use tokio::{fs, main, sync::RwLock, test, time::error::Elapsed as TimeoutError};
+
+/// Opens a file for reading through the `futures` I/O traits.
+#[cfg(not(feature = "tokio"))]
+pub async fn open(path: &Path) -> io::Result<impl AsyncRead + Unpin> {
+    async_std::fs::File::open(path).await
+}
+
+/// Opens a file for reading through the `futures` I/O traits.
+#[cfg(feature = "tokio")]
+pub async fn open(path: &Path) -> io::Result<impl AsyncRead + Unpin> {
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+
+    Ok(tokio::fs::File::open(path).await?.compat())
+}
+
+/// Runs blocking work, such as walking a directory tree, off the async executor's threads.
+#[cfg(not(feature = "tokio"))]
+pub async fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    async_std::task::spawn_blocking(f).await
+}
+
+/// Runs blocking work, such as walking a directory tree, off the async executor's threads.
+#[cfg(feature = "tokio")]
+pub async fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+/// Runs a future to completion on the current thread, for callers outside any async context
+/// such as benchmarks.
+#[cfg(not(feature = "tokio"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
+
+/// Runs a future to completion on the current thread, for callers outside any async context
+/// such as benchmarks.
+#[cfg(feature = "tokio")]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the tokio runtime")
+        .block_on(future)
+}
+
+/// Lets a future built on this runtime's primitives be polled by another executor, as tide's
+/// server does.
+#[cfg(not(feature = "tokio"))]
+pub fn compat<F: Future>(future: F) -> F {
+    future
+}
+
+/// Lets a future built on this runtime's primitives be polled by another executor, as tide's
+/// server does.
+#[cfg(feature = "tokio")]
+pub fn compat<F: Future>(future: F) -> async_compat::Compat<F> {
+    async_compat::Compat::new(future)
+}
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(not(feature = "tokio"))]
+    async_std::task::sleep(duration).await;
+
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+}
+
+/// Awaits `future`, giving up with a [`TimeoutError`] once `duration` has passed.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, TimeoutError> {
+    #[cfg(not(feature = "tokio"))]
+    return async_std::future::timeout(duration, future).await;
+
+    #[cfg(feature = "tokio")]
+    return tokio::time::timeout(duration, future).await;
+}
\ No newline at end of file
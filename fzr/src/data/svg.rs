@@ -0,0 +1,105 @@
+pub const SVG_MIME_TYPE: &str = "image/svg+xml";
+
+/// Whether `markup` is an SVG document, i.e. its root element is `<svg>`. Only an XML
+/// declaration, comments and a doctype may come before it.
+pub fn is_svg(markup: &str) -> bool {
+    root_tag(markup).is_some()
+}
+
+/// Reads `(width_px, height_px, view_box)` from an SVG's root element. Dimensions missing from
+/// `width` and `height`, or given in units other than pixels, are taken from the `viewBox`.
+pub fn svg_dimensions(markup: &str) -> (Option<u32>, Option<u32>, Option<String>) {
+    let tag = match root_tag(markup) {
+        Some(tag) => tag,
+        None => return (None, None, None),
+    };
+
+    let view_box = attribute(tag, "viewBox").map(str::to_string);
+    let view_box_size: Vec<f64> = view_box
+        .iter()
+        .flat_map(|view_box| view_box.split(|c: char| c.is_whitespace() || c == ','))
+        .filter(|value| !value.is_empty())
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    let (view_box_width, view_box_height) = match view_box_size[..] {
+        [_, _, width, height] => (to_px(width), to_px(height)),
+        _ => (None, None),
+    };
+
+    let width_px = attribute(tag, "width")
+        .and_then(parse_length)
+        .or(view_box_width);
+    let height_px = attribute(tag, "height")
+        .and_then(parse_length)
+        .or(view_box_height);
+
+    (width_px, height_px, view_box)
+}
+
+/// The attributes of the root `<svg ...>` start tag, if that's what the document starts with.
+fn root_tag(markup: &str) -> Option<&str> {
+    let mut rest = markup.trim_start_matches('\u{feff}').trim_start();
+
+    // Skip the prolog: an XML declaration, comments and a doctype, in any order.
+    loop {
+        let skipped = if rest.starts_with("<?") {
+            rest.find("?>").map(|end| &rest[end + 2..])
+        } else if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| &rest[end + 3..])
+        } else if rest.starts_with("<!") {
+            rest.find('>').map(|end| &rest[end + 1..])
+        } else {
+            break;
+        };
+
+        rest = skipped?.trim_start();
+    }
+
+    let tag = rest.strip_prefix("<svg")?;
+
+    if !tag.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+        return None;
+    }
+
+    tag.find('>').map(|end| &tag[..end])
+}
+
+/// Finds a quoted attribute's value in a start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+
+    while let Some(start) = rest.find(name) {
+        let preceded_by_space = rest[..start].ends_with(char::is_whitespace);
+        let after = rest[start + name.len()..].trim_start();
+
+        if preceded_by_space {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+                let value = &value[1..];
+
+                return value.find(quote).map(|end| &value[..end]);
+            }
+        }
+
+        rest = &rest[start + name.len()..];
+    }
+
+    None
+}
+
+/// Parses a length given in pixels, with or without the `px` suffix.
+fn parse_length(length: &str) -> Option<u32> {
+    let length = length.trim();
+    let number = length.strip_suffix("px").unwrap_or(length);
+
+    number.parse().ok().and_then(to_px)
+}
+
+fn to_px(value: f64) -> Option<u32> {
+    if value.is_finite() && value >= 0.0 && value <= u32::MAX as f64 {
+        Some(value.round() as u32)
+    } else {
+        None
+    }
+}
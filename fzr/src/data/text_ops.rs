@@ -0,0 +1,167 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+
+use crate::data::content::{JsonKind, LineEndings};
+
+pub const JSON_MIME_TYPE: &str = "application/json";
+
+pub const TEXT_MIME_TYPE: &str = "text/plain";
+
+/// Texts shorter than this are stored uncompressed, since zstd's framing would outweigh savings.
+pub const MIN_COMPRESSIBLE_BYTES: usize = 64;
+
+/// zstd compression level; 0 selects the library default.
+const ZSTD_LEVEL: i32 = 0;
+
+/// Decodes text stored in an encoding other than UTF-8, returning it with the detected
+/// encoding's name, or `None` if the bytes look like binary data rather than text.
+pub fn decode_non_utf8(buffer: &[u8]) -> Option<(String, String)> {
+    let encoding = match Encoding::for_bom(buffer) {
+        Some((encoding, _)) => encoding,
+        None => {
+            // Legacy single-byte encodings map nearly every byte, so reject control bytes that
+            // real text wouldn't contain before guessing.
+            if buffer.iter().any(|&byte| is_binary_control(byte)) {
+                return None;
+            }
+
+            let mut detector = EncodingDetector::new();
+            detector.feed(buffer, true);
+            detector.guess(None, true)
+        }
+    };
+
+    let (string, encoding, had_errors) = encoding.decode(buffer);
+
+    if had_errors {
+        return None;
+    }
+
+    Some((string.into_owned(), encoding.name().to_string()))
+}
+
+/// One line of a line-based diff, without its line ending.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiffLine {
+    Added(String),
+    Removed(String),
+    Unchanged(String),
+}
+
+/// Diffs `old` against `new` line by line, in order, with each run of removed lines listed before
+/// the lines that replace it.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.value().trim_end_matches(['\r', '\n']).to_string();
+
+            match change.tag() {
+                ChangeTag::Insert => DiffLine::Added(line),
+                ChangeTag::Delete => DiffLine::Removed(line),
+                ChangeTag::Equal => DiffLine::Unchanged(line),
+            }
+        })
+        .collect()
+}
+
+/// Finds which line endings `string` uses, counting a CR directly followed by LF as one CRLF.
+pub fn line_endings(string: &str) -> LineEndings {
+    let (mut lf, mut crlf, mut cr) = (false, false, false);
+    let mut bytes = string.bytes().peekable();
+
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\n' => lf = true,
+            b'\r' if bytes.next_if_eq(&b'\n').is_some() => crlf = true,
+            b'\r' => cr = true,
+            _ => {}
+        }
+    }
+
+    match (lf, crlf, cr) {
+        (false, false, false) => LineEndings::None,
+        (true, false, false) => LineEndings::Lf,
+        (false, true, false) => LineEndings::Crlf,
+        (false, false, true) => LineEndings::Cr,
+        _ => LineEndings::Mixed,
+    }
+}
+
+/// Converts CRLF and lone CR line endings to LF.
+pub fn normalize_line_endings(string: &str) -> String {
+    string.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Compresses text with zstd, or returns `None` if it's too small or compressing wouldn't make it
+/// smaller.
+pub fn compress(string: &str) -> Result<Option<Vec<u8>>> {
+    if string.len() < MIN_COMPRESSIBLE_BYTES {
+        return Ok(None);
+    }
+
+    let compressed = zstd::encode_all(string.as_bytes(), ZSTD_LEVEL)?;
+
+    Ok(Some(compressed).filter(|compressed| compressed.len() < string.len()))
+}
+
+pub fn decompress(buffer: &[u8]) -> Result<String> {
+    let decompressed = zstd::decode_all(buffer)?;
+
+    Ok(String::from_utf8(decompressed)?)
+}
+
+/// Parses `string` as JSON, returning its top-level kind and key or element count if it holds an
+/// object or array.
+pub fn json_shape(string: &str) -> Option<(JsonKind, u64)> {
+    // Only objects and arrays are worth a full parse.
+    if !string.trim_start().starts_with(['{', '[']) {
+        return None;
+    }
+
+    match serde_json::from_str(string).ok()? {
+        Value::Object(object) => Some((JsonKind::Object, object.len() as u64)),
+        Value::Array(array) => Some((JsonKind::Array, array.len() as u64)),
+        _ => None,
+    }
+}
+
+fn is_binary_control(byte: u8) -> bool {
+    byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_non_utf8() {
+        assert_eq!(
+            decode_non_utf8(b"Caf\xe9 cr\xe8me br\xfbl\xe9e, na\xefve r\xe9sum\xe9\n"),
+            Some((
+                "Café crème brûlée, naïve résumé\n".to_string(),
+                "windows-1252".to_string()
+            ))
+        );
+        assert_eq!(
+            decode_non_utf8(b"\xff\xfeh\0i\0"),
+            Some(("hi".to_string(), "UTF-16LE".to_string()))
+        );
+        assert_eq!(
+            decode_non_utf8(b"\x00\x9f\x92\x96\xc3\x28\xa0\xa1\xfe\xff"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(LineEndings::None, line_endings("one line"));
+        assert_eq!(LineEndings::Lf, line_endings("one\ntwo\n"));
+        assert_eq!(LineEndings::Crlf, line_endings("one\r\ntwo\r\n"));
+        assert_eq!(LineEndings::Cr, line_endings("one\rtwo\r"));
+        assert_eq!(LineEndings::Mixed, line_endings("one\r\ntwo\n"));
+    }
+}
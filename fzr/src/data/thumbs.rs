@@ -1,17 +1,16 @@
-use async_std::sync::{Arc, RwLock};
-use async_std::task::sleep;
 use crossbeam_utils::atomic::AtomicCell;
 use iced::Subscription;
 use iced_futures::futures::{stream, StreamExt};
 use image::io::Reader as ImageReader;
 use image::GenericImageView;
-use log::{debug, error};
 use par_stream::{ParMapUnordered, ParStreamExt};
 use std::hash::Hash;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
+use tracing::{debug, error};
 
-use crate::data::content::{ImageMetadata, PathThumb};
+use crate::data::content::{Fingerprint, ImageMetadata, PathThumb};
+use crate::data::runtime::{sleep, Arc, RwLock};
 
 pub struct ProcessThumbs {
     paths: Arc<RwLock<Vec<PathBuf>>>,
@@ -84,9 +83,17 @@ fn resize_image(path: &Path) -> Option<PathThumb> {
 
             let metadata = ImageMetadata {
                 size_bytes: 0, // Thumbnail size doesn't matter because it's not persisted
+                fingerprint: Fingerprint::of(&image),
                 mime_type,
                 width_px,
                 height_px,
+                exif: None,
+                phash: None,
+                dominant_colors: Vec::new(),
+                blurhash: None,
+                frame_count: None,
+                loop_count: None,
+                original_format: None,
             };
 
             Some(PathThumb {
@@ -96,10 +103,7 @@ fn resize_image(path: &Path) -> Option<PathThumb> {
             })
         }
         Err(err) => {
-            error!(
-                "Error decoding image at path: {:?}\nError was: {}",
-                &path, err
-            );
+            error!(path = ?&path, error = %err, "Error decoding image");
             None
         }
     }
@@ -139,7 +143,7 @@ where
                         let paths_stream =
                             stream::iter(paths_vec).par_map_unordered(None, move |path| {
                                 let remaining = Arc::clone(&remaining_ref);
-                                debug!("Processing {:.2?}, Path: {:?}", &start.elapsed(), &path);
+                                debug!(path = ?&path, elapsed = ?start.elapsed(), "Processing");
                                 move || {
                                     if let Some(thumb) = resize_image(&path) {
                                         remaining.fetch_sub(1);
@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Result};
+use matroska::{Matroska, Settings};
+use mp4::{Mp4Reader, TrackType};
+use std::io::Cursor;
+
+/// Video container MIME types (as reported by `infer`) that can be probed.
+const VIDEO_MIME_TYPES: [&str; 3] = ["video/mp4", "video/webm", "video/x-matroska"];
+
+pub fn is_video(mime_type: &str) -> bool {
+    VIDEO_MIME_TYPES.contains(&mime_type)
+}
+
+/// Reads `(width_px, height_px, duration_ms)` from the container headers of an in-memory video.
+pub fn video_dimensions(buffer: &[u8], mime_type: &str) -> Result<(u32, u32, u64)> {
+    match mime_type {
+        "video/mp4" => {
+            let reader = Mp4Reader::read_header(Cursor::new(buffer), buffer.len() as u64)?;
+            let duration_ms = reader.duration().as_millis() as u64;
+
+            let track = reader
+                .tracks()
+                .values()
+                .find(|track| matches!(track.track_type(), Ok(TrackType::Video)))
+                .ok_or_else(|| anyhow!("No video track found in mp4 container"))?;
+
+            Ok((track.width() as u32, track.height() as u32, duration_ms))
+        }
+        "video/webm" | "video/x-matroska" => {
+            let matroska = Matroska::open(Cursor::new(buffer))?;
+            let duration_ms = matroska
+                .info
+                .duration
+                .map_or(0, |duration| duration.as_millis() as u64);
+
+            let (width_px, height_px) = matroska
+                .tracks
+                .iter()
+                .find_map(|track| match &track.settings {
+                    Settings::Video(video) => Some((video.pixel_width, video.pixel_height)),
+                    _ => None,
+                })
+                .ok_or_else(|| anyhow!("No video track found in matroska container"))?;
+
+            Ok((width_px as u32, height_px as u32, duration_ms))
+        }
+        _ => Err(anyhow!("Unsupported video MIME type: {}", mime_type)),
+    }
+}
@@ -0,0 +1 @@
+pub mod data;
\ No newline at end of file
@@ -1,3 +1,133 @@
-fn main() {
-    println!("Fuse Dev");
+use clap::{Parser, Subcommand};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+use fzr::data::content_cid::ContentCid;
+use fzr::data::error::FzrError;
+use fzr::data::gateway;
+use fzr::data::ipfs_client::{IpfsClient, IpfsClientConfig};
+use fzr::data::ipfs_ops::{load_file, store_directory_with_options, store_file, DirectoryOptions};
+use fzr::data::runtime::{self, fs, Arc, RwLock};
+
+/// Stores files in IPFS and loads them back by CID.
+#[derive(Debug, Parser)]
+#[clap(name = "fzr", version)]
+struct Cli {
+    /// Path of the local block store. Defaults to the platform's local data directory.
+    #[clap(long, global = true)]
+    repo: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Stores a file and prints its CID.
+    Store { path: PathBuf },
+    /// Recursively stores a directory and prints its manifest of relative paths to CIDs as JSON.
+    StoreDir {
+        path: PathBuf,
+        /// Also store dot-prefixed files and directories.
+        #[clap(long)]
+        include_hidden: bool,
+        /// Maximum number of files stored at once.
+        #[clap(long)]
+        concurrency: Option<usize>,
+        /// Store what symlinks point to, instead of listing them as skipped.
+        #[clap(long)]
+        follow_symlinks: bool,
+        /// Only store files whose relative path matches this glob, such as `**/*.jpg`. May be
+        /// repeated.
+        #[clap(long)]
+        include: Vec<String>,
+        /// Leave out files whose relative path matches this glob, even if included. May be
+        /// repeated.
+        #[clap(long)]
+        exclude: Vec<String>,
+    },
+    /// Loads content by CID and writes it to a file, or stdout by default.
+    Load {
+        cid: ContentCid,
+        /// File to write the content to.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Serves stored content over HTTP at `/ipfs/<cid>`, for viewing in a browser.
+    Serve {
+        #[clap(long, default_value = "8080")]
+        port: u16,
+    },
+}
+
+#[runtime::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(cli).await {
+        eprintln!("fzr: {}", err);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), FzrError> {
+    let config = IpfsClientConfig {
+        repo_path: cli.repo,
+        ..IpfsClientConfig::default()
+    };
+    let ipfs_client = Arc::new(RwLock::new(IpfsClient::with_config(config).await?));
+
+    match cli.command {
+        Command::Store { path } => match store_file(path.clone(), ipfs_client.clone()).await? {
+            Some(cid) => println!("{}", cid),
+            None => {
+                return Err(FzrError::UnsupportedFormat(format!(
+                    "Nothing was stored for path: {:?}",
+                    path
+                )))
+            }
+        },
+        Command::StoreDir {
+            path,
+            include_hidden,
+            concurrency,
+            follow_symlinks,
+            include,
+            exclude,
+        } => {
+            let defaults = DirectoryOptions::default();
+            let options = DirectoryOptions {
+                skip_hidden: !include_hidden,
+                concurrency: concurrency.unwrap_or(defaults.concurrency),
+                follow_symlinks,
+                include,
+                exclude,
+            };
+
+            let manifest = store_directory_with_options(path, ipfs_client.clone(), options).await?;
+            let json = serde_json::to_string_pretty(&manifest)
+                .map_err(|err| FzrError::Decode(Arc::new(err.into())))?;
+            println!("{}", json);
+        }
+        Command::Load { cid, output } => {
+            let bytes = Vec::try_from(&load_file(cid, ipfs_client.clone()).await?)?;
+
+            match output {
+                Some(path) => fs::write(path, bytes).await?,
+                None => io::stdout().write_all(&bytes)?,
+            }
+        }
+        Command::Serve { port } => {
+            eprintln!("Serving on http://127.0.0.1:{}/ipfs/<cid>", port);
+            gateway::app(ipfs_client.clone())
+                .listen(format!("127.0.0.1:{}", port))
+                .await?;
+        }
+    }
+
+    // Stored blocks may still be buffered by the store until flushed.
+    ipfs_client.read().await.flush().await?;
+
+    Ok(())
 }
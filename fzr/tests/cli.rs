@@ -0,0 +1,102 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn fzr(repo: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("fzr").unwrap();
+    cmd.arg("--repo").arg(repo);
+    cmd
+}
+
+#[test]
+fn test_store_load() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let repo = dir.path().join("repo");
+    let input = dir.path().join("howdy.txt");
+    fs::write(&input, "howdy")?;
+
+    let output = fzr(&repo).arg("store").arg(&input).assert().success();
+    let cid = String::from_utf8(output.get_output().stdout.clone())?;
+    let cid = cid.trim();
+
+    fzr(&repo)
+        .args(["load", cid])
+        .assert()
+        .success()
+        .stdout("howdy");
+
+    let loaded = dir.path().join("loaded.txt");
+    fzr(&repo)
+        .args(["load", cid, "--output"])
+        .arg(&loaded)
+        .assert()
+        .success();
+    assert_eq!("howdy", fs::read_to_string(loaded)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_store_dir() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let root = dir.path().join("photos");
+    fs::create_dir_all(root.join("2021/summer"))?;
+    fs::write(root.join("index.txt"), "howdy")?;
+    fs::write(root.join("2021/summer/beach.txt"), "sandy")?;
+    fs::write(root.join(".hidden.txt"), "shh")?;
+
+    let output = fzr(&dir.path().join("repo"))
+        .args(["store-dir", "--concurrency", "2"])
+        .arg(&root)
+        .assert()
+        .success();
+    let manifest: Value = serde_json::from_slice(&output.get_output().stdout)?;
+    let entries = manifest["entries"].as_object().unwrap();
+
+    let mut paths: Vec<_> = entries.keys().map(String::as_str).collect();
+    paths.sort_unstable();
+    assert_eq!(vec!["2021/summer/beach.txt", "index.txt"], paths);
+    assert!(entries["index.txt"]["Stored"].is_string());
+
+    let output = fzr(&dir.path().join("repo"))
+        .args(["store-dir", "--include-hidden"])
+        .arg(&root)
+        .assert()
+        .success();
+    let manifest: Value = serde_json::from_slice(&output.get_output().stdout)?;
+    assert!(manifest["entries"].get(".hidden.txt").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_store_missing_file() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+
+    fzr(&dir.path().join("repo"))
+        .arg("store")
+        .arg(dir.path().join("missing.txt"))
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(contains("fzr: I/O error"));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_invalid_cid() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+
+    fzr(&dir.path().join("repo"))
+        .args(["load", "not-a-cid"])
+        .assert()
+        .failure()
+        .stderr(contains("not-a-cid"));
+
+    Ok(())
+}
\ No newline at end of file